@@ -23,6 +23,8 @@ pub struct RawSyncMessage {
     pub need: Vec<ChangeHash>,
     pub have: Vec<RawSyncHave>,
     pub changes: Vec<BinaryChange>,
+    #[serde(default)]
+    pub document: Option<BinaryDocument>,
 }
 
 impl TryFrom<SyncMessage> for RawSyncMessage {
@@ -44,6 +46,7 @@ impl TryFrom<SyncMessage> for RawSyncMessage {
             need: value.need,
             have,
             changes,
+            document: value.document.map(BinaryDocument),
         })
     }
 }
@@ -67,6 +70,7 @@ impl TryFrom<RawSyncMessage> for SyncMessage {
             need: value.need,
             have,
             changes,
+            document: value.document.map(|d| d.0),
         })
     }
 }
@@ -117,7 +117,7 @@ impl ActorMap {
         }
     }
 
-    fn cmp_opid(&self, op1: &OpId, op2: &OpId) -> Ordering {
+    pub fn cmp_opid(&self, op1: &OpId, op2: &OpId) -> Ordering {
         if op1.0 == op2.0 {
             let actor1 = &self.0[(op1.1).0];
             let actor2 = &self.0[(op2.1).0];
@@ -13,7 +13,7 @@ use crate::{
     error::AutomergeError,
     event_handlers::{EventHandlerId, EventHandlers},
     op_handle::OpHandle,
-    op_set::OpSet,
+    op_set::{CursorBias, OpSet},
     patches::{generate_from_scratch_diff, IncrementalPatch},
     Change, EventHandler,
 };
@@ -470,6 +470,34 @@ impl Backend {
     pub fn remove_event_handler(&mut self, id: EventHandlerId) -> bool {
         self.event_handlers.remove_handler(id)
     }
+
+    /// Forget the history kept to reorder concurrent moves, for the moves in the history of
+    /// `stable_heads`.
+    ///
+    /// A move which arrives after moves with greater op IDs undoes and redoes them, so every move
+    /// is kept until it is known that no such move can arrive. That is the case once every change
+    /// still to be applied to this backend depends on `stable_heads`: every peer has seen them,
+    /// and every change a peer made before seeing them has been applied here.
+    ///
+    /// Nothing else forgets this history, so it grows with every move applied to this backend
+    /// unless this is called.
+    pub fn compact_moves(&mut self, stable_heads: &[amp::ChangeHash]) {
+        let max_op = stable_heads
+            .iter()
+            .filter_map(|hash| self.get_change_by_hash(hash))
+            .map(Change::max_op)
+            .max();
+        if let Some(max_op) = max_op {
+            self.op_set.compact_moves(max_op);
+        }
+    }
+
+    /// Choose which neighbour a cursor resolves to once the element it points at has been
+    /// deleted. The index of every cursor is updated, and is sent to the frontend with the next
+    /// patch which changes the sequence the cursor points into, or with `get_patch`.
+    pub fn set_cursor_bias(&mut self, bias: CursorBias) {
+        self.op_set.set_cursor_bias(bias);
+    }
 }
 
 #[cfg(test)]
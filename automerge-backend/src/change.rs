@@ -38,6 +38,12 @@ const HASH_BYTES: usize = 32;
 const BLOCK_TYPE_DOC: u8 = 0;
 const BLOCK_TYPE_CHANGE: u8 = 1;
 const BLOCK_TYPE_DEFLATE: u8 = 2;
+// Chunks which contain move ops have their own types, so that versions which predate moves
+// reject them instead of silently dropping the ops from the first move onwards. Chunks without
+// moves keep the original types, and so their bytes and hashes.
+const BLOCK_TYPE_DOC_WITH_MOVES: u8 = 3;
+const BLOCK_TYPE_CHANGE_WITH_MOVES: u8 = 4;
+const BLOCK_TYPE_DEFLATE_WITH_MOVES: u8 = 5;
 const CHUNK_START: usize = 8;
 const HASH_RANGE: Range<usize> = 4..8;
 
@@ -65,7 +71,11 @@ fn encode(change: &amp::Change) -> Change {
 
     bytes.extend(vec![0, 0, 0, 0]); // we dont know the hash yet so fill in a fake
 
-    bytes.push(BLOCK_TYPE_CHANGE);
+    bytes.push(if has_moves(change) {
+        BLOCK_TYPE_CHANGE_WITH_MOVES
+    } else {
+        BLOCK_TYPE_CHANGE
+    });
 
     leb128::write::unsigned(&mut bytes, chunk.bytes.len() as u64).unwrap();
 
@@ -107,6 +117,13 @@ fn encode(change: &amp::Change) -> Change {
     }
 }
 
+fn has_moves(change: &amp::Change) -> bool {
+    change
+        .operations
+        .iter()
+        .any(|op| matches!(op.action, amp::OpType::Move(_)))
+}
+
 struct ChunkIntermediate {
     bytes: Vec<u8>,
     body: Range<usize>,
@@ -195,7 +212,13 @@ impl ChangeBytes {
                 if uncompressed.len() > DEFLATE_MIN_SIZE {
                     let mut result = Vec::with_capacity(uncompressed.len());
                     result.extend(&uncompressed[0..8]);
-                    result.push(BLOCK_TYPE_DEFLATE);
+                    result.push(
+                        if uncompressed[PREAMBLE_BYTES] == BLOCK_TYPE_CHANGE_WITH_MOVES {
+                            BLOCK_TYPE_DEFLATE_WITH_MOVES
+                        } else {
+                            BLOCK_TYPE_DEFLATE
+                        },
+                    );
                     let mut deflater =
                         DeflateEncoder::new(&uncompressed[body_start..], Compression::default());
                     let mut deflated = Vec::new();
@@ -254,7 +277,7 @@ impl Change {
         self.start_op + (len as u64) - 1
     }
 
-    fn message(&self) -> Option<String> {
+    pub(crate) fn message(&self) -> Option<String> {
         let m = &self.bytes.uncompressed()[self.message.clone()];
         if m.is_empty() {
             None
@@ -280,6 +303,7 @@ impl Change {
                         InternalOpType::Del => OpType::Del(nonzero!(1_u32)),
                         InternalOpType::Inc(i) => OpType::Inc(i),
                         InternalOpType::Set(value) => OpType::Set(value),
+                        InternalOpType::Move(source) => OpType::Move(source),
                     },
                     obj: op.obj.clone().into_owned(),
                     key: op.key.into_owned(),
@@ -494,16 +518,26 @@ fn decode_columns(
 
 fn decode_block(bytes: &[u8], changes: &mut Vec<Change>) -> Result<(), decoding::Error> {
     match bytes[PREAMBLE_BYTES] {
-        BLOCK_TYPE_DOC => {
+        BLOCK_TYPE_DOC | BLOCK_TYPE_DOC_WITH_MOVES => {
             changes.extend(decode_document(bytes)?);
             Ok(())
         }
-        BLOCK_TYPE_CHANGE | BLOCK_TYPE_DEFLATE => {
+        BLOCK_TYPE_CHANGE
+        | BLOCK_TYPE_DEFLATE
+        | BLOCK_TYPE_CHANGE_WITH_MOVES
+        | BLOCK_TYPE_DEFLATE_WITH_MOVES => {
             changes.push(decode_change(bytes.to_vec())?);
             Ok(())
         }
         found => Err(decoding::Error::WrongType {
-            expected_one_of: vec![BLOCK_TYPE_DOC, BLOCK_TYPE_CHANGE, BLOCK_TYPE_DEFLATE],
+            expected_one_of: vec![
+                BLOCK_TYPE_DOC,
+                BLOCK_TYPE_CHANGE,
+                BLOCK_TYPE_DEFLATE,
+                BLOCK_TYPE_DOC_WITH_MOVES,
+                BLOCK_TYPE_CHANGE_WITH_MOVES,
+                BLOCK_TYPE_DEFLATE_WITH_MOVES,
+            ],
             found,
         }),
     }
@@ -511,17 +545,19 @@ fn decode_block(bytes: &[u8], changes: &mut Vec<Change>) -> Result<(), decoding:
 
 fn decode_change(bytes: Vec<u8>) -> Result<Change, decoding::Error> {
     let (chunktype, body) = decode_header_without_hash(&bytes)?;
-    let bytes = if chunktype == BLOCK_TYPE_DEFLATE {
-        decompress_chunk(0..PREAMBLE_BYTES, body, bytes)?
-    } else {
-        ChangeBytes::Uncompressed(bytes)
+    let bytes = match chunktype {
+        BLOCK_TYPE_DEFLATE => decompress_chunk(0..PREAMBLE_BYTES, body, bytes, BLOCK_TYPE_CHANGE)?,
+        BLOCK_TYPE_DEFLATE_WITH_MOVES => {
+            decompress_chunk(0..PREAMBLE_BYTES, body, bytes, BLOCK_TYPE_CHANGE_WITH_MOVES)?
+        }
+        _ => ChangeBytes::Uncompressed(bytes),
     };
 
     let (chunktype, hash, body) = decode_header(bytes.uncompressed())?;
 
-    if chunktype != BLOCK_TYPE_CHANGE {
+    if chunktype != BLOCK_TYPE_CHANGE && chunktype != BLOCK_TYPE_CHANGE_WITH_MOVES {
         return Err(decoding::Error::WrongType {
-            expected_one_of: vec![BLOCK_TYPE_CHANGE],
+            expected_one_of: vec![BLOCK_TYPE_CHANGE, BLOCK_TYPE_CHANGE_WITH_MOVES],
             found: chunktype,
         });
     }
@@ -562,13 +598,14 @@ fn decompress_chunk(
     preamble: Range<usize>,
     body: Range<usize>,
     compressed: Vec<u8>,
+    chunktype: u8,
 ) -> Result<ChangeBytes, decoding::Error> {
     let mut decoder = DeflateDecoder::new(&compressed[body]);
     let mut decompressed = Vec::new();
     decoder.read_to_end(&mut decompressed)?;
     let mut result = Vec::with_capacity(decompressed.len() + preamble.len());
     result.extend(&compressed[preamble]);
-    result.push(BLOCK_TYPE_CHANGE);
+    result.push(chunktype);
     leb128::write::unsigned::<Vec<u8>>(&mut result, decompressed.len() as u64).unwrap();
     result.extend(decompressed);
     Ok(ChangeBytes::Compressed {
@@ -749,10 +786,9 @@ fn pop_block(bytes: &[u8]) -> Result<Option<Range<usize>>, decoding::Error> {
 fn decode_document(bytes: &[u8]) -> Result<Vec<Change>, decoding::Error> {
     let (chunktype, _hash, mut cursor) = decode_header(bytes)?;
 
-    // chunktype == 0 is a document, chunktype = 1 is a change
-    if chunktype > 0 {
+    if chunktype != BLOCK_TYPE_DOC && chunktype != BLOCK_TYPE_DOC_WITH_MOVES {
         return Err(decoding::Error::WrongType {
-            expected_one_of: vec![0],
+            expected_one_of: vec![BLOCK_TYPE_DOC, BLOCK_TYPE_DOC_WITH_MOVES],
             found: chunktype,
         });
     }
@@ -957,7 +993,11 @@ pub(crate) fn encode_document(changes: &[amp::Change]) -> Result<Vec<u8>, encodi
 
     bytes.extend(&MAGIC_BYTES);
     bytes.extend(vec![0, 0, 0, 0]); // we dont know the hash yet so fill in a fake
-    bytes.push(BLOCK_TYPE_DOC);
+    bytes.push(if changes.iter().any(has_moves) {
+        BLOCK_TYPE_DOC_WITH_MOVES
+    } else {
+        BLOCK_TYPE_DOC
+    });
 
     let mut chunk = Vec::new();
 
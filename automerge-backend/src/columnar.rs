@@ -140,6 +140,10 @@ impl<'a> Iterator for OperationIterator<'a> {
             Action::MakeTable => InternalOpType::Make(amp::ObjType::Table),
             Action::Del => InternalOpType::Del,
             Action::Inc => InternalOpType::Inc(value.to_i64()?),
+            Action::Move => match value {
+                amp::ScalarValue::Cursor(source) => InternalOpType::Move(source),
+                _ => return None,
+            },
         };
         Some(ExpandedOp {
             action,
@@ -181,6 +185,10 @@ impl<'a> Iterator for DocOpIterator<'a> {
             Action::MakeTable => InternalOpType::Make(amp::ObjType::Table),
             Action::Del => InternalOpType::Del,
             Action::Inc => InternalOpType::Inc(value.to_i64()?),
+            Action::Move => match value {
+                amp::ScalarValue::Cursor(source) => InternalOpType::Move(source),
+                _ => return None,
+            },
         };
         Some(DocOp {
             actor,
@@ -943,6 +951,12 @@ impl DocOpEncoder {
                     self.val.append_value(&amp::ScalarValue::Int(*val), actors);
                     Action::Inc
                 }
+                InternalOpType::Move(source) => {
+                    // the source is stored in the same columns as the target of a cursor
+                    self.val
+                        .append_value(&amp::ScalarValue::Cursor(source.clone()), actors);
+                    Action::Move
+                }
                 InternalOpType::Del => {
                     // FIXME throw error
                     self.val.append_null();
@@ -1053,6 +1067,12 @@ impl ColumnEncoder {
                 self.val.append_value(&amp::ScalarValue::Int(*val), actors);
                 Action::Inc
             }
+            InternalOpType::Move(source) => {
+                // the source is stored in the same columns as the target of a cursor
+                self.val
+                    .append_value(&amp::ScalarValue::Cursor(source.clone()), actors);
+                Action::Move
+            }
             InternalOpType::Del => {
                 self.val.append_null();
                 Action::Del
@@ -1162,8 +1182,14 @@ pub(crate) enum Action {
     MakeText,
     Inc,
     MakeTable,
+    Move,
 }
-const ACTIONS: [Action; 7] = [
+/// The actions in the order of their number in the action column.
+///
+/// `Move` (7) was added after the others. Versions which predate it stop reading a change's ops at
+/// the first action they don't know, so changes and documents containing moves are written as
+/// chunk types which those versions reject.
+const ACTIONS: [Action; 8] = [
     Action::MakeMap,
     Action::Set,
     Action::MakeList,
@@ -1171,6 +1197,7 @@ const ACTIONS: [Action; 7] = [
     Action::MakeText,
     Action::Inc,
     Action::MakeTable,
+    Action::Move,
 ];
 
 impl Decodable for Action {
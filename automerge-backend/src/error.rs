@@ -60,6 +60,10 @@ pub enum AutomergeError {
     InvalidCursor { opid: amp::OpId },
     #[error("A compressed chunk could not be decompressed")]
     BadCompressedChunk,
+    #[error("Sync message failed authentication")]
+    SyncMessageAuthenticationFailed,
+    #[error("Sync message with counter {counter} was already received or is out of order")]
+    SyncMessageReplayed { counter: u64 },
 }
 
 #[derive(Error, Debug)]
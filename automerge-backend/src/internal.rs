@@ -54,6 +54,7 @@ pub(crate) enum InternalOpType {
     Del,
     Inc(i64),
     Set(amp::ScalarValue),
+    Move(amp::OpId),
 }
 
 impl Key {
@@ -97,6 +98,7 @@ impl From<&InternalOpType> for amp::OpType {
             InternalOpType::Make(ot) => amp::OpType::Make(*ot),
             InternalOpType::Set(v) => amp::OpType::Set(v.clone()),
             InternalOpType::Inc(i) => amp::OpType::Inc(*i),
+            InternalOpType::Move(source) => amp::OpType::Move(source.clone()),
         }
     }
 }
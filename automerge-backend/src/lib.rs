@@ -54,7 +54,11 @@ pub use decoding::Error as DecodingError;
 pub use encoding::Error as EncodingError;
 pub use error::AutomergeError;
 pub use event_handlers::{ChangeEventHandler, EventHandler, EventHandlerId};
-pub use sync::{BloomFilter, SyncHave, SyncMessage, SyncState};
+pub use op_set::CursorBias;
+pub use sync::{
+    BloomFilter, DocumentSet, DocumentSetMessage, DocumentSetSyncState, EphemeralManager,
+    EphemeralMessage, SyncHave, SyncMessage, SyncState,
+};
 
 #[cfg(test)]
 mod tests {
@@ -8,6 +8,7 @@ use crate::{
     concurrent_operations::ConcurrentOperations,
     internal::{ElementId, Key, OpId},
     op_handle::OpHandle,
+    op_set::CursorBias,
     ordered_set::{OrderedSet, SkipList},
 };
 
@@ -78,6 +79,21 @@ impl ObjState {
         index.map(|i| i + 1)
     }
 
+    /// The index a cursor pointing at element `id` resolves to: the index of the element if it is
+    /// visible, otherwise the index of its nearest visible neighbour on the side given by `bias`,
+    /// or on the other side if there is no neighbour on that side
+    pub fn cursor_index(&self, id: OpId, bias: CursorBias) -> usize {
+        // the number of visible elements before `id`
+        let before = self.index_of(id).unwrap_or(0);
+        if self.seq.len == 0 || self.seq.index_of(&id).is_some() {
+            return before;
+        }
+        match bias {
+            CursorBias::Left => before.saturating_sub(1),
+            CursorBias::Right => before.min(self.seq.len - 1),
+        }
+    }
+
     fn get_previous(&self, element: &ElementId) -> Option<ElementId> {
         let parent_id = match self.get_parent(element) {
             Some(p) => p,
@@ -18,6 +18,8 @@ pub(crate) struct OpHandle {
     pub id: OpId,
     pub op: InternalOp,
     pub delta: i64,
+    /// If this handle places a moved value, the ID of the op which created the value
+    pub origin: Option<OpId>,
 }
 
 impl OpHandle {
@@ -34,6 +36,7 @@ impl OpHandle {
                     id,
                     op: internal_op,
                     delta: 0,
+                    origin: None,
                 }
             })
             .collect()
@@ -51,11 +54,49 @@ impl OpHandle {
 
     pub fn child(&self) -> Option<ObjectId> {
         match &self.action {
-            InternalOpType::Make(_) => Some(self.id.into()),
+            InternalOpType::Make(_) => Some(self.origin.unwrap_or(self.id).into()),
             _ => None,
         }
     }
 
+    /// The ID of the op which created the value this handle places in the document
+    pub fn origin(&self) -> OpId {
+        self.origin.unwrap_or(self.id)
+    }
+
+    /// A handle which places the value of this handle at the location given by the move op `mv`
+    pub fn placed_by(&self, mv: &OpHandle) -> OpHandle {
+        OpHandle {
+            id: mv.id,
+            op: InternalOp {
+                action: self.action.clone(),
+                obj: mv.obj,
+                key: mv.key.clone(),
+                pred: Vec::new(),
+                insert: mv.insert,
+            },
+            delta: self.delta,
+            origin: Some(self.origin()),
+        }
+    }
+
+    /// A copy of this op which applies to the value placed by `target` rather than to the values
+    /// in its own pred
+    pub fn applied_to(&self, target: &OpHandle, action: InternalOpType) -> OpHandle {
+        OpHandle {
+            id: self.id,
+            op: InternalOp {
+                action,
+                obj: target.obj,
+                key: target.operation_key().into_owned(),
+                pred: vec![target.id],
+                insert: false,
+            },
+            delta: 0,
+            origin: None,
+        }
+    }
+
     pub fn operation_key(&self) -> Cow<Key> {
         if self.insert {
             Cow::Owned(self.id.into())
@@ -6,7 +6,10 @@
 //! document::state) the implementation fetches the root object ID's history
 //! and then recursively walks through the tree of histories constructing the
 //! state. Obviously this is not very efficient.
-use std::collections::{HashMap, HashSet};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
 
 use automerge_protocol as amp;
 use fxhash::FxBuildHasher;
@@ -16,7 +19,7 @@ use tracing::instrument;
 use crate::{
     actor_map::ActorMap,
     error::AutomergeError,
-    internal::{InternalOpType, Key, ObjectId},
+    internal::{InternalOpType, Key, ObjectId, OpId},
     object_store::ObjState,
     op_handle::OpHandle,
     ordered_set::OrderedSet,
@@ -45,6 +48,18 @@ pub(crate) struct OpSet {
     pub deps: HashSet<amp::ChangeHash>,
     pub max_op: u64,
     cursors: HashMap<ObjectId, Vec<CursorState>>,
+    /// Which neighbour cursors pointing at deleted elements resolve to
+    cursor_bias: CursorBias,
+    /// The move ops which have been applied, in the order of their op IDs. This is only trimmed by
+    /// `compact_moves`
+    moves: Vec<MoveRecord>,
+    /// The ID of the op which created the value moved by each move op
+    move_sources: HashMap<OpId, OpId>,
+    /// The values which have been moved, by the ID of the op which created them
+    moved: HashMap<OpId, MovedValue>,
+    /// The object and key of every op which places a value, so that the value a move refers to
+    /// can be found. This is only built once the first move op is applied.
+    value_keys: Option<HashMap<OpId, (ObjectId, Key)>>,
 }
 
 impl Default for OpSet {
@@ -63,6 +78,11 @@ impl OpSet {
             max_op: 0,
             deps: HashSet::default(),
             cursors: HashMap::new(),
+            cursor_bias: CursorBias::default(),
+            moves: Vec::new(),
+            move_sources: HashMap::new(),
+            moved: HashMap::new(),
+            value_keys: None,
         }
     }
 
@@ -92,6 +112,11 @@ impl OpSet {
         actors: &mut ActorMap,
         patch: &mut IncrementalPatch,
     ) -> Result<(), AutomergeError> {
+        if let InternalOpType::Move(ref source) = op.action {
+            let source = actors.import_opid(source);
+            return self.apply_move(op, source, actors, patch);
+        }
+
         if let (Some(child), Some(obj_type)) = (op.child(), op.obj_type()) {
             //let child = actors.import_obj(child);
             self.objs.insert(child, ObjState::new(obj_type));
@@ -110,7 +135,7 @@ impl OpSet {
                         key: op.key.clone(),
                         element_opid: oid.clone(),
                         internal_element_opid: internal_opid,
-                        index: obj.index_of(internal_opid).unwrap_or(0),
+                        index: obj.cursor_index(internal_opid, self.cursor_bias),
                         referred_object_id: actors.export_obj(obj_id),
                         internal_referred_object_id: *obj_id,
                     });
@@ -121,18 +146,43 @@ impl OpSet {
             }
         }
 
-        let object_id = op.obj;
-        let object = self.get_obj_mut(&object_id)?;
+        if !self.moved.is_empty() {
+            self.apply_to_moved_values(&op, actors, patch)?;
+        }
+
+        let object = self.get_obj_mut(&op.obj)?;
+        if object.is_seq() && op.insert {
+            object.insert_after(
+                op.key.as_element_id().ok_or(AutomergeError::MapKeyInSeq)?,
+                op.clone(),
+                actors,
+            );
+        }
+
+        let overwritten = self.incorporate(op, actors, patch)?;
 
-        let overwritten = if object.is_seq() {
-            if op.insert {
-                object.insert_after(
-                    op.key.as_element_id().ok_or(AutomergeError::MapKeyInSeq)?,
-                    op.clone(),
-                    actors,
-                );
+        for op in overwritten {
+            if let InternalOpType::Set(amp::ScalarValue::Cursor(ref oid)) = op.op.action {
+                if let Some(opids) = self.cursors.get_mut(&op.op.obj) {
+                    opids.retain(|o| o.element_opid != *oid);
+                }
             }
+        }
+        Ok(())
+    }
 
+    /// Add `op` to the concurrent operations at its key, record the difference this makes in
+    /// `patch` and return the operations it overwrites
+    fn incorporate(
+        &mut self,
+        op: OpHandle,
+        actors: &ActorMap,
+        patch: &mut IncrementalPatch,
+    ) -> Result<Vec<OpHandle>, AutomergeError> {
+        let object_id = op.obj;
+        let object = self.get_obj_mut(&object_id)?;
+
+        if object.is_seq() {
             let ops = object
                 .props
                 .entry(op.operation_key().into_owned())
@@ -170,37 +220,258 @@ impl OpSet {
                     let index = object.index_of(id).unwrap_or(0);
                     tracing::debug!(new_id=?id, index=%index, after=?op.operation_key(), "inserting new element");
                     object.seq.insert_index(index, id);
-                    patch.record_seq_insert(&object_id, op.clone(), index, op.id);
+                    patch.record_seq_insert(&object_id, op.clone(), index, id);
                 }
                 (false, false) => {}
             };
 
             self.unlink(&op, &overwritten_ops)?;
+            self.index_value(&op, &overwritten_ops);
 
-            overwritten_ops
+            Ok(overwritten_ops)
         } else {
             let ops = object.props.entry(op.key.clone()).or_default();
             let before = !ops.is_empty();
             let (op, overwritten_ops) = ops.incorporate_new_op(op);
             let after = !ops.is_empty();
             self.unlink(&op, &overwritten_ops)?;
+            self.index_value(&op, &overwritten_ops);
 
             if before || after {
                 patch.record_set(&object_id, op);
             }
-            overwritten_ops
+            Ok(overwritten_ops)
+        }
+    }
+
+    /// Apply a move operation.
+    ///
+    /// Moves are applied in the order of their op IDs, regardless of the order in which they
+    /// arrive: a move which arrives after moves with greater IDs undoes those moves, takes effect
+    /// and then redoes them. A move has no effect if the value it moves has been deleted or if
+    /// it would make an object a descendant of itself, so when several moves of the same value
+    /// are concurrent the one with the greatest ID determines where the value ends up.
+    fn apply_move(
+        &mut self,
+        op: OpHandle,
+        source: OpId,
+        actors: &mut ActorMap,
+        patch: &mut IncrementalPatch,
+    ) -> Result<(), AutomergeError> {
+        // moving a value which has been moved before moves the value which was first created
+        let source = self.move_sources.get(&source).copied().unwrap_or(source);
+        self.move_sources.insert(op.id, source);
+        if !self.moved.contains_key(&source) {
+            let current = self.find_value(source);
+            self.moved.insert(source, MovedValue { current });
+        }
+
+        let object = self.get_obj_mut(&op.obj)?;
+        if object.is_seq() && op.insert {
+            object.insert_after(
+                op.key.as_element_id().ok_or(AutomergeError::MapKeyInSeq)?,
+                op.clone(),
+                actors,
+            );
+        }
+        if !op.pred.is_empty() {
+            // the values at the destination are overwritten whether or not the move takes effect
+            let mut overwrite = op.clone();
+            overwrite.op.action = InternalOpType::Del;
+            self.apply_op(overwrite, actors, patch)?;
+        }
+
+        let position = self
+            .moves
+            .iter()
+            .rposition(|m| actors.cmp_opid(&m.op.id, &op.id) == Ordering::Less)
+            .map_or(0, |i| i + 1);
+        let mut later = self.moves.split_off(position);
+        for record in later.iter_mut().rev() {
+            self.undo_move(record, actors, patch)?;
+        }
+        for mut record in std::iter::once(MoveRecord {
+            op,
+            source,
+            displaced: None,
+        })
+        .chain(later)
+        {
+            self.do_move(&mut record, actors, patch)?;
+            self.moves.push(record);
+        }
+        Ok(())
+    }
+
+    /// Forget the moves with counters up to `max_op`. Every op still to be applied must have a
+    /// greater counter, so these moves are never undone and redone again.
+    pub fn compact_moves(&mut self, max_op: u64) {
+        self.moves.retain(|record| record.op.id.0 > max_op);
+    }
+
+    fn do_move(
+        &mut self,
+        record: &mut MoveRecord,
+        actors: &ActorMap,
+        patch: &mut IncrementalPatch,
+    ) -> Result<(), AutomergeError> {
+        let current = match self.current_value(record.source) {
+            Some(current) => current,
+            None => return Ok(()),
         };
+        if let Some(child) = current.child() {
+            if self.is_ancestor(&child, &record.op.obj) {
+                tracing::debug!(mv=?record.op.id, "ignoring move which would create a cycle");
+                return Ok(());
+            }
+        }
+        let placed = current.placed_by(&record.op);
+        self.incorporate(
+            current.applied_to(&current, InternalOpType::Del),
+            actors,
+            patch,
+        )?;
+        self.incorporate(placed.clone(), actors, patch)?;
+        patch.record_move(&placed);
+        self.moved.insert(
+            record.source,
+            MovedValue {
+                current: Some(placed),
+            },
+        );
+        record.displaced = Some(current);
+        Ok(())
+    }
 
-        for op in overwritten {
-            if let InternalOpType::Set(amp::ScalarValue::Cursor(ref oid)) = op.op.action {
-                if let Some(opids) = self.cursors.get_mut(&op.op.obj) {
-                    opids.retain(|o| o.element_opid != *oid);
+    fn undo_move(
+        &mut self,
+        record: &mut MoveRecord,
+        actors: &ActorMap,
+        patch: &mut IncrementalPatch,
+    ) -> Result<(), AutomergeError> {
+        let mut displaced = match record.displaced.take() {
+            Some(displaced) => displaced,
+            None => return Ok(()),
+        };
+        let current = match self.current_value(record.source) {
+            Some(current) => current,
+            None => return Ok(()),
+        };
+        displaced.delta = current.delta;
+        self.incorporate(
+            current.applied_to(&current, InternalOpType::Del),
+            actors,
+            patch,
+        )?;
+        self.incorporate(displaced.clone(), actors, patch)?;
+        patch.record_move(&displaced);
+        self.moved.insert(
+            record.source,
+            MovedValue {
+                current: Some(displaced),
+            },
+        );
+        Ok(())
+    }
+
+    /// Ops which overwrite or increment a value which has been moved refer to it by the ID of the
+    /// op which created it or of a move of it, so they may not find the value at their own key.
+    /// Apply such ops to the value wherever it is now, and make sure that values which have been
+    /// overwritten are not put back by moves which are undone and redone later.
+    fn apply_to_moved_values(
+        &mut self,
+        op: &OpHandle,
+        actors: &ActorMap,
+        patch: &mut IncrementalPatch,
+    ) -> Result<(), AutomergeError> {
+        for pred in &op.pred {
+            let source = match self.move_sources.get(pred) {
+                Some(source) => *source,
+                None if self.moved.contains_key(pred) => *pred,
+                None => continue,
+            };
+            let current = match self.current_value(source) {
+                Some(current) => current,
+                None => continue,
+            };
+            let in_place = current.id == *pred
+                && current.obj == op.obj
+                && *current.operation_key() == *op.operation_key();
+            if op.is_inc() {
+                if !in_place {
+                    self.incorporate(op.applied_to(&current, op.action.clone()), actors, patch)?;
+                }
+            } else {
+                self.moved.insert(source, MovedValue { current: None });
+                if !in_place {
+                    self.incorporate(op.applied_to(&current, InternalOpType::Del), actors, patch)?;
                 }
             }
         }
         Ok(())
     }
 
+    /// The handle which currently places the value created by `source` in the document, if it
+    /// has not been deleted
+    fn current_value(&self, source: OpId) -> Option<OpHandle> {
+        let current = self.moved.get(&source)?.current.as_ref()?;
+        self.objs
+            .get(&current.obj)?
+            .conflicts(&current.operation_key())
+            .find(|op| op.id == current.id)
+            .cloned()
+    }
+
+    /// Find the op which places the value created by `source`, which has not been moved before
+    fn find_value(&mut self, source: OpId) -> Option<OpHandle> {
+        if let Some(obj) = self.objs.get(&source.into()) {
+            return obj.inbound.clone();
+        }
+        if self.value_keys.is_none() {
+            let mut value_keys = HashMap::new();
+            for (object_id, obj) in &self.objs {
+                for (key, ops) in &obj.props {
+                    for op in ops.iter() {
+                        value_keys.insert(op.id, (*object_id, key.clone()));
+                    }
+                }
+            }
+            self.value_keys = Some(value_keys);
+        }
+        let (object_id, key) = self.value_keys.as_ref()?.get(&source)?;
+        self.objs
+            .get(object_id)?
+            .conflicts(key)
+            .find(|op| op.id == source)
+            .cloned()
+    }
+
+    /// Keep `value_keys` up to date once it has been built
+    fn index_value(&mut self, op: &OpHandle, overwritten: &[OpHandle]) {
+        if let Some(value_keys) = &mut self.value_keys {
+            for old in overwritten {
+                value_keys.remove(&old.id);
+            }
+            if op.action != InternalOpType::Del {
+                value_keys.insert(op.id, (op.obj, op.operation_key().into_owned()));
+            }
+        }
+    }
+
+    /// Whether `ancestor` is `object_id` or one of the objects which contain it
+    fn is_ancestor(&self, ancestor: &ObjectId, object_id: &ObjectId) -> bool {
+        let mut object_id = *object_id;
+        loop {
+            if object_id == *ancestor {
+                return true;
+            }
+            match self.objs.get(&object_id).and_then(|o| o.inbound.as_ref()) {
+                Some(inbound) => object_id = inbound.obj,
+                None => return false,
+            }
+        }
+    }
+
     fn unlink(&mut self, op: &OpHandle, overwritten: &[OpHandle]) -> Result<(), AutomergeError> {
         if let Some(child) = op.child() {
             self.get_obj_mut(&child)?.inbound = Some(op.clone());
@@ -240,7 +511,8 @@ impl OpSet {
             if let Some(cursors) = self.cursors.get_mut(obj_id) {
                 for cursor in cursors.iter_mut() {
                     if let Some(obj) = self.objs.get(&cursor.internal_referred_object_id) {
-                        cursor.index = obj.index_of(cursor.internal_element_opid).unwrap_or(0);
+                        cursor.index =
+                            obj.cursor_index(cursor.internal_element_opid, self.cursor_bias);
                         cursor_changes
                             .entry(cursor.internal_referring_object_id)
                             .or_default()
@@ -256,6 +528,17 @@ impl OpSet {
         }
     }
 
+    /// Change which neighbour cursors pointing at deleted elements resolve to, and update the
+    /// index of every cursor to match
+    pub(crate) fn set_cursor_bias(&mut self, bias: CursorBias) {
+        self.cursor_bias = bias;
+        for cursor in self.cursors.values_mut().flatten() {
+            if let Some(obj) = self.objs.get(&cursor.internal_referred_object_id) {
+                cursor.index = obj.cursor_index(cursor.internal_element_opid, bias);
+            }
+        }
+    }
+
     pub fn update_deps(&mut self, change: &Change) {
         //self.max_op = max(self.max_op, change.max_op());
 
@@ -273,6 +556,41 @@ impl OpSet {
     }
 }
 
+/// A move op which has been applied and the handle it removed from the document when it took
+/// effect, so that it can be undone
+#[derive(Debug, PartialEq, Clone)]
+struct MoveRecord {
+    op: OpHandle,
+    /// The ID of the op which created the moved value
+    source: OpId,
+    /// `None` if the move did not take effect
+    displaced: Option<OpHandle>,
+}
+
+/// A value which has been the subject of a move op
+#[derive(Debug, PartialEq, Clone)]
+struct MovedValue {
+    /// The handle which places the value in the document, `None` if the value has been deleted
+    current: Option<OpHandle>,
+}
+
+/// Which neighbour a cursor resolves to once the element it points at has been deleted
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CursorBias {
+    /// The nearest visible element before the deleted one
+    Left,
+    /// The nearest visible element after the deleted one
+    Right,
+}
+
+// `#[default]` on an enum variant needs a newer compiler than this crate supports
+#[allow(clippy::derivable_impls)]
+impl Default for CursorBias {
+    fn default() -> Self {
+        CursorBias::Right
+    }
+}
+
 /// `CursorState` is the information we need to track in order to update cursors as changes come
 /// in. Cursors are created by `Set` operations and therefore live in a particular object (the
 /// "referring object") and point at an element in a sequence (the "referred" object). For example
@@ -188,7 +188,7 @@ fn construct_text(
     }
 }
 
-fn construct_object(object_id: &ObjectId, workshop: &dyn PatchWorkshop) -> amp::Diff {
+pub(super) fn construct_object(object_id: &ObjectId, workshop: &dyn PatchWorkshop) -> amp::Diff {
     // Safety: if the object is missing when we're generating a diff from
     // scratch then the document is corrupt
     let object = workshop.get_obj(object_id).expect("missing object");
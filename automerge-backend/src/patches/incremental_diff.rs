@@ -5,7 +5,9 @@ use std::{
 
 use automerge_protocol as amp;
 
-use super::{gen_value_diff::gen_value_diff, Edits, PatchWorkshop};
+use super::{
+    from_scratch_diff::construct_object, gen_value_diff::gen_value_diff, Edits, PatchWorkshop,
+};
 use crate::{
     actor_map::ActorMap,
     internal::{InternalOpType, Key, ObjectId, OpId},
@@ -49,11 +51,23 @@ impl PendingDiff {
 /// generating a diff without any existing state, as in the case when we first load a saved
 /// document.
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) struct IncrementalPatch(HashMap<ObjectId, Vec<PendingDiff>>);
+pub(crate) struct IncrementalPatch {
+    diffs: HashMap<ObjectId, Vec<PendingDiff>>,
+    /// The IDs of the ops which have placed a moved value in the document. The frontend has no
+    /// state for the new location of these values so their diffs are constructed from scratch.
+    moved: HashSet<OpId>,
+}
 
 impl IncrementalPatch {
     pub(crate) fn new() -> IncrementalPatch {
-        IncrementalPatch(HashMap::new())
+        IncrementalPatch {
+            diffs: HashMap::new(),
+            moved: HashSet::new(),
+        }
+    }
+
+    pub(crate) fn record_move(&mut self, op: &OpHandle) {
+        self.moved.insert(op.id);
     }
 
     pub(crate) fn record_set(&mut self, oid: &ObjectId, op: OpHandle) {
@@ -85,7 +99,7 @@ impl IncrementalPatch {
         // TODO: Remove the actors argument and instead add a new case to the `PendingDiff`
         // enum to represent multiple seq updates, then sort by actor ID at the point at which we
         // finalize the diffs, when we have access to a `PatchWorkshop` to perform the sorting
-        let diffs = self.0.entry(*oid).or_default();
+        let diffs = self.diffs.entry(*oid).or_default();
         let mut new_diffs = Vec::new();
         'outer: for op in ops {
             let i = op
@@ -128,19 +142,19 @@ impl IncrementalPatch {
     }
 
     fn append_diff(&mut self, oid: &ObjectId, diff: PendingDiff) {
-        self.0.entry(*oid).or_default().push(diff);
+        self.diffs.entry(*oid).or_default().push(diff);
     }
 
     fn append_diffs(&mut self, oid: &ObjectId, mut diffs: Vec<PendingDiff>) {
-        self.0.entry(*oid).or_default().append(&mut diffs);
+        self.diffs.entry(*oid).or_default().append(&mut diffs);
     }
 
     pub(crate) fn changed_object_ids(&self) -> impl Iterator<Item = &ObjectId> {
-        self.0.keys()
+        self.diffs.keys()
     }
 
     pub(crate) fn finalize(mut self, workshop: &dyn PatchWorkshop) -> amp::RootDiff {
-        if self.0.is_empty() {
+        if self.diffs.is_empty() {
             return amp::RootDiff::default();
         }
 
@@ -150,7 +164,7 @@ impl IncrementalPatch {
                 .get_obj(&obj_id)
                 .and_then(|obj| obj.inbound.as_ref())
             {
-                if !self.0.contains_key(&inbound.obj) {
+                if !self.diffs.contains_key(&inbound.obj) {
                     // our parent was not changed - walk up the tree and try them too
                     objs.push(inbound.obj);
                 }
@@ -158,7 +172,7 @@ impl IncrementalPatch {
             }
         }
 
-        if let Some(root) = self.0.remove(&ObjectId::Root) {
+        if let Some(root) = self.diffs.remove(&ObjectId::Root) {
             // I may have duplicate keys - I do this to make sure I visit each one only once
             let keys: HashSet<_> = root.iter().map(PendingDiff::operation_key).collect();
             let mut props = HashMap::with_capacity(keys.len());
@@ -167,11 +181,7 @@ impl IncrementalPatch {
                 let key_string = workshop.key_to_string(key);
                 let mut opid_to_value = HashMap::new();
                 for op in obj.conflicts(key) {
-                    let link = match op.action {
-                        InternalOpType::Set(ref value) => gen_value_diff(op, value, workshop),
-                        InternalOpType::Make(_) => self.gen_obj_diff(&op.id.into(), workshop),
-                        _ => panic!("del or inc found in field_operations"),
-                    };
+                    let link = self.gen_op_diff(op, workshop);
                     opid_to_value.insert(workshop.make_external_opid(&op.id), link);
                 }
                 props.insert(key_string, opid_to_value);
@@ -184,6 +194,17 @@ impl IncrementalPatch {
         }
     }
 
+    fn gen_op_diff(&self, op: &OpHandle, workshop: &dyn PatchWorkshop) -> amp::Diff {
+        match (&op.action, op.child()) {
+            (InternalOpType::Set(value), _) => gen_value_diff(op, value, workshop),
+            (InternalOpType::Make(_), Some(child)) if self.moved.contains(&op.id) => {
+                construct_object(&child, workshop)
+            }
+            (InternalOpType::Make(_), Some(child)) => self.gen_obj_diff(&child, workshop),
+            _ => panic!("del, inc or move found in field operations"),
+        }
+    }
+
     fn gen_obj_diff(&self, obj_id: &ObjectId, workshop: &dyn PatchWorkshop) -> amp::Diff {
         // Safety: the pending diffs we are working with are all generated by
         // the OpSet, we should never have a missing object and if we do
@@ -191,7 +212,7 @@ impl IncrementalPatch {
         let obj = workshop
             .get_obj(obj_id)
             .expect("Missing object in internal diff");
-        if let Some(pending) = self.0.get(obj_id) {
+        if let Some(pending) = self.diffs.get(obj_id) {
             match obj.obj_type {
                 amp::ObjType::List => {
                     amp::Diff::List(self.gen_list_diff(obj_id, obj, pending, workshop))
@@ -244,11 +265,7 @@ impl IncrementalPatch {
             match pending_edit {
                 PendingDiff::SeqInsert(op, index, opid) => {
                     seen_op_ids.insert(op.id);
-                    let value = match op.action {
-                        InternalOpType::Set(ref value) => gen_value_diff(op, value, workshop),
-                        InternalOpType::Make(_) => self.gen_obj_diff(&op.id.into(), workshop),
-                        _ => panic!("del or inc found in field operations"),
-                    };
+                    let value = self.gen_op_diff(op, workshop);
                     let op_id = workshop.make_external_opid(opid);
                     edits.append_edit(amp::DiffEdit::SingleElementInsert {
                         index: *index as u64,
@@ -259,14 +276,10 @@ impl IncrementalPatch {
                 }
                 PendingDiff::SeqUpdate(op, index, opid) => {
                     seen_op_ids.insert(op.id);
-                    let value = match op.action {
-                        InternalOpType::Set(ref value) => gen_value_diff(op, value, workshop),
-                        InternalOpType::Make(_) => self.gen_obj_diff(&op.id.into(), workshop),
-                        InternalOpType::Del | InternalOpType::Inc(..) => {
-                            // do nothing
-                            continue;
-                        }
-                    };
+                    if !matches!(op.action, InternalOpType::Set(_) | InternalOpType::Make(_)) {
+                        continue;
+                    }
+                    let value = self.gen_op_diff(op, workshop);
                     edits.append_edit(amp::DiffEdit::Update {
                         index: *index as u64,
                         op_id: workshop.make_external_opid(opid),
@@ -285,15 +298,7 @@ impl IncrementalPatch {
                     for op in obj.conflicts(&op.operation_key()) {
                         if !seen_op_ids.contains(&op.id) {
                             seen_op_ids.insert(op.id);
-                            let value = match op.action {
-                                InternalOpType::Set(ref value) => {
-                                    gen_value_diff(op, value, workshop)
-                                }
-                                InternalOpType::Make(_) => {
-                                    self.gen_obj_diff(&op.id.into(), workshop)
-                                }
-                                _ => panic!("del or inc found in field operations"),
-                            };
+                            let value = self.gen_op_diff(op, workshop);
                             edits.append_edit(amp::DiffEdit::Update {
                                 index: obj.index_of(op.id).unwrap_or(0) as u64,
                                 op_id: workshop.make_external_opid(&op.id),
@@ -328,11 +333,7 @@ impl IncrementalPatch {
             match pending_edit {
                 PendingDiff::SeqInsert(op, index, opid) => {
                     seen_op_ids.insert(op.id);
-                    let value = match op.action {
-                        InternalOpType::Set(ref value) => gen_value_diff(op, value, workshop),
-                        InternalOpType::Make(_) => self.gen_obj_diff(&op.id.into(), workshop),
-                        _ => panic!("del or inc found in field operations"),
-                    };
+                    let value = self.gen_op_diff(op, workshop);
                     let op_id = workshop.make_external_opid(opid);
                     edits.append_edit(amp::DiffEdit::SingleElementInsert {
                         index: *index as u64,
@@ -343,14 +344,10 @@ impl IncrementalPatch {
                 }
                 PendingDiff::SeqUpdate(op, index, opid) => {
                     seen_op_ids.insert(op.id);
-                    let value = match op.action {
-                        InternalOpType::Set(ref value) => gen_value_diff(op, value, workshop),
-                        InternalOpType::Make(_) => self.gen_obj_diff(&op.id.into(), workshop),
-                        InternalOpType::Del | InternalOpType::Inc(..) => {
-                            // do nothing
-                            continue;
-                        }
-                    };
+                    if !matches!(op.action, InternalOpType::Set(_) | InternalOpType::Make(_)) {
+                        continue;
+                    }
+                    let value = self.gen_op_diff(op, workshop);
                     edits.append_edit(amp::DiffEdit::Update {
                         index: *index as u64,
                         op_id: workshop.make_external_opid(opid),
@@ -369,15 +366,7 @@ impl IncrementalPatch {
                     for op in obj.conflicts(&op.operation_key()) {
                         if !seen_op_ids.contains(&op.id) {
                             seen_op_ids.insert(op.id);
-                            let value = match op.action {
-                                InternalOpType::Set(ref value) => {
-                                    gen_value_diff(op, value, workshop)
-                                }
-                                InternalOpType::Make(_) => {
-                                    self.gen_obj_diff(&op.id.into(), workshop)
-                                }
-                                _ => panic!("del or inc found in field operations"),
-                            };
+                            let value = self.gen_op_diff(op, workshop);
                             edits.append_edit(amp::DiffEdit::Update {
                                 index: obj.index_of(op.id).unwrap_or(0) as u64,
                                 op_id: workshop.make_external_opid(&op.id),
@@ -411,11 +400,7 @@ impl IncrementalPatch {
             let key_string = workshop.key_to_string(key);
             let mut opid_to_value = HashMap::new();
             for op in obj.conflicts(key) {
-                let value = match op.action {
-                    InternalOpType::Set(ref value) => gen_value_diff(op, value, workshop),
-                    InternalOpType::Make(_) => self.gen_obj_diff(&op.id.into(), workshop),
-                    _ => panic!("del or inc found in field_operations"),
-                };
+                let value = self.gen_op_diff(op, workshop);
                 opid_to_value.insert(workshop.make_external_opid(&op.id), value);
             }
             props.insert(key_string, opid_to_value);
@@ -440,11 +425,7 @@ impl IncrementalPatch {
             let key_string = workshop.key_to_string(key);
             let mut opid_to_value = HashMap::new();
             for op in obj.conflicts(key) {
-                let link = match op.action {
-                    InternalOpType::Set(ref value) => gen_value_diff(op, value, workshop),
-                    InternalOpType::Make(_) => self.gen_obj_diff(&op.id.into(), workshop),
-                    _ => panic!("del or inc found in field_operations"),
-                };
+                let link = self.gen_op_diff(op, workshop);
                 opid_to_value.insert(workshop.make_external_opid(&op.id), link);
             }
             props.insert(key_string, opid_to_value);
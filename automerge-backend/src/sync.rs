@@ -6,16 +6,25 @@ use std::{
     io::Write,
 };
 
-use automerge_protocol::{ChangeHash, Patch};
+use automerge_protocol::{ActorId, ChangeHash, Patch};
+use serde::{
+    ser::{Error as _, SerializeStruct},
+    Serialize, Serializer,
+};
 
 use crate::{
     decoding, decoding::Decoder, encoding, encoding::Encodable, AutomergeError, Backend, Change,
 };
 
+mod auth;
 mod bloom;
+mod document_set;
+mod ephemeral;
 mod state;
 
 pub use bloom::BloomFilter;
+pub use document_set::{DocumentSet, DocumentSetMessage, DocumentSetSyncState};
+pub use ephemeral::{EphemeralManager, EphemeralMessage};
 pub use state::{SyncHave, SyncState};
 
 const HASH_SIZE: usize = 32; // 256 bits = 32 bytes
@@ -50,6 +59,7 @@ impl Backend {
                         need: Vec::new(),
                         have: vec![SyncHave::default()],
                         changes: Vec::new(),
+                        document: None,
                     };
                     return Some(reset_msg);
                 }
@@ -81,6 +91,18 @@ impl Backend {
             return None;
         }
 
+        // a peer with no heads has none of our history, so rather than streaming every change we
+        // can hand it the whole document in one compressed chunk
+        let document = if sync_state.bootstrap_with_snapshot
+            && sync_state.sent_hashes.is_empty()
+            && !changes_to_send.is_empty()
+            && matches!(sync_state.their_heads.as_deref(), Some([]))
+        {
+            self.save().ok()
+        } else {
+            None
+        };
+
         // deduplicate the changes to send with those we have already sent
         changes_to_send.retain(|change| !sync_state.sent_hashes.contains(&change.hash));
 
@@ -89,11 +111,18 @@ impl Backend {
             .sent_hashes
             .extend(changes_to_send.iter().map(|c| c.hash));
 
+        let changes = if document.is_some() {
+            Vec::new()
+        } else {
+            changes_to_send.into_iter().cloned().collect()
+        };
+
         let sync_message = SyncMessage {
             heads: our_heads,
             have: our_have,
             need: our_need,
-            changes: changes_to_send.into_iter().cloned().collect(),
+            changes,
+            document,
         };
 
         Some(sync_message)
@@ -110,11 +139,18 @@ impl Backend {
 
         let SyncMessage {
             heads: message_heads,
-            changes: message_changes,
+            changes: mut message_changes,
             need: message_need,
             have: message_have,
+            document: message_document,
         } = message;
 
+        if let Some(document) = message_document {
+            let mut document_changes = Change::load_document(&document)?;
+            document_changes.append(&mut message_changes);
+            message_changes = document_changes;
+        }
+
         let changes_is_empty = message_changes.is_empty();
         if !changes_is_empty {
             patch = Some(self.apply_changes(message_changes)?);
@@ -125,9 +161,6 @@ impl Backend {
             );
         }
 
-        // trim down the sent hashes to those that we know they haven't seen
-        self.filter_changes(&message_heads, &mut sync_state.sent_hashes);
-
         if changes_is_empty && message_heads == before_heads {
             sync_state.last_sent_heads = Some(message_heads.clone());
         }
@@ -150,6 +183,14 @@ impl Backend {
             sync_state.shared_heads.sort();
         }
 
+        // trim down the sent hashes to those that we know they haven't seen, so that the set only
+        // holds changes which are still in flight rather than every change we have ever sent
+        if sync_state.shared_heads == self.get_heads() {
+            sync_state.sent_hashes.clear();
+        } else {
+            self.filter_changes(&sync_state.shared_heads, &mut sync_state.sent_hashes);
+        }
+
         sync_state.their_have = Some(message_have);
         sync_state.their_heads = Some(message_heads);
         sync_state.their_need = Some(message_need);
@@ -240,12 +281,76 @@ impl Backend {
     }
 }
 
+/// A message in the sync protocol.
+///
+/// The [`Serialize`] implementation is intended for inspecting messages while debugging, changes
+/// (including those in `document`) are summarised rather than serialized in full.
 #[derive(Debug, Clone)]
 pub struct SyncMessage {
     pub heads: Vec<ChangeHash>,
     pub need: Vec<ChangeHash>,
     pub have: Vec<SyncHave>,
     pub changes: Vec<Change>,
+    /// A whole document in the format produced by [`Backend::save`], sent in place of `changes`
+    /// to a peer which has nothing yet.
+    pub document: Option<Vec<u8>>,
+}
+
+/// The metadata of a change, without its operations
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangeSummary<'a> {
+    hash: &'a ChangeHash,
+    actor: &'a ActorId,
+    seq: u64,
+    start_op: u64,
+    time: i64,
+    message: Option<String>,
+    deps: &'a [ChangeHash],
+    num_ops: usize,
+}
+
+impl<'a> From<&'a Change> for ChangeSummary<'a> {
+    fn from(change: &'a Change) -> Self {
+        Self {
+            hash: &change.hash,
+            actor: change.actor_id(),
+            seq: change.seq,
+            start_op: change.start_op,
+            time: change.time,
+            message: change.message(),
+            deps: &change.deps,
+            num_ops: change.iter_ops().count(),
+        }
+    }
+}
+
+/// The summaries of a list of changes
+struct ChangeSummaries<'a>(&'a [Change]);
+
+impl Serialize for ChangeSummaries<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(ChangeSummary::from))
+    }
+}
+
+impl Serialize for SyncMessage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let document = self
+            .document
+            .as_ref()
+            .map(|document| Change::load_document(document))
+            .transpose()
+            .map_err(S::Error::custom)?;
+
+        let mut message = serializer.serialize_struct("SyncMessage", 5)?;
+        message.serialize_field("heads", &self.heads)?;
+        message.serialize_field("need", &self.need)?;
+        message.serialize_field("have", &self.have)?;
+        message.serialize_field("changes", &ChangeSummaries(&self.changes))?;
+        message.serialize_field("document", &document.as_deref().map(ChangeSummaries))?;
+        message.end()
+    }
 }
 
 impl SyncMessage {
@@ -266,6 +371,12 @@ impl SyncMessage {
             change.raw_bytes().encode(&mut buf)?;
         }
 
+        // the document is appended at the end so that messages without one are unchanged on the
+        // wire
+        if let Some(document) = self.document {
+            document.encode(&mut buf)?;
+        }
+
         Ok(buf)
     }
 
@@ -298,11 +409,18 @@ impl SyncMessage {
             changes.push(Change::from_bytes(change)?);
         }
 
+        let document = if decoder.done() {
+            None
+        } else {
+            Some(decoder.read::<Vec<u8>>()?)
+        };
+
         Ok(SyncMessage {
             heads,
             need,
             have,
             changes,
+            document,
         })
     }
 }
@@ -1,6 +1,7 @@
 use std::{borrow::Cow, convert::TryFrom};
 
 use automerge_protocol::ChangeHash;
+use serde::Serialize;
 
 use crate::{decoding, decoding::Decoder, encoding, encoding::Encodable};
 
@@ -10,11 +11,14 @@ use crate::{decoding, decoding::Decoder, encoding, encoding::Encodable};
 const BITS_PER_ENTRY: u32 = 10;
 const NUM_PROBES: u32 = 7;
 
-#[derive(Default, Debug, Clone)]
+/// Serializes to the filter's parameters only, the bits themselves are not useful to a reader.
+#[derive(Default, Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct BloomFilter {
     num_entries: u32,
     num_bits_per_entry: u32,
     num_probes: u32,
+    #[serde(skip)]
     bits: Vec<u8>,
 }
 
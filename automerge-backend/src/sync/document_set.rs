@@ -0,0 +1,248 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use automerge_protocol::Patch;
+
+use super::{EphemeralMessage, SyncMessage, SyncState};
+use crate::{decoding, decoding::Decoder, encoding, encoding::Encodable, AutomergeError, Backend};
+
+const MESSAGE_TYPE_DOCUMENT_SET: u8 = 0x44; // first byte of a document set message
+
+/// A collection of documents, keyed by an application defined id, which can be synchronised with
+/// a peer over a single stream of [`DocumentSetMessage`]s.
+///
+/// Every document held by either side is replicated to the other. A document which the peer has
+/// but we do not is created locally as an empty [`Backend`] and then filled in by the usual sync
+/// protocol.
+#[derive(Debug, Default, Clone)]
+pub struct DocumentSet {
+    documents: HashMap<String, Backend>,
+}
+
+/// The per connection state of a [`DocumentSet`], wrapping a [`SyncState`] for each document.
+#[derive(Debug, Default, Clone)]
+pub struct DocumentSetSyncState {
+    pub doc_states: HashMap<String, SyncState>,
+    /// The ids the peer last announced. Once these cover all of our documents we stop announcing
+    /// ours.
+    pub their_documents: Option<HashSet<String>>,
+    pub last_sent_documents: Option<Vec<String>>,
+    /// Ephemeral messages waiting to go out with the next [`DocumentSetMessage`].
+    pub outgoing_ephemeral: Vec<EphemeralMessage>,
+    /// Ephemeral messages received from the peer which the application has not taken yet.
+    pub incoming_ephemeral: Vec<EphemeralMessage>,
+}
+
+/// A batch of per document [`SyncMessage`]s along with the ids of the documents the sender holds
+/// and any ephemeral messages queued for the peer.
+#[derive(Debug, Clone, Default)]
+pub struct DocumentSetMessage {
+    /// The ids of every document the sender holds, only sent when this has changed since the
+    /// last message and the peer has not already announced all of them.
+    pub documents: Option<Vec<String>>,
+    pub messages: Vec<(String, SyncMessage)>,
+    pub ephemeral: Vec<EphemeralMessage>,
+}
+
+impl DocumentSetSyncState {
+    /// Queue an ephemeral message to be sent to this peer with the next sync message.
+    pub fn queue_ephemeral(&mut self, message: EphemeralMessage) {
+        self.outgoing_ephemeral.push(message);
+    }
+
+    /// Take every ephemeral message received from this peer so far, to be passed to
+    /// [`super::EphemeralManager::receive`].
+    pub fn take_ephemeral(&mut self) -> Vec<EphemeralMessage> {
+        std::mem::take(&mut self.incoming_ephemeral)
+    }
+}
+
+impl DocumentSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a document, returning the document previously held under this id, if any.
+    pub fn insert(&mut self, id: String, backend: Backend) -> Option<Backend> {
+        self.documents.insert(id, backend)
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<Backend> {
+        self.documents.remove(id)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Backend> {
+        self.documents.get(id)
+    }
+
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut Backend> {
+        self.documents.get_mut(id)
+    }
+
+    /// The ids of the documents in this set, sorted.
+    pub fn document_ids(&self) -> Vec<String> {
+        let mut ids = self.documents.keys().cloned().collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    pub fn generate_sync_message(
+        &self,
+        sync_state: &mut DocumentSetSyncState,
+    ) -> Option<DocumentSetMessage> {
+        let our_documents = self.document_ids();
+
+        // the peer only needs our ids to learn about documents it does not hold yet
+        let peer_has_ours = match &sync_state.their_documents {
+            Some(theirs) => our_documents.iter().all(|id| theirs.contains(id)),
+            None => false,
+        };
+        let documents =
+            if peer_has_ours || sync_state.last_sent_documents.as_ref() == Some(&our_documents) {
+                None
+            } else {
+                sync_state.last_sent_documents = Some(our_documents.clone());
+                Some(our_documents.clone())
+            };
+
+        let mut messages = Vec::new();
+        for id in our_documents {
+            let doc_state = sync_state.doc_states.entry(id.clone()).or_default();
+            if let Some(message) = self.documents[&id].generate_sync_message(doc_state) {
+                messages.push((id, message));
+            }
+        }
+
+        let ephemeral = std::mem::take(&mut sync_state.outgoing_ephemeral);
+
+        if documents.is_none() && messages.is_empty() && ephemeral.is_empty() {
+            None
+        } else {
+            Some(DocumentSetMessage {
+                documents,
+                messages,
+                ephemeral,
+            })
+        }
+    }
+
+    /// Apply a message from the peer, returning the patch for every document which changed.
+    pub fn receive_sync_message(
+        &mut self,
+        sync_state: &mut DocumentSetSyncState,
+        message: DocumentSetMessage,
+    ) -> Result<HashMap<String, Patch>, AutomergeError> {
+        let DocumentSetMessage {
+            documents,
+            messages,
+            ephemeral,
+        } = message;
+
+        sync_state.incoming_ephemeral.extend(ephemeral);
+
+        if let Some(documents) = documents {
+            for id in &documents {
+                self.documents.entry(id.clone()).or_default();
+            }
+            sync_state.their_documents = Some(documents.into_iter().collect());
+        }
+
+        let mut patches = HashMap::new();
+        for (id, message) in messages {
+            let doc_state = sync_state.doc_states.entry(id.clone()).or_default();
+            let backend = self.documents.entry(id.clone()).or_default();
+            if let Some(patch) = backend.receive_sync_message(doc_state, message)? {
+                patches.insert(id, patch);
+            }
+        }
+        Ok(patches)
+    }
+}
+
+impl DocumentSetMessage {
+    pub fn encode(self) -> Result<Vec<u8>, encoding::Error> {
+        let mut buf = vec![MESSAGE_TYPE_DOCUMENT_SET];
+
+        if let Some(documents) = self.documents {
+            buf.push(1);
+            (documents.len() as u32).encode(&mut buf)?;
+            for id in documents {
+                id.encode(&mut buf)?;
+            }
+        } else {
+            buf.push(0);
+        }
+
+        (self.messages.len() as u32).encode(&mut buf)?;
+        for (id, message) in self.messages {
+            id.encode(&mut buf)?;
+            message.encode()?.encode(&mut buf)?;
+        }
+
+        // ephemeral messages are appended at the end so that messages without any are unchanged
+        // on the wire
+        if !self.ephemeral.is_empty() {
+            (self.ephemeral.len() as u32).encode(&mut buf)?;
+            for message in self.ephemeral {
+                message.encode()?.encode(&mut buf)?;
+            }
+        }
+
+        Ok(buf)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<DocumentSetMessage, decoding::Error> {
+        let mut decoder = Decoder::new(Cow::Borrowed(bytes));
+
+        let message_type = decoder.read::<u8>()?;
+        if message_type != MESSAGE_TYPE_DOCUMENT_SET {
+            return Err(decoding::Error::WrongType {
+                expected_one_of: vec![MESSAGE_TYPE_DOCUMENT_SET],
+                found: message_type,
+            });
+        }
+
+        let documents = if decoder.read::<u8>()? == 0 {
+            None
+        } else {
+            let document_count = decoder.read::<u32>()?;
+            let mut documents = Vec::with_capacity(capacity(document_count, bytes, &decoder));
+            for _ in 0..document_count {
+                documents.push(decoder.read::<String>()?);
+            }
+            Some(documents)
+        };
+
+        let message_count = decoder.read::<u32>()?;
+        let mut messages = Vec::with_capacity(capacity(message_count, bytes, &decoder));
+        for _ in 0..message_count {
+            let id = decoder.read::<String>()?;
+            let message_bytes: Vec<u8> = decoder.read()?;
+            messages.push((id, SyncMessage::decode(&message_bytes)?));
+        }
+
+        let mut ephemeral = Vec::new();
+        if !decoder.done() {
+            let ephemeral_count = decoder.read::<u32>()?;
+            ephemeral.reserve(capacity(ephemeral_count, bytes, &decoder));
+            for _ in 0..ephemeral_count {
+                let message_bytes: Vec<u8> = decoder.read()?;
+                ephemeral.push(EphemeralMessage::decode(&message_bytes)?);
+            }
+        }
+
+        Ok(DocumentSetMessage {
+            documents,
+            messages,
+            ephemeral,
+        })
+    }
+}
+
+/// Every entry takes at least one byte, so a count read from the peer can never need more slots
+/// than there are bytes left to decode.
+fn capacity(count: u32, bytes: &[u8], decoder: &Decoder) -> usize {
+    (count as usize).min(bytes.len().saturating_sub(decoder.offset))
+}
@@ -0,0 +1,131 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use automerge_protocol as amp;
+
+use crate::{decoding, decoding::Decoder, encoding, encoding::Encodable};
+
+const MESSAGE_TYPE_EPHEMERAL: u8 = 0x45; // first byte of an ephemeral message
+
+/// An application defined payload, such as a cursor position, which is shared with peers
+/// alongside the sync protocol but is never stored in the document.
+///
+/// Each actor has at most one live payload at a time, a message with a higher `seq` replaces the
+/// previous one. Times are in milliseconds since the unix epoch, as supplied by the caller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EphemeralMessage {
+    pub actor: amp::ActorId,
+    pub seq: u64,
+    pub expires_at: u64,
+    pub payload: Vec<u8>,
+}
+
+/// Tracks the ephemeral state of the local actor and of every peer we have heard from.
+///
+/// Messages returned by [`EphemeralManager::broadcast`] should be sent to every connected peer,
+/// either with [`super::DocumentSetSyncState::queue_ephemeral`] so that they travel in the same
+/// [`super::DocumentSetMessage`] stream as the documents, or by the application's own transport.
+/// [`EphemeralManager::receive`] reports whether a message was new so that relays only forward a
+/// message once.
+#[derive(Debug, Clone)]
+pub struct EphemeralManager {
+    actor: amp::ActorId,
+    seq: u64,
+    states: HashMap<amp::ActorId, EphemeralMessage>,
+}
+
+impl EphemeralManager {
+    pub fn new(actor: amp::ActorId) -> Self {
+        Self {
+            actor,
+            seq: 0,
+            states: HashMap::new(),
+        }
+    }
+
+    /// Replace the local actor's payload, returning the message to send to peers.
+    pub fn broadcast(&mut self, payload: Vec<u8>, ttl: u64, now: u64) -> EphemeralMessage {
+        self.seq += 1;
+        let message = EphemeralMessage {
+            actor: self.actor.clone(),
+            seq: self.seq,
+            expires_at: now.saturating_add(ttl),
+            payload,
+        };
+        self.states.insert(self.actor.clone(), message.clone());
+        message
+    }
+
+    /// Record a message from a peer, returning whether it was newer than what we already had.
+    pub fn receive(&mut self, message: EphemeralMessage, now: u64) -> bool {
+        if message.expires_at <= now || message.actor == self.actor {
+            return false;
+        }
+        match self.states.get(&message.actor) {
+            Some(existing) if existing.seq >= message.seq => false,
+            _ => {
+                self.states.insert(message.actor.clone(), message);
+                true
+            }
+        }
+    }
+
+    /// Drop every payload which has expired, returning the actors which were removed.
+    pub fn prune(&mut self, now: u64) -> Vec<amp::ActorId> {
+        let expired = self
+            .states
+            .values()
+            .filter(|message| message.expires_at <= now)
+            .map(|message| message.actor.clone())
+            .collect::<Vec<_>>();
+        for actor in &expired {
+            self.states.remove(actor);
+        }
+        expired
+    }
+
+    /// The current payload for `actor`, if it has not expired.
+    pub fn get(&self, actor: &amp::ActorId, now: u64) -> Option<&[u8]> {
+        self.states
+            .get(actor)
+            .filter(|message| message.expires_at > now)
+            .map(|message| message.payload.as_slice())
+    }
+
+    /// Every live message, including our own, which can be sent to a newly connected peer.
+    pub fn live_messages(&self, now: u64) -> Vec<&EphemeralMessage> {
+        self.states
+            .values()
+            .filter(|message| message.expires_at > now)
+            .collect()
+    }
+}
+
+impl EphemeralMessage {
+    pub fn encode(&self) -> Result<Vec<u8>, encoding::Error> {
+        let mut buf = vec![MESSAGE_TYPE_EPHEMERAL];
+        self.actor.to_bytes().encode(&mut buf)?;
+        self.seq.encode(&mut buf)?;
+        self.expires_at.encode(&mut buf)?;
+        self.payload.encode(&mut buf)?;
+        Ok(buf)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<EphemeralMessage, decoding::Error> {
+        let mut decoder = Decoder::new(Cow::Borrowed(bytes));
+
+        let message_type = decoder.read::<u8>()?;
+        if message_type != MESSAGE_TYPE_EPHEMERAL {
+            return Err(decoding::Error::WrongType {
+                expected_one_of: vec![MESSAGE_TYPE_EPHEMERAL],
+                found: message_type,
+            });
+        }
+
+        Ok(EphemeralMessage {
+            actor: decoder.read()?,
+            seq: decoder.read()?,
+            expires_at: decoder.read()?,
+            payload: decoder.read()?,
+        })
+    }
+}
@@ -1,23 +1,40 @@
 use std::{borrow::Cow, collections::HashSet};
 
 use automerge_protocol::ChangeHash;
+use serde::{Serialize, Serializer};
 
 use super::{decode_hashes, encode_hashes};
 use crate::{decoding, decoding::Decoder, encoding, BloomFilter};
 
 const SYNC_STATE_TYPE: u8 = 0x43; // first byte of an encoded sync state, for identification
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SyncState {
     pub shared_heads: Vec<ChangeHash>,
     pub last_sent_heads: Option<Vec<ChangeHash>>,
     pub their_heads: Option<Vec<ChangeHash>>,
     pub their_need: Option<Vec<ChangeHash>>,
     pub their_have: Option<Vec<SyncHave>>,
+    /// Changes we have sent which the peer has not yet acknowledged, so that we don't send them
+    /// again. Hashes are removed once the peer's heads show that it has them.
+    #[serde(serialize_with = "serialize_sorted")]
     pub sent_hashes: HashSet<ChangeHash>,
+    /// When set, a peer which reports no heads is sent the whole document as produced by
+    /// [`crate::Backend::save`] rather than every change individually.
+    ///
+    /// This is a local setting and is not persisted by [`SyncState::encode`]. Both peers must
+    /// understand the `document` field of [`crate::SyncMessage`] for this to be enabled.
+    pub bootstrap_with_snapshot: bool,
+    /// The counter of the last authenticated message we sent in this session, see
+    /// [`crate::Backend::generate_authenticated_sync_message`]. Not persisted.
+    pub authenticated_sent: u64,
+    /// The counter of the last authenticated message we accepted in this session. Not persisted.
+    pub authenticated_received: u64,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SyncHave {
     pub last_sync: Vec<ChangeHash>,
     pub bloom: BloomFilter,
@@ -49,10 +66,23 @@ impl SyncState {
             their_need: None,
             their_have: Some(Vec::new()),
             sent_hashes: HashSet::new(),
+            bootstrap_with_snapshot: false,
+            authenticated_sent: 0,
+            authenticated_received: 0,
         })
     }
 }
 
+/// Serialize a set of hashes in a stable order so that the output can be compared
+fn serialize_sorted<S: Serializer>(
+    hashes: &HashSet<ChangeHash>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut hashes = hashes.iter().collect::<Vec<_>>();
+    hashes.sort();
+    serializer.collect_seq(hashes)
+}
+
 impl Default for SyncState {
     fn default() -> Self {
         Self {
@@ -62,6 +92,9 @@ impl Default for SyncState {
             their_need: None,
             their_have: None,
             sent_hashes: HashSet::new(),
+            bootstrap_with_snapshot: false,
+            authenticated_sent: 0,
+            authenticated_received: 0,
         }
     }
 }
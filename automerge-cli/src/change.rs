@@ -57,50 +57,46 @@ where
     ))
 }
 
-fn key_parser<Input>() -> impl Parser<Input, Output = String>
+/// The raw text of a quoted key, including the quotes and any escapes, which may contain
+/// whitespace
+fn quoted_key_parser<Input>() -> impl Parser<Input, Output = String>
 where
     Input: combine::Stream<Token = char>,
+    Input::Error: combine::ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    let key_char_parser = combine::choice::<Input, _>((
-        charparser::alpha_num(),
-        charparser::char('-'),
-        charparser::char('_'),
-    ));
-    combine::many1(key_char_parser).map(|chars: Vec<char>| chars.into_iter().collect())
+    let escaped = charparser::char('\\')
+        .and(combine::any())
+        .map(|(backslash, c)| format!("{}{}", backslash, c));
+    let unescaped = combine::satisfy(|c: char| c != '"' && c != '\\').map(String::from);
+    (
+        charparser::char('"'),
+        combine::many::<String, _, _>(escaped.or(unescaped)),
+        charparser::char('"'),
+    )
+        .map(|(open, key, close)| format!("{}{}{}", open, key, close))
 }
 
-fn index_parser<Input>() -> impl Parser<Input, Output = u32>
+/// A path is everything up to the next whitespace outside of a quoted key, in any syntax
+/// `amf::Path` can parse
+fn path_parser<Input>() -> impl Parser<Input, Output = amf::Path>
 where
     Input: combine::Stream<Token = char>,
+    Input::Error: combine::ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    combine::many1::<Vec<char>, Input, _>(charparser::digit()).map(|digits| {
-        let num_string: String = digits.iter().collect();
-        num_string.parse::<u32>().unwrap()
-    })
-}
-
-combine::parser! {
-    fn path_segment_parser[Input](path_so_far: amf::Path)(Input) -> amf::Path
-    where [Input:  combine::Stream<Token=char>]
-    {
-        let key_path_so_far = path_so_far.clone();
-        let key_segment_parser = charparser::string("[\"")
-            .with(key_parser())
-            .skip(charparser::string("\"]"))
-            .then(move |key| path_segment_parser(key_path_so_far.clone().key(key)));
-
-        let index_path_so_far = path_so_far.clone();
-        let index_segment_parser = charparser::char('[')
-            .with(index_parser())
-            .skip(charparser::char(']'))
-            .then(move |index| path_segment_parser(index_path_so_far.clone().index(index)));
-
-        combine::choice((
-            combine::attempt(key_segment_parser),
-            combine::attempt(index_segment_parser),
-            combine::value(path_so_far.clone())
+    let unquoted = combine::satisfy(|c: char| !c.is_whitespace() && c != '"').map(String::from);
+    combine::position()
+        .and(combine::many1::<String, _, _>(
+            quoted_key_parser().or(unquoted),
         ))
-    }
+        .flat_map(
+            |(position, path): (Input::Position, String)| -> Result<amf::Path, Input::Error> {
+                path.parse().map_err(|e: amf::InvalidPath| {
+                    let mut pe = Input::Error::empty(position);
+                    pe.add_message(combine::error::Format(e.to_string()));
+                    pe
+                })
+            },
+        )
 }
 
 fn value_parser<'a, Input>(
@@ -136,12 +132,7 @@ where
     Input::Error: combine::ParseError<Input::Token, Input::Range, Input::Position>,
 {
     charparser::spaces()
-        .with(
-            op_parser()
-                .skip(charparser::spaces())
-                .skip(charparser::string("$"))
-                .and(path_segment_parser(amf::Path::root())),
-        )
+        .with(op_parser().skip(charparser::spaces()).and(path_parser()))
         .skip(charparser::spaces())
         .then(|(operation, path)| {
             let onwards: Box<
@@ -240,6 +231,20 @@ mod tests {
                 input: "increment $[\"map\"][0]",
                 expected: amf::LocalChange::increment(amf::Path::root().key("map").index(0)),
             },
+            Scenario {
+                input: "set $[\"two words\"][\"a \\\" b\"] \"value\"",
+                expected: amf::LocalChange::set(
+                    amf::Path::root().key("two words").key("a \" b"),
+                    amf::Value::from("value"),
+                ),
+            },
+            Scenario {
+                input: "set /map/0 \"value\"",
+                expected: amf::LocalChange::set(
+                    amf::Path::root().key("map").index(0),
+                    amf::Value::from("value"),
+                ),
+            },
         ];
         for (index, scenario) in scenarios.into_iter().enumerate() {
             let result: Result<(amf::LocalChange, _), _> =
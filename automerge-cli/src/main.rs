@@ -7,6 +7,7 @@ mod change;
 mod examine;
 mod export;
 mod import;
+mod sync_inspect;
 
 #[derive(Debug, Clap)]
 #[clap(about = "Automerge CLI")]
@@ -65,9 +66,10 @@ enum Command {
         /// The change script to perform. Change scripts have the form <command> <path> [<JSON value>].
         /// The possible commands are 'set', 'insert', 'delete', and 'increment'.
         ///
-        /// Paths look like this: $["mapkey"][0]. They always lways start with a '$', then each
+        /// Paths look like this: $["mapkey"][0]. They always start with a '$', then each
         /// subsequent segment of the path is either a string in double quotes to index a key in a
-        /// map, or an integer index to address an array element.
+        /// map, or an integer index to address an array element. JSON Pointers such as /mapkey/0
+        /// are also accepted.
         ///
         /// Examples
         ///
@@ -99,6 +101,10 @@ enum Command {
 
     /// Read an automerge document and print a JSON representation of the changes in it to stdout
     Examine { input_file: Option<PathBuf> },
+
+    /// Read a sync message or sync state, as produced by `SyncMessage::encode` or
+    /// `SyncState::encode`, and print a JSON representation of it to stdout
+    SyncInspect { input_file: Option<PathBuf> },
 }
 
 fn open_file_or_stdin(maybe_path: Option<PathBuf>) -> Result<Box<dyn std::io::Read>> {
@@ -178,5 +184,11 @@ fn main() -> Result<()> {
             }
             Ok(())
         }
+        Command::SyncInspect { input_file } => {
+            let in_buffer = open_file_or_stdin(input_file)?;
+            let out_buffer = std::io::stdout();
+            sync_inspect::sync_inspect(in_buffer, out_buffer, atty::is(atty::Stream::Stdout))
+                .map_err(|e| anyhow::format_err!("Unable to inspect sync data: {:?}", e))
+        }
     }
 }
@@ -0,0 +1,59 @@
+use automerge_backend as amb;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SyncInspectError {
+    #[error("Error reading input: {:?}", source)]
+    ReadingInput {
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Error decoding input: {:?}", source)]
+    Decoding {
+        #[source]
+        source: amb::DecodingError,
+    },
+    #[error("Error serializing to JSON: {:?}", source)]
+    Serializing {
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("Error writing to output: {:?}", source)]
+    WritingToOutput {
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+/// Print a JSON representation of a message produced by `SyncMessage::encode` or a state
+/// produced by `SyncState::encode`.
+pub fn sync_inspect(
+    mut input: impl std::io::Read,
+    mut output: impl std::io::Write,
+    is_tty: bool,
+) -> Result<(), SyncInspectError> {
+    let mut buf: Vec<u8> = Vec::new();
+    input
+        .read_to_end(&mut buf)
+        .map_err(|e| SyncInspectError::ReadingInput { source: e })?;
+    // the first byte of each encoding identifies its type
+    let json = match amb::SyncMessage::decode(&buf) {
+        Ok(message) => serde_json::to_value(message),
+        Err(amb::DecodingError::WrongType { .. }) => {
+            let state = amb::SyncState::decode(&buf)
+                .map_err(|e| SyncInspectError::Decoding { source: e })?;
+            serde_json::to_value(state)
+        }
+        Err(e) => return Err(SyncInspectError::Decoding { source: e }),
+    }
+    .map_err(|e| SyncInspectError::Serializing { source: e })?;
+    if is_tty {
+        colored_json::write_colored_json(&json, &mut output).unwrap();
+    } else {
+        let json = serde_json::to_string_pretty(&json).unwrap();
+        output
+            .write_all(&json.into_bytes())
+            .map_err(|e| SyncInspectError::WritingToOutput { source: e })?;
+    }
+    Ok(())
+}
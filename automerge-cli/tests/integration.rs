@@ -89,3 +89,67 @@ fn import_change_export() {
     });
     assert_eq!(result, expected);
 }
+
+#[test]
+fn sync_inspect_message_and_state() {
+    use automerge_backend::{Backend, SyncHave, SyncMessage, SyncState};
+    use automerge_frontend::{Frontend, InvalidChangeRequest, LocalChange, Path, Value};
+
+    let bin = env!("CARGO_BIN_EXE_automerge");
+    let mut frontend = Frontend::new();
+    let mut backend = Backend::new();
+    let change = frontend
+        .change::<_, _, InvalidChangeRequest>(Some("add a bird".into()), |d| {
+            d.add_change(LocalChange::set(
+                Path::root().key("bird"),
+                Value::from("magpie"),
+            ))
+        })
+        .unwrap()
+        .1
+        .unwrap();
+    backend.apply_local_change(change).unwrap();
+    let heads = serde_json::to_value(backend.get_heads()).unwrap();
+
+    // a peer which has asked for everything
+    let mut sync_state = SyncState::default();
+    let request = SyncMessage {
+        heads: Vec::new(),
+        need: Vec::new(),
+        have: vec![SyncHave::default()],
+        changes: Vec::new(),
+        document: None,
+    };
+    backend
+        .receive_sync_message(&mut sync_state, request)
+        .unwrap();
+    let message = backend.generate_sync_message(&mut sync_state).unwrap();
+
+    let stdout = cmd!(bin, "sync-inspect")
+        .stdin_bytes(message.encode().unwrap())
+        .read()
+        .unwrap();
+    let result: serde_json::Value = serde_json::from_str(stdout.as_str()).unwrap();
+    assert_eq!(result["heads"], heads);
+    assert_eq!(result["have"][0]["bloom"]["numEntries"], 1);
+    assert_eq!(result["changes"][0]["hash"], heads[0]);
+    assert_eq!(result["changes"][0]["seq"], 1);
+    assert_eq!(result["changes"][0]["message"], "add a bird");
+    assert_eq!(result["changes"][0]["numOps"], 1);
+    assert_eq!(result["document"], serde_json::Value::Null);
+
+    sync_state.shared_heads = backend.get_heads();
+    let stdout = cmd!(bin, "sync-inspect")
+        .stdin_bytes(sync_state.encode().unwrap())
+        .read()
+        .unwrap();
+    let result: serde_json::Value = serde_json::from_str(stdout.as_str()).unwrap();
+    assert_eq!(result["sharedHeads"], heads);
+
+    let invalid = cmd!(bin, "sync-inspect")
+        .stdin_bytes(vec![0x01, 0x02])
+        .stdout_capture()
+        .stderr_capture()
+        .run();
+    assert!(invalid.is_err());
+}
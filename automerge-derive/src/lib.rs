@@ -0,0 +1,270 @@
+//! `#[derive(Automerge)]`, which implements `automerge_frontend::Hydrate` and
+//! `automerge_frontend::Reconcile` for a struct with named fields or an enum whose variants have
+//! no fields.
+//!
+//! Structs are represented by maps with a key for each field, and enums by the name of the
+//! variant. Fields accept these attributes:
+//!
+//! - `#[automerge(rename = "name")]` uses `name` as the key of the field
+//! - `#[automerge(key)]` makes the field the key which identifies the struct within a list, see
+//!   `automerge_frontend::reconcile`
+//!
+//! Enum variants accept `#[automerge(rename = "name")]`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Error, Fields, Generics, Ident,
+    Lit, Meta, NestedMeta,
+};
+
+#[proc_macro_derive(Automerge, attributes(automerge))]
+pub fn derive_automerge(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let expanded = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields
+                .named
+                .iter()
+                .map(|field| {
+                    let attrs = Attrs::parse(&field.attrs)?;
+                    let ident = field.ident.clone().unwrap();
+                    Ok(Field {
+                        name: attrs.rename.unwrap_or_else(|| ident.to_string()),
+                        ident,
+                        key: attrs.key,
+                    })
+                })
+                .collect::<Result<Vec<_>, Error>>()
+                .and_then(|fields| derive_struct(&input, &fields)),
+            _ => Err(unsupported(&input)),
+        },
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| {
+                if !matches!(variant.fields, Fields::Unit) {
+                    return Err(unsupported(&input));
+                }
+                let attrs = Attrs::parse(&variant.attrs)?;
+                if attrs.key {
+                    return Err(Error::new_spanned(
+                        variant,
+                        "only struct fields can be keys",
+                    ));
+                }
+                Ok((
+                    variant.ident.clone(),
+                    attrs.rename.unwrap_or_else(|| variant.ident.to_string()),
+                ))
+            })
+            .collect::<Result<Vec<_>, Error>>()
+            .map(|variants| derive_enum(&input, &variants)),
+        Data::Union(_) => Err(unsupported(&input)),
+    };
+    expanded.unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn unsupported(input: &DeriveInput) -> Error {
+    Error::new_spanned(
+        &input.ident,
+        "Automerge can only be derived for structs with named fields and enums whose variants have no fields",
+    )
+}
+
+struct Field {
+    ident: Ident,
+    /// The key of the field in the map which represents the struct
+    name: String,
+    key: bool,
+}
+
+#[derive(Default)]
+struct Attrs {
+    rename: Option<String>,
+    key: bool,
+}
+
+impl Attrs {
+    fn parse(attrs: &[Attribute]) -> Result<Attrs, Error> {
+        let mut result = Attrs::default();
+        for attr in attrs.iter().filter(|a| a.path.is_ident("automerge")) {
+            let list = match attr.parse_meta()? {
+                Meta::List(list) => list,
+                meta => return Err(Error::new_spanned(meta, "expected #[automerge(...)]")),
+            };
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("key") => result.key = true,
+                    NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("rename") => {
+                        match nv.lit {
+                            Lit::Str(s) => result.rename = Some(s.value()),
+                            lit => return Err(Error::new_spanned(lit, "expected a string")),
+                        }
+                    }
+                    nested => {
+                        return Err(Error::new_spanned(
+                            nested,
+                            "unknown attribute, expected `key` or `rename = \"...\"`",
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// `generics` with `bound` added to each type parameter
+fn with_bound(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+    generics
+}
+
+fn derive_struct(input: &DeriveInput, fields: &[Field]) -> Result<TokenStream2, Error> {
+    let keys = fields.iter().filter(|f| f.key).collect::<Vec<_>>();
+    if keys.len() > 1 {
+        return Err(Error::new_spanned(
+            &keys[1].ident,
+            "a struct can only have one key field",
+        ));
+    }
+    let ident = &input.ident;
+    let idents = fields.iter().map(|f| &f.ident).collect::<Vec<_>>();
+    let names = fields.iter().map(|f| &f.name).collect::<Vec<_>>();
+
+    let generics = with_bound(&input.generics, quote!(::automerge_frontend::Hydrate));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let hydrate = quote! {
+        impl #impl_generics ::automerge_frontend::Hydrate for #ident #ty_generics #where_clause {
+            fn hydrate(
+                value: ::automerge_frontend::value_ref::ValueRef,
+            ) -> ::std::result::Result<Self, ::automerge_frontend::HydrateError> {
+                match &value {
+                    ::automerge_frontend::value_ref::ValueRef::Map(map) => Ok(#ident {
+                        #(
+                            #idents: match map.get(#names) {
+                                Some(value) => ::automerge_frontend::Hydrate::hydrate(value)?,
+                                None => ::automerge_frontend::Hydrate::hydrate_missing(#names)?,
+                            },
+                        )*
+                    }),
+                    _ => Err(::automerge_frontend::HydrateError::Unexpected {
+                        expected: ::std::any::type_name::<Self>(),
+                        found: value.value(),
+                    }),
+                }
+            }
+
+            fn hydrate_root(
+                root: ::automerge_frontend::value_ref::RootRef,
+            ) -> ::std::result::Result<Self, ::automerge_frontend::HydrateError> {
+                Ok(#ident {
+                    #(
+                        #idents: match root.get(#names) {
+                            Some(value) => ::automerge_frontend::Hydrate::hydrate(value)?,
+                            None => ::automerge_frontend::Hydrate::hydrate_missing(#names)?,
+                        },
+                    )*
+                })
+            }
+        }
+    };
+
+    let key = keys.first().map(|key| {
+        let (ident, name) = (&key.ident, &key.name);
+        quote! {
+            fn key(&self) -> ::std::option::Option<::automerge_frontend::Value> {
+                Some(::automerge_frontend::Reconcile::value(&self.#ident))
+            }
+
+            fn key_of(
+                value: &::automerge_frontend::Value,
+            ) -> ::std::option::Option<::automerge_frontend::Value> {
+                value.map()?.get(#name).cloned()
+            }
+        }
+    });
+    let generics = with_bound(&input.generics, quote!(::automerge_frontend::Reconcile));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let reconcile = quote! {
+        impl #impl_generics ::automerge_frontend::Reconcile for #ident #ty_generics #where_clause {
+            fn value(&self) -> ::automerge_frontend::Value {
+                let mut props = ::std::collections::HashMap::new();
+                #(
+                    props.insert(
+                        ::std::convert::From::from(#names),
+                        ::automerge_frontend::Reconcile::value(&self.#idents),
+                    );
+                )*
+                ::automerge_frontend::Value::Map(props)
+            }
+
+            fn reconcile(
+                &self,
+                doc: &mut dyn ::automerge_frontend::MutableDocument,
+                path: &::automerge_frontend::Path,
+                current: ::std::option::Option<&::automerge_frontend::Value>,
+            ) -> ::std::result::Result<(), ::automerge_frontend::InvalidChangeRequest> {
+                match current {
+                    Some(::automerge_frontend::Value::Map(props)) => {
+                        #(
+                            ::automerge_frontend::Reconcile::reconcile(
+                                &self.#idents,
+                                doc,
+                                &path.clone().key(#names),
+                                props.get(#names),
+                            )?;
+                        )*
+                        Ok(())
+                    }
+                    _ => doc.add_change(::automerge_frontend::LocalChange::set(
+                        path.clone(),
+                        ::automerge_frontend::Reconcile::value(self),
+                    )),
+                }
+            }
+
+            #key
+        }
+    };
+
+    Ok(quote! {
+        #hydrate
+        #reconcile
+    })
+}
+
+fn derive_enum(input: &DeriveInput, variants: &[(Ident, String)]) -> TokenStream2 {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let idents = variants.iter().map(|(ident, _)| ident).collect::<Vec<_>>();
+    let names = variants.iter().map(|(_, name)| name).collect::<Vec<_>>();
+    quote! {
+        impl #impl_generics ::automerge_frontend::Hydrate for #ident #ty_generics #where_clause {
+            fn hydrate(
+                value: ::automerge_frontend::value_ref::ValueRef,
+            ) -> ::std::result::Result<Self, ::automerge_frontend::HydrateError> {
+                match value.primitive().and_then(::automerge_frontend::Primitive::str) {
+                    #(Some(#names) => Ok(#ident::#idents),)*
+                    _ => Err(::automerge_frontend::HydrateError::Unexpected {
+                        expected: ::std::any::type_name::<Self>(),
+                        found: value.value(),
+                    }),
+                }
+            }
+        }
+
+        impl #impl_generics ::automerge_frontend::Reconcile for #ident #ty_generics #where_clause {
+            fn value(&self) -> ::automerge_frontend::Value {
+                match self {
+                    #(#ident::#idents => ::automerge_frontend::Value::from(#names),)*
+                }
+            }
+        }
+    }
+}
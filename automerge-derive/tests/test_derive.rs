@@ -0,0 +1,183 @@
+use automerge_backend::Backend;
+use automerge_frontend::{
+    value_serde::{Counter, Text},
+    Automerge, Frontend, HydrateError, Path, Primitive, Value,
+};
+use automerge_protocol as amp;
+
+#[derive(Debug, Clone, PartialEq, Automerge)]
+struct Garden {
+    name: String,
+    birds: Vec<Bird>,
+    notes: Text,
+    visits: Counter,
+    #[automerge(rename = "head-gardener")]
+    head_gardener: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Automerge)]
+struct Bird {
+    #[automerge(key)]
+    id: u64,
+    species: String,
+    size: Size,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Automerge)]
+enum Size {
+    Small,
+    #[automerge(rename = "large")]
+    Large,
+}
+
+fn bird(id: u64, species: &str) -> Bird {
+    Bird {
+        id,
+        species: species.to_string(),
+        size: Size::Small,
+    }
+}
+
+fn garden() -> Garden {
+    Garden {
+        name: "back garden".to_string(),
+        birds: vec![bird(1, "magpie"), bird(2, "wren")],
+        notes: Text("quiet".to_string()),
+        visits: Counter(1),
+        head_gardener: None,
+    }
+}
+
+/// A frontend holding `garden`, saved to the returned backend
+fn saved_garden(garden: &Garden) -> (Frontend, Backend) {
+    let mut doc = Frontend::new();
+    let mut backend = Backend::new();
+    let change = doc.reconcile(&Path::root(), garden).unwrap().unwrap();
+    let (patch, _) = backend.apply_local_change(change).unwrap();
+    doc.apply_patch(patch).unwrap();
+    (doc, backend)
+}
+
+#[test]
+fn reconciled_values_can_be_hydrated() {
+    let (doc, _) = saved_garden(&garden());
+    assert_eq!(doc.hydrate::<Garden>().unwrap(), garden());
+    assert_eq!(
+        doc.get_value(&Path::root().key("head-gardener")),
+        Some(Value::Primitive(Primitive::Null))
+    );
+    assert_eq!(
+        doc.get_value(&Path::root().key("birds").index(1).key("size")),
+        Some(Value::from("Small"))
+    );
+}
+
+#[test]
+fn reconciling_an_unchanged_value_makes_no_change() {
+    let (mut doc, _) = saved_garden(&garden());
+    assert_eq!(doc.reconcile(&Path::root(), &garden()), Ok(None));
+}
+
+#[test]
+fn only_changed_fields_are_written() {
+    let (mut doc, _) = saved_garden(&garden());
+    let mut new = garden();
+    new.visits = Counter(4);
+    new.notes = Text("very quiet".to_string());
+    new.birds[1].size = Size::Large;
+    let change = doc.reconcile(&Path::root(), &new).unwrap().unwrap();
+
+    let actions = change
+        .operations
+        .iter()
+        .map(|op| op.action.clone())
+        .collect::<Vec<_>>();
+    // the counter is incremented, "very " is inserted into the text and the size is set
+    assert_eq!(actions.len(), 7);
+    assert!(actions.contains(&amp::OpType::Inc(3)));
+    assert!(actions.contains(&amp::OpType::Set(amp::ScalarValue::Str("large".into()))));
+    assert_eq!(doc.hydrate::<Garden>().unwrap(), new);
+}
+
+#[test]
+fn list_elements_are_matched_by_key() {
+    let (mut doc, mut backend) = saved_garden(&garden());
+    let mut remote = Frontend::new();
+    remote.apply_patch(backend.get_patch().unwrap()).unwrap();
+
+    // the remote peer changes the wren while we add a robin before it and remove the magpie
+    let mut remote_garden = garden();
+    remote_garden.birds[1].size = Size::Large;
+    let remote_change = remote
+        .reconcile(&Path::root(), &remote_garden)
+        .unwrap()
+        .unwrap();
+    let mut local_garden = garden();
+    local_garden.birds = vec![bird(3, "robin"), bird(2, "wren")];
+    let local_change = doc
+        .reconcile(&Path::root(), &local_garden)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        local_change
+            .operations
+            .iter()
+            .filter(|op| matches!(op.action, amp::OpType::Del(_)))
+            .count(),
+        1
+    );
+
+    backend.apply_local_change(local_change).unwrap();
+    backend.apply_changes(vec![remote_change.into()]).unwrap();
+    let mut merged = Frontend::new();
+    merged.apply_patch(backend.get_patch().unwrap()).unwrap();
+    let merged = merged.hydrate::<Garden>().unwrap();
+    assert_eq!(
+        merged.birds,
+        vec![
+            bird(3, "robin"),
+            Bird {
+                size: Size::Large,
+                ..bird(2, "wren")
+            }
+        ]
+    );
+}
+
+#[test]
+fn hydrating_reports_missing_and_mismatched_fields() {
+    let (mut doc, _) = saved_garden(&garden());
+    let (_, change) = doc
+        .change::<_, _, automerge_frontend::InvalidChangeRequest>(None, |d| {
+            d.add_change(automerge_frontend::LocalChange::delete(
+                Path::root().key("head-gardener"),
+            ))
+        })
+        .unwrap();
+    assert!(change.is_some());
+    // optional fields may be missing
+    assert_eq!(doc.hydrate::<Garden>().unwrap(), garden());
+
+    doc.change::<_, _, automerge_frontend::InvalidChangeRequest>(None, |d| {
+        d.add_change(automerge_frontend::LocalChange::delete(
+            Path::root().key("name"),
+        ))
+    })
+    .unwrap();
+    assert_eq!(
+        doc.hydrate::<Garden>(),
+        Err(HydrateError::MissingField("name"))
+    );
+
+    doc.change::<_, _, automerge_frontend::InvalidChangeRequest>(None, |d| {
+        d.add_change(automerge_frontend::LocalChange::set(
+            Path::root().key("name"),
+            Value::Primitive(Primitive::Int(3)),
+        ))
+    })
+    .unwrap();
+    assert!(matches!(
+        doc.hydrate::<Garden>(),
+        Err(HydrateError::Unexpected { .. })
+    ));
+}
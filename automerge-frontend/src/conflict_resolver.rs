@@ -0,0 +1,50 @@
+use automerge_protocol::OpId;
+
+use crate::{Path, Primitive, Value};
+
+/// Chooses what to show for a location in the document which holds more than one value.
+///
+/// Concurrent changes which set the same key of a map or table, or the same element of a list,
+/// leave every value they set in the document. By default the value set by the op with the
+/// greatest ID is shown. A resolver set with
+/// [`Frontend::set_conflict_resolver`](crate::Frontend::set_conflict_resolver) replaces that
+/// choice wherever the frontend is read: [`Frontend::state`](crate::Frontend::state),
+/// [`Frontend::get_value`](crate::Frontend::get_value),
+/// [`Frontend::value_ref`](crate::Frontend::value_ref), and everything built on them such as
+/// hydrating and subscriptions. [`Frontend::get_conflicts`](crate::Frontend::get_conflicts) still
+/// returns every value. Resolvers only change what is read, the document itself and the changes
+/// made to it are the same whichever resolver is used.
+///
+/// Resolvers are called with the values of each conflicting location, so to be deterministic a
+/// resolver should only depend on `path` and `candidates`. Applications which want to resolve
+/// conflicts using information the frontend does not have, like the time of the change which set
+/// each value, can look it up using the actor ID and counter of each op ID.
+///
+/// Closures with the same signature as [`ConflictResolver::resolve`] are resolvers.
+///
+/// Conflicting characters in text objects always show the default.
+pub trait ConflictResolver {
+    /// Choose what to show at `path`. `candidates` holds each value at `path` and the ID of the
+    /// op which set it, sorted by op ID, so the default choice is the last one. Conflicts
+    /// within the candidates have already been resolved.
+    fn resolve(&self, path: &Path, candidates: &[(OpId, Value)]) -> Resolution;
+}
+
+impl<F> ConflictResolver for F
+where
+    F: Fn(&Path, &[(OpId, Value)]) -> Resolution,
+{
+    fn resolve(&self, path: &Path, candidates: &[(OpId, Value)]) -> Resolution {
+        self(path, candidates)
+    }
+}
+
+/// The choice made by a [`ConflictResolver`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    /// Show the value set by this op. If the op did not set one of the candidates the default
+    /// value is shown.
+    Winner(OpId),
+    /// Show a primitive made from the candidates, e.g. the largest of some conflicting numbers
+    Merged(Primitive),
+}
@@ -88,6 +88,8 @@ pub enum InvalidChangeRequest {
     CannotOverwriteCounter { path: Path },
     #[error("attempted an operation on a path that does not exist: {path:?}")]
     NoSuchPathError { path: Path },
+    #[error("no value at {path:?} was set by {opid:?}")]
+    NoSuchConflictingValue { path: Path, opid: amp::OpId },
     #[error("attempted to set a non map object {value:?} as the root")]
     CannotSetNonMapObjectAsRoot { value: Value },
     #[error("attempted to increment an object which is not a counter at {path:?}")]
@@ -100,13 +102,96 @@ pub enum InvalidChangeRequest {
     InsertPastEndOfSequence { path: Path, sequence_length: u64 },
     #[error("attempted to insert something into a text object which is not a character, object: {object:?}")]
     InsertNonTextInTextObject { path: Path, object: Value },
+    #[error("attempted to update the text of an object which is not text at {path:?}")]
+    UpdateTextForNonTextObject { path: Path },
+    #[error("expected {expected} at {path:?}")]
+    UnexpectedObjectType { path: Path, expected: &'static str },
+    #[error("attempted to insert a row which is not a map into the table at {path:?}: {row:?}")]
+    InvalidTableRow { path: Path, row: Value },
     #[error("attmpted to delete root object")]
     CannotDeleteRootObject,
+    #[error("attempted to move the root object")]
+    CannotMoveRootObject,
+    #[error("attempted to move the value at {from:?} to {to:?}, which is inside it")]
+    MoveIntoItself { from: Path, to: Path },
+    #[error("attempted to move a value into or out of a text object at {path:?}")]
+    MoveInTextObject { path: Path },
+    #[error(
+        "attempted to roll back to a savepoint which is not in this change or was rolled back past"
+    )]
+    NoSuchSavepoint,
+    #[error("attempted to use savepoints in a document which does not support them")]
+    SavepointsNotSupported,
     #[error("Attempted to access a missing index")]
     MissingIndexError {
         #[from]
         source: MissingIndexError,
     },
+    #[error("could not convert a value: {source}")]
+    SerdeError {
+        #[from]
+        source: SerdeError,
+    },
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("invalid path {path:?}, unexpected input at position {position}")]
+pub struct InvalidPath {
+    pub path: String,
+    pub position: usize,
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum JsonPatchError {
+    #[error("invalid JSON pointer: {0:?}")]
+    InvalidPointer(String),
+    #[error("no value exists at {0:?}")]
+    NoSuchPath(String),
+    #[error("test failed, the value at {path:?} is not {expected}")]
+    TestFailed {
+        path: String,
+        expected: serde_json::Value,
+    },
+    #[error("cannot move {from:?} into its own child {path:?}")]
+    MoveIntoChild { from: String, path: String },
+    #[error(transparent)]
+    InvalidChangeRequest(#[from] InvalidChangeRequest),
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SerdeError {
+    #[error("{0}")]
+    Custom(String),
+    #[error("map keys must be strings")]
+    KeyMustBeAString,
+    #[error("cannot represent {value:?} as {representation}")]
+    InvalidRepresentation {
+        representation: &'static str,
+        value: Value,
+    },
+}
+
+impl serde::ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerdeError::Custom(msg.to_string())
+    }
+}
+
+impl serde::de::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerdeError::Custom(msg.to_string())
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum HydrateError {
+    #[error("cannot read {found:?} as {expected}")]
+    Unexpected {
+        expected: &'static str,
+        found: Value,
+    },
+    #[error("missing field {0:?}")]
+    MissingField(&'static str),
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -0,0 +1,307 @@
+//! Conversion between patches and [RFC 6902](https://tools.ietf.org/html/rfc6902) JSON Patch
+//! documents.
+//!
+//! JSON has no text type, so any change to a text object is exported as a `replace` of the
+//! whole string. Likewise, incrementing a counter is exported as a `replace` with the new total.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::JsonPatchError, path, value_mut::ObjectMut, LocalChange, MutableDocument, PatchEvent,
+    Path, PathElement, Value,
+};
+
+/// A single JSON Patch operation. Paths are JSON Pointers, and the type serializes to (and from)
+/// the JSON representation defined by RFC 6902.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JsonPatchOp {
+    Add {
+        path: String,
+        value: serde_json::Value,
+    },
+    Remove {
+        path: String,
+    },
+    Replace {
+        path: String,
+        value: serde_json::Value,
+    },
+    Move {
+        from: String,
+        path: String,
+    },
+    Copy {
+        from: String,
+        path: String,
+    },
+    Test {
+        path: String,
+        value: serde_json::Value,
+    },
+}
+
+/// Convert `events` into JSON Patch operations, looking up the values of text objects and
+/// counters with `get_value`, which should return values from the document after the events
+pub(crate) fn from_events<F>(events: &[PatchEvent], get_value: F) -> Vec<JsonPatchOp>
+where
+    F: Fn(&Path) -> Option<Value>,
+{
+    let mut ops = Vec::new();
+    let replace_with_current = |ops: &mut Vec<JsonPatchOp>, path: &Path| {
+        let op = JsonPatchOp::Replace {
+            path: path.to_json_pointer(),
+            value: get_value(path)
+                .map(|v| v.to_json())
+                .unwrap_or(serde_json::Value::Null),
+        };
+        // consecutive edits to the same text object become a single replace
+        if ops.last() != Some(&op) {
+            ops.push(op);
+        }
+    };
+    let is_text = |path: &Path| matches!(get_value(path), Some(Value::Text(_)));
+
+    for event in events {
+        match event {
+            PatchEvent::Put { path, value } => {
+                let parent = path.parent();
+                if is_text(&parent) {
+                    replace_with_current(&mut ops, &parent)
+                } else if matches!(path.name(), Some(PathElement::Index(_))) {
+                    ops.push(JsonPatchOp::Replace {
+                        path: path.to_json_pointer(),
+                        value: value.to_json(),
+                    })
+                } else {
+                    // adding an object member which already exists replaces it
+                    ops.push(JsonPatchOp::Add {
+                        path: path.to_json_pointer(),
+                        value: value.to_json(),
+                    })
+                }
+            }
+            PatchEvent::Insert {
+                path,
+                index,
+                values,
+            } => {
+                if is_text(path) {
+                    replace_with_current(&mut ops, path)
+                } else {
+                    for (i, value) in values.iter().enumerate() {
+                        ops.push(JsonPatchOp::Add {
+                            path: path.clone().index(index + i as u32).to_json_pointer(),
+                            value: value.to_json(),
+                        })
+                    }
+                }
+            }
+            PatchEvent::Delete { path, index, count } => {
+                if is_text(path) {
+                    replace_with_current(&mut ops, path)
+                } else {
+                    let path = path.clone().index(*index).to_json_pointer();
+                    for _ in 0..*count {
+                        ops.push(JsonPatchOp::Remove { path: path.clone() })
+                    }
+                }
+            }
+            PatchEvent::DeleteKey { path } => ops.push(JsonPatchOp::Remove {
+                path: path.to_json_pointer(),
+            }),
+            PatchEvent::Increment { path, .. } => replace_with_current(&mut ops, path),
+        }
+    }
+    ops
+}
+
+/// Apply a single JSON Patch operation to `doc`
+pub(crate) fn apply_op(
+    doc: &mut dyn MutableDocument,
+    op: &JsonPatchOp,
+) -> Result<(), JsonPatchError> {
+    match op {
+        JsonPatchOp::Add { path, value } => add(doc, path, Value::from_json(value)),
+        JsonPatchOp::Remove { path } => {
+            let target = existing(doc, path)?;
+            doc.add_change(LocalChange::delete(target))?;
+            Ok(())
+        }
+        JsonPatchOp::Replace { path, value } => {
+            let target = existing(doc, path)?;
+            set(doc, target, Value::from_json(value))
+        }
+        JsonPatchOp::Move { from, path } => {
+            if path.starts_with(from.as_str()) && path[from.len()..].starts_with('/') {
+                return Err(JsonPatchError::MoveIntoChild {
+                    from: from.clone(),
+                    path: path.clone(),
+                });
+            }
+            let (source, value) = existing_value(doc, from)?;
+            doc.add_change(LocalChange::delete(source))?;
+            add(doc, path, value)
+        }
+        JsonPatchOp::Copy { from, path } => {
+            let value = existing_value(doc, from)?.1;
+            add(doc, path, value)
+        }
+        JsonPatchOp::Test { path, value } => {
+            let actual = existing_value(doc, path)?.1;
+            if json_eq(&actual.to_json(), value) {
+                Ok(())
+            } else {
+                Err(JsonPatchError::TestFailed {
+                    path: path.clone(),
+                    expected: value.clone(),
+                })
+            }
+        }
+    }
+}
+
+fn add(doc: &mut dyn MutableDocument, pointer: &str, value: Value) -> Result<(), JsonPatchError> {
+    let target = resolve(doc, pointer)?;
+    if target.in_sequence {
+        doc.add_change(LocalChange::insert(target.path, value))?;
+        Ok(())
+    } else {
+        set(doc, target.path, value)
+    }
+}
+
+/// Set the value at `target`. Replacing the root sets each key of the new value, removing any
+/// key it doesn't have.
+fn set(doc: &mut dyn MutableDocument, target: Path, value: Value) -> Result<(), JsonPatchError> {
+    if !target.is_root() {
+        doc.add_change(LocalChange::set(target, value))?;
+        return Ok(());
+    }
+    let new = match value {
+        Value::Map(new) => new,
+        value => {
+            return Err(JsonPatchError::InvalidChangeRequest(
+                crate::InvalidChangeRequest::CannotSetNonMapObjectAsRoot { value },
+            ))
+        }
+    };
+    if let Some(Value::Map(old)) = doc.value_at_path(&Path::root()) {
+        for key in old.keys().filter(|key| !new.contains_key(*key)) {
+            doc.add_change(LocalChange::delete(Path::root().key(key.clone())))?;
+        }
+    }
+    for (key, value) in new {
+        doc.add_change(LocalChange::set(Path::root().key(key), value))?;
+    }
+    Ok(())
+}
+
+/// Resolve `pointer` to a path which must exist
+fn existing(doc: &mut dyn MutableDocument, pointer: &str) -> Result<Path, JsonPatchError> {
+    match resolve(doc, pointer)? {
+        Target {
+            path, exists: true, ..
+        } => Ok(path),
+        Target { exists: false, .. } => Err(JsonPatchError::NoSuchPath(pointer.to_string())),
+    }
+}
+
+/// Resolve `pointer` to a path and the value it points to, which must exist
+fn existing_value(
+    doc: &mut dyn MutableDocument,
+    pointer: &str,
+) -> Result<(Path, Value), JsonPatchError> {
+    let path = existing(doc, pointer)?;
+    match doc.value_at_path(&path) {
+        Some(value) => Ok((path, value)),
+        None => Err(JsonPatchError::NoSuchPath(pointer.to_string())),
+    }
+}
+
+/// The location a JSON Pointer refers to
+struct Target {
+    path: Path,
+    /// Whether the location is an element of a list or a text object
+    in_sequence: bool,
+    /// Whether there is a value at the location
+    exists: bool,
+}
+
+/// Resolve `pointer` against the current state of `doc`, so that a reference token is treated as
+/// a key or an index depending on the object it refers into. The `-` token resolves to the index
+/// after the last element of a sequence. The final token need not exist.
+///
+/// Each token is resolved against the object the previous one led to, so only the objects along
+/// the path are looked at.
+fn resolve(doc: &mut dyn MutableDocument, pointer: &str) -> Result<Target, JsonPatchError> {
+    if pointer.is_empty() {
+        return Ok(Target {
+            path: Path::root(),
+            in_sequence: false,
+            exists: true,
+        });
+    }
+    if !pointer.starts_with('/') {
+        return Err(JsonPatchError::InvalidPointer(pointer.to_string()));
+    }
+    let mut current = Some(ObjectMut::from(doc.root_mut()));
+    let mut path = Path::root();
+    let mut in_sequence = false;
+    for token in path::json_pointer_tokens(pointer) {
+        let element = match &current {
+            Some(ObjectMut::Map(_)) => PathElement::Key(token.into()),
+            Some(ObjectMut::List(list)) => {
+                PathElement::Index(parse_index(&token, list.len(), pointer)?)
+            }
+            Some(ObjectMut::Text(text)) => {
+                PathElement::Index(parse_index(&token, text.len(), pointer)?)
+            }
+            Some(ObjectMut::Leaf) | None => {
+                return Err(JsonPatchError::NoSuchPath(pointer.to_string()))
+            }
+        };
+        in_sequence = matches!(element, PathElement::Index(_));
+        path = match &element {
+            PathElement::Key(k) => path.key(k.clone()),
+            PathElement::Index(i) => path.index(*i),
+        };
+        current = current.and_then(|object| object.into_child(&element));
+    }
+    Ok(Target {
+        path,
+        in_sequence,
+        exists: current.is_some(),
+    })
+}
+
+fn parse_index(token: &str, len: usize, pointer: &str) -> Result<u32, JsonPatchError> {
+    if token == "-" {
+        return Ok(len as u32);
+    }
+    // RFC 6901 doesn't allow leading zeros
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) {
+        return Err(JsonPatchError::InvalidPointer(pointer.to_string()));
+    }
+    token
+        .parse()
+        .map_err(|_| JsonPatchError::InvalidPointer(pointer.to_string()))
+}
+
+/// Compare JSON values as RFC 6902 `test` does, where numbers are equal if their values are
+/// equal regardless of how they are represented
+pub(crate) fn json_eq(left: &serde_json::Value, right: &serde_json::Value) -> bool {
+    use serde_json::Value as Json;
+    match (left, right) {
+        (Json::Number(l), Json::Number(r)) => l.as_f64() == r.as_f64(),
+        (Json::Array(l), Json::Array(r)) => {
+            l.len() == r.len() && l.iter().zip(r).all(|(l, r)| json_eq(l, r))
+        }
+        (Json::Object(l), Json::Object(r)) => {
+            l.len() == r.len()
+                && l.iter()
+                    .all(|(k, l)| r.get(k).map(|r| json_eq(l, r)).unwrap_or(false))
+        }
+        _ => left == right,
+    }
+}
@@ -0,0 +1,92 @@
+//! Updating part of a document to match a new JSON value by making only the changes needed,
+//! rather than replacing the whole value, so that concurrent changes to the parts which are the
+//! same in both are preserved.
+
+use crate::{
+    json_patch::json_eq,
+    sequence_diff::{self, Step},
+    InvalidChangeRequest, LocalChange, MutableDocument, Path, Primitive, Value,
+};
+
+pub(crate) fn update(
+    doc: &mut dyn MutableDocument,
+    path: &Path,
+    new: &serde_json::Value,
+) -> Result<(), InvalidChangeRequest> {
+    match doc.value_at_path(path) {
+        Some(current) => update_value(doc, path, &current, new),
+        None => doc.add_change(LocalChange::set(path.clone(), Value::from_json(new))),
+    }
+}
+
+fn update_value(
+    doc: &mut dyn MutableDocument,
+    path: &Path,
+    current: &Value,
+    new: &serde_json::Value,
+) -> Result<(), InvalidChangeRequest> {
+    use serde_json::Value as Json;
+    match (current, new) {
+        (Value::Map(props), Json::Object(new_props))
+        | (Value::Table(props), Json::Object(new_props)) => {
+            for key in props.keys() {
+                if !new_props.contains_key(key.as_str()) {
+                    doc.add_change(LocalChange::delete(path.clone().key(key.clone())))?;
+                }
+            }
+            for (key, new) in new_props {
+                let path = path.clone().key(key.as_str());
+                match props.get(key.as_str()) {
+                    Some(current) => update_value(doc, &path, current, new)?,
+                    None => doc.add_change(LocalChange::set(path, Value::from_json(new)))?,
+                }
+            }
+            Ok(())
+        }
+        (Value::List(elements), Json::Array(new_elements)) => {
+            update_list(doc, path, elements, new_elements)
+        }
+        (Value::Text(_), Json::String(new_text)) => doc.update_text(path, new_text),
+        (Value::Primitive(Primitive::Counter(current)), Json::Number(new)) => {
+            // incrementing rather than setting the counter preserves concurrent increments
+            match new.as_i64() {
+                Some(new) if new == *current => Ok(()),
+                Some(new) => doc.add_change(LocalChange::increment_by(path.clone(), new - current)),
+                None => Err(InvalidChangeRequest::CannotOverwriteCounter { path: path.clone() }),
+            }
+        }
+        (current, new) if json_eq(&current.to_json(), new) => Ok(()),
+        (_, new) => doc.add_change(LocalChange::set(path.clone(), Value::from_json(new))),
+    }
+}
+
+fn update_list(
+    doc: &mut dyn MutableDocument,
+    path: &Path,
+    elements: &[Value],
+    new_elements: &[serde_json::Value],
+) -> Result<(), InvalidChangeRequest> {
+    let current = elements.iter().map(Value::to_json).collect::<Vec<_>>();
+    let edits = sequence_diff::diff(current.len(), new_elements.len(), |i, j| {
+        json_eq(&current[i], &new_elements[j])
+    });
+
+    // elements which are replaced one for one are updated in place so that changes within them
+    // are kept
+    sequence_diff::apply(edits, true, |step| match step {
+        Step::Keep { .. } => Ok(()),
+        Step::Replace { index, old, new } => update_value(
+            doc,
+            &path.clone().index(index),
+            &elements[old],
+            &new_elements[new],
+        ),
+        Step::Delete { index, .. } => {
+            doc.add_change(LocalChange::delete(path.clone().index(index)))
+        }
+        Step::Insert { index, new } => doc.add_change(LocalChange::insert(
+            path.clone().index(index),
+            Value::from_json(&new_elements[new]),
+        )),
+    })
+}
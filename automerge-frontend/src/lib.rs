@@ -2,22 +2,48 @@ use automerge_protocol as amp;
 use automerge_protocol::{ActorId, ChangeHash, ObjectId, Op, OpId, Patch};
 use value_ref::RootRef;
 
+mod conflict_resolver;
 mod error;
+mod json_patch;
+mod json_update;
 mod mutation;
+mod patch_event;
 mod path;
+pub mod reconcile;
+mod sequence_diff;
+mod span;
 mod state_tree;
+mod subscriptions;
+mod table_index;
 mod value;
+pub mod value_mut;
 pub mod value_ref;
+pub mod value_serde;
 
 use std::{collections::HashMap, convert::TryFrom, error::Error, fmt::Debug};
 
+use smol_str::SmolStr;
+
+#[cfg(feature = "derive")]
+pub use automerge_derive::Automerge;
+pub use conflict_resolver::{ConflictResolver, Resolution};
 pub use error::{
-    AutomergeFrontendError, InvalidChangeRequest, InvalidInitialStateError, InvalidPatch,
+    AutomergeFrontendError, HydrateError, InvalidChangeRequest, InvalidInitialStateError,
+    InvalidPatch, InvalidPath, JsonPatchError, SerdeError,
 };
-pub use mutation::{LocalChange, MutableDocument};
+pub use json_patch::JsonPatchOp;
+pub use mutation::{LocalChange, MutableDocument, Savepoint};
+pub use patch_event::PatchEvent;
 pub use path::Path;
 use path::PathElement;
+pub use reconcile::{Hydrate, Reconcile};
+pub use span::{Bias, Span, SpanId};
 use state_tree::ResolvedPath;
+pub use state_tree::{Conflict, DocumentConflicts};
+pub use subscriptions::SubscriptionId;
+use subscriptions::Subscriptions;
+pub use table_index::TableIndex;
+use table_index::TableIndexes;
 pub use value::{Conflicts, Cursor, Primitive, Value};
 
 /// Tracks the possible states of the frontend
@@ -55,12 +81,6 @@ enum FrontendState {
         reconciled_root_state: state_tree::StateTree,
         /// The optimistic version of the root state that the user manipulates.
         optimistically_updated_root_state: state_tree::StateTree,
-        /// A flag to track whether this state has seen a patch from the backend that represented
-        /// changes from another actor.
-        ///
-        /// If this is true then our optimistic state will not equal the reconciled state so we may
-        /// need to do extra work when moving to the reconciled state.
-        seen_non_local_patch: bool,
         /// The maximum operation observed.
         max_op: u64,
     },
@@ -91,8 +111,7 @@ impl FrontendState {
             FrontendState::WaitingForInFlightRequests {
                 in_flight_requests,
                 reconciled_root_state,
-                optimistically_updated_root_state,
-                seen_non_local_patch,
+                optimistically_updated_root_state: _,
                 max_op: _,
             } => {
                 let mut new_in_flight_requests = in_flight_requests.clone();
@@ -100,7 +119,6 @@ impl FrontendState {
                 // to a local change (i.e it came from Backend::apply_local_change
                 // so we don't need to apply it, we just need to remove it from
                 // the in_flight_requests vector
-                let mut is_local = false;
                 if let (Some(patch_actor), Some(patch_seq)) = (&patch.actor, patch.seq) {
                     // If this is a local change corresponding to our actor then we
                     // need to match it against in flight requests
@@ -113,7 +131,6 @@ impl FrontendState {
                                 actual: patch_seq,
                             });
                         }
-                        is_local = true;
                         // unwrap should be fine here as `in_flight_requests` should never have zero length
                         // because we transition to reconciled state when that happens
                         let (_, remaining_requests) = new_in_flight_requests.split_first().unwrap();
@@ -124,20 +141,19 @@ impl FrontendState {
 
                 reconciled_root_state.apply_diff(checked_diff);
                 if new_in_flight_requests.is_empty() {
-                    if *seen_non_local_patch {
-                        *optimistically_updated_root_state = reconciled_root_state.clone();
-                    }
+                    // The optimistic state can differ from the reconciled state even if every patch
+                    // was local, e.g. a local set only overwrites the winning value of a conflict
+                    // so the backend keeps the other values, whereas the optimistic state drops
+                    // them. So start again from the reconciled state.
+                    let reconciled_root_state = std::mem::take(reconciled_root_state);
                     *self = FrontendState::Reconciled {
-                        reconciled_root_state: std::mem::take(reconciled_root_state),
-                        reconciled_root_state_copy_for_rollback: std::mem::take(
-                            optimistically_updated_root_state,
-                        ),
+                        reconciled_root_state_copy_for_rollback: reconciled_root_state.clone(),
+                        reconciled_root_state,
                         max_op: patch.max_op,
                         deps_of_last_received_patch: patch.deps,
                     }
                 } else {
                     *in_flight_requests = new_in_flight_requests;
-                    *seen_non_local_patch = *seen_non_local_patch || !is_local;
                     // don't update max_op as we have progressed since then
                 }
                 Ok(())
@@ -160,12 +176,34 @@ impl FrontendState {
         }
     }
 
+    /// The paths which applying `patch` will change, this must be called before the patch is
+    /// applied
+    fn changed_paths(&self, patch: &Patch) -> Vec<Path> {
+        self.reconciled_root_state().changed_paths(&patch.diffs)
+    }
+
+    fn reconciled_root_state(&self) -> &state_tree::StateTree {
+        match self {
+            FrontendState::WaitingForInFlightRequests {
+                reconciled_root_state,
+                ..
+            }
+            | FrontendState::Reconciled {
+                reconciled_root_state,
+                ..
+            } => reconciled_root_state,
+        }
+    }
+
     fn get_object_id(&self, path: &Path) -> Option<ObjectId> {
         self.resolve_path(path).and_then(|r| r.object_id())
     }
 
-    fn get_value(&self, path: &Path) -> Option<Value> {
-        self.resolve_path(path).map(|r| r.default_value())
+    fn get_value(&self, path: &Path, resolver: Option<&dyn ConflictResolver>) -> Option<Value> {
+        match resolver {
+            Some(resolver) => self.value_ref(Some(resolver)).get_path(path),
+            None => self.resolve_path(path).map(|r| r.default_value()),
+        }
     }
 
     fn resolve_path(&self, path: &Path) -> Option<ResolvedPath> {
@@ -201,7 +239,6 @@ impl FrontendState {
                 in_flight_requests,
                 reconciled_root_state: _,
                 optimistically_updated_root_state,
-                seen_non_local_patch: _,
                 max_op,
             } => {
                 let mut mutation_tracker = mutation::MutationTracker::new(
@@ -219,7 +256,8 @@ impl FrontendState {
                         return Err(e);
                     }
                 };
-                *max_op = mutation_tracker.max_op;
+                *max_op = mutation_tracker.max_op();
+                let changed_paths = mutation_tracker.changed_paths();
                 let ops = mutation_tracker.ops();
                 if !ops.is_empty() {
                     // we actually have made a change so expect it to be sent to the backend
@@ -229,6 +267,7 @@ impl FrontendState {
                 Ok(OptimisticChangeResult {
                     ops,
                     deps: Vec::new(),
+                    changed_paths,
                     closure_result: result,
                 })
             }
@@ -251,7 +290,8 @@ impl FrontendState {
                         return Err(e);
                     }
                 };
-                *max_op = mutation_tracker.max_op;
+                *max_op = mutation_tracker.max_op();
+                let changed_paths = mutation_tracker.changed_paths();
                 let ops = mutation_tracker.ops();
                 let in_flight_requests = vec![seq];
                 let deps = deps_of_last_received_patch.clone();
@@ -261,7 +301,6 @@ impl FrontendState {
                         optimistically_updated_root_state: std::mem::take(
                             reconciled_root_state_copy_for_rollback,
                         ),
-                        seen_non_local_patch: false,
                         reconciled_root_state: std::mem::take(reconciled_root_state),
                         max_op: *max_op,
                     }
@@ -276,12 +315,69 @@ impl FrontendState {
                 Ok(OptimisticChangeResult {
                     ops,
                     deps,
+                    changed_paths,
                     closure_result: result,
                 })
             }
         }
     }
 
+    fn track_span(&mut self, id: SpanId, span: &Span) {
+        match self {
+            FrontendState::WaitingForInFlightRequests {
+                reconciled_root_state,
+                optimistically_updated_root_state,
+                ..
+            } => {
+                reconciled_root_state.track_span(id, span);
+                optimistically_updated_root_state.track_span(id, span);
+            }
+            FrontendState::Reconciled {
+                reconciled_root_state,
+                reconciled_root_state_copy_for_rollback,
+                ..
+            } => {
+                reconciled_root_state.track_span(id, span);
+                reconciled_root_state_copy_for_rollback.track_span(id, span);
+            }
+        }
+    }
+
+    fn untrack_span(&mut self, id: SpanId) -> bool {
+        match self {
+            FrontendState::WaitingForInFlightRequests {
+                reconciled_root_state,
+                optimistically_updated_root_state,
+                ..
+            } => {
+                optimistically_updated_root_state.untrack_span(id);
+                reconciled_root_state.untrack_span(id)
+            }
+            FrontendState::Reconciled {
+                reconciled_root_state,
+                reconciled_root_state_copy_for_rollback,
+                ..
+            } => {
+                reconciled_root_state_copy_for_rollback.untrack_span(id);
+                reconciled_root_state.untrack_span(id)
+            }
+        }
+    }
+
+    /// The state tree which is shown to the user, including any optimistic changes
+    fn visible_root_state(&self) -> &state_tree::StateTree {
+        match self {
+            FrontendState::WaitingForInFlightRequests {
+                optimistically_updated_root_state,
+                ..
+            } => optimistically_updated_root_state,
+            FrontendState::Reconciled {
+                reconciled_root_state,
+                ..
+            } => reconciled_root_state,
+        }
+    }
+
     fn in_flight_requests(&self) -> Vec<u64> {
         match self {
             FrontendState::WaitingForInFlightRequests {
@@ -311,16 +407,29 @@ impl FrontendState {
         }
     }
 
-    fn value_ref(&self) -> RootRef {
+    fn conflicts(&self) -> DocumentConflicts<'_> {
+        match self {
+            FrontendState::WaitingForInFlightRequests {
+                optimistically_updated_root_state,
+                ..
+            } => optimistically_updated_root_state.conflicts(),
+            FrontendState::Reconciled {
+                reconciled_root_state,
+                ..
+            } => reconciled_root_state.conflicts(),
+        }
+    }
+
+    fn value_ref<'a>(&'a self, resolver: Option<&'a dyn ConflictResolver>) -> RootRef<'a> {
         match self {
             FrontendState::WaitingForInFlightRequests {
                 optimistically_updated_root_state,
                 ..
-            } => optimistically_updated_root_state.value_ref(),
+            } => optimistically_updated_root_state.value_ref(resolver),
             FrontendState::Reconciled {
                 reconciled_root_state,
                 ..
-            } => reconciled_root_state.value_ref(),
+            } => reconciled_root_state.value_ref(resolver),
         }
     }
 }
@@ -336,6 +445,15 @@ pub struct Frontend {
     cached_value: Option<Value>,
     /// A function for generating timestamps
     timestamper: Box<dyn Fn() -> Option<i64>>,
+    /// Chooses which value to show where there are conflicts, if not the default
+    conflict_resolver: Option<Box<dyn ConflictResolver>>,
+    subscriptions: Subscriptions,
+    /// Paths changed by remote patches which subscribers have not been told about yet because
+    /// the changes are not visible until our in flight requests are reconciled
+    pending_changed_paths: Vec<Path>,
+    table_indexes: TableIndexes,
+    /// The ID of the next span added with `add_span`
+    next_span_id: u64,
 }
 
 impl Debug for Frontend {
@@ -346,6 +464,11 @@ impl Debug for Frontend {
             state,
             cached_value,
             timestamper: _,
+            conflict_resolver: _,
+            subscriptions,
+            pending_changed_paths,
+            table_indexes,
+            next_span_id: _,
         } = self;
         {
             let mut builder = f.debug_struct("Frontend");
@@ -353,6 +476,9 @@ impl Debug for Frontend {
             let _ = builder.field("seq", &seq);
             let _ = builder.field("state", &state);
             let _ = builder.field("cached_value", &cached_value);
+            let _ = builder.field("subscriptions", &subscriptions);
+            let _ = builder.field("pending_changed_paths", &pending_changed_paths);
+            let _ = builder.field("table_indexes", &table_indexes);
             builder.finish()
         }
     }
@@ -408,6 +534,11 @@ impl Frontend {
             },
             cached_value: None,
             timestamper: t,
+            conflict_resolver: None,
+            subscriptions: Subscriptions::default(),
+            pending_changed_paths: Vec::new(),
+            table_indexes: TableIndexes::default(),
+            next_span_id: 0,
         }
     }
 
@@ -461,14 +592,28 @@ impl Frontend {
         if let Some(ref v) = self.cached_value {
             v
         } else {
-            let value = self.state.value();
+            let value = match &self.conflict_resolver {
+                Some(resolver) => self.state.value_ref(Some(resolver.as_ref())).value(),
+                None => self.state.value(),
+            };
             self.cached_value = Some(value);
             self.cached_value.as_ref().unwrap()
         }
     }
 
     pub fn value_ref(&self) -> RootRef {
-        self.state.value_ref()
+        self.state.value_ref(self.conflict_resolver.as_deref())
+    }
+
+    /// Use `resolver` to choose which value to show where there are conflicts, or the default if
+    /// `resolver` is `None`. See [`ConflictResolver`] for details.
+    pub fn set_conflict_resolver(&mut self, resolver: Option<Box<dyn ConflictResolver>>) {
+        self.conflict_resolver = resolver;
+        self.cached_value = None;
+        let state = &self.state;
+        let resolver = self.conflict_resolver.as_deref();
+        self.table_indexes
+            .rebuild(|path| state.get_value(path, resolver));
     }
 
     pub fn change<F, O, E>(
@@ -498,6 +643,7 @@ impl Frontend {
                 operations: change_result.ops,
                 extra_bytes: Vec::new(),
             };
+            self.paths_changed(&change_result.changed_paths);
             Ok((change_result.closure_result, Some(change)))
         } else {
             Ok((change_result.closure_result, None))
@@ -511,10 +657,193 @@ impl Frontend {
                 self.seq = *seq;
             }
         }
+        // patches for our own changes were applied optimistically, and subscribers notified,
+        // when the change was made
+        let is_local = patch.actor.as_ref() == Some(&self.actor_id) && patch.seq.is_some();
+        let changed_paths =
+            if is_local || (self.subscriptions.is_empty() && self.table_indexes.is_empty()) {
+                Vec::new()
+            } else {
+                self.state.changed_paths(&patch)
+            };
         self.state.apply_remote_patch(&self.actor_id, patch)?;
+        self.pending_changed_paths.extend(changed_paths);
+        if self.state.in_flight_requests().is_empty() && !self.pending_changed_paths.is_empty() {
+            let changed_paths = std::mem::take(&mut self.pending_changed_paths);
+            self.paths_changed(&changed_paths);
+        }
         Ok(())
     }
 
+    /// Apply `patch` like [`Frontend::apply_patch`], returning the changes it made as
+    /// [`PatchEvent`]s addressed by path.
+    ///
+    /// The events describe the state the backend has acknowledged, which is what
+    /// [`Frontend::state`] shows once there are no in flight requests.
+    pub fn apply_patch_with_events(
+        &mut self,
+        patch: Patch,
+    ) -> Result<Vec<PatchEvent>, InvalidPatch> {
+        let before = self
+            .state
+            .reconciled_root_state()
+            .patch_events_before(&patch.diffs);
+        let diffs = patch.diffs.clone();
+        self.apply_patch(patch)?;
+        Ok(self
+            .state
+            .reconciled_root_state()
+            .patch_events(&before, &diffs))
+    }
+
+    /// Apply `patch` like [`Frontend::apply_patch`], returning the changes it made as
+    /// [RFC 6902](https://tools.ietf.org/html/rfc6902) JSON Patch operations.
+    pub fn apply_patch_with_json_patch(
+        &mut self,
+        patch: Patch,
+    ) -> Result<Vec<JsonPatchOp>, InvalidPatch> {
+        let events = self.apply_patch_with_events(patch)?;
+        let root = self.state.reconciled_root_state();
+        Ok(json_patch::from_events(&events, |path| {
+            root.resolve_path(path).map(|r| r.default_value())
+        }))
+    }
+
+    /// Apply a list of [RFC 6902](https://tools.ietf.org/html/rfc6902) JSON Patch operations as
+    /// a single change.
+    ///
+    /// The operations are applied in order and if any of them fails, including a `test` which
+    /// does not match, the document is left unchanged.
+    pub fn apply_json_patch(
+        &mut self,
+        ops: &[JsonPatchOp],
+    ) -> Result<Option<amp::Change>, JsonPatchError> {
+        let (_, change) = self.change(None, |doc| {
+            ops.iter().try_for_each(|op| json_patch::apply_op(doc, op))
+        })?;
+        Ok(change)
+    }
+
+    /// Update the value at `path` to match `new`, making only the changes needed rather than
+    /// replacing the whole value.
+    ///
+    /// Keys which are in both the old and new objects are updated recursively, lists and text are
+    /// diffed so that only the elements which were added or removed are inserted or deleted, and
+    /// counters are incremented. This means that concurrent changes to parts of the
+    /// document which `new` does not change are preserved.
+    pub fn update_from_json(
+        &mut self,
+        path: &Path,
+        new: &serde_json::Value,
+    ) -> Result<Option<amp::Change>, InvalidChangeRequest> {
+        let (_, change) = self.change(None, |doc| json_update::update(doc, path, new))?;
+        Ok(change)
+    }
+
+    /// Read a `T` from the document
+    pub fn hydrate<T: Hydrate>(&self) -> Result<T, HydrateError> {
+        T::hydrate_root(self.value_ref())
+    }
+
+    /// Change the value at `path` to `value`, making only the changes needed to reconcile the
+    /// document with `value`, as described in [`Reconcile`].
+    pub fn reconcile<T>(
+        &mut self,
+        path: &Path,
+        value: &T,
+    ) -> Result<Option<amp::Change>, InvalidChangeRequest>
+    where
+        T: Reconcile + ?Sized,
+    {
+        let (_, change) = self.change(None, |doc| reconcile::reconcile(doc, path, value))?;
+        Ok(change)
+    }
+
+    /// Call `callback` with the new value at `path` (or `None` if there is no longer a value
+    /// there) whenever a local change or a patch changes anything at or below `path`, or replaces
+    /// one of its ancestors.
+    ///
+    /// Changes from patches are reported once the frontend has no in flight requests, as that is
+    /// when they become visible in [`Frontend::state`].
+    pub fn subscribe<F>(&mut self, path: Path, callback: F) -> SubscriptionId
+    where
+        F: FnMut(Option<&Value>) + 'static,
+    {
+        self.subscriptions.add(path, Box::new(callback))
+    }
+
+    /// Remove a subscription, returning whether it existed.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        self.subscriptions.remove(id)
+    }
+
+    /// Maintain an index of the rows of the table at `table` by the value of `column`, which can
+    /// be read with [`Frontend::table_index`]. The index is kept up to date as local changes and
+    /// patches are applied, following what [`Frontend::state`] shows, so changes from patches are
+    /// indexed once the frontend has no in flight requests. If `table` is not a table the index
+    /// is empty until a table is put there.
+    ///
+    /// Returns false if there already was an index of `column` of `table`.
+    pub fn add_table_index<S: Into<SmolStr>>(&mut self, table: Path, column: S) -> bool {
+        let state = &self.state;
+        let resolver = self.conflict_resolver.as_deref();
+        self.table_indexes
+            .add(table, column.into(), |path| state.get_value(path, resolver))
+    }
+
+    /// Stop maintaining an index, returning whether it existed.
+    pub fn remove_table_index(&mut self, table: &Path, column: &str) -> bool {
+        self.table_indexes.remove(table, column)
+    }
+
+    /// The index of `column` of the table at `table`, if one was added with
+    /// [`Frontend::add_table_index`]
+    pub fn table_index(&self, table: &Path, column: &str) -> Option<&TableIndex> {
+        self.table_indexes.get(table, column)
+    }
+
+    /// Track `span` as the document changes, so that the indices of the elements it covers can be
+    /// read with [`Frontend::resolve_span`]. The cursors of the span must be in the same list or
+    /// text object, otherwise the span never resolves.
+    pub fn add_span(&mut self, span: Span) -> SpanId {
+        let id = SpanId(self.next_span_id);
+        self.next_span_id += 1;
+        self.state.track_span(id, &span);
+        id
+    }
+
+    /// Stop tracking a span, returning whether it was tracked.
+    pub fn remove_span(&mut self, id: SpanId) -> bool {
+        self.state.untrack_span(id)
+    }
+
+    /// The indices of the elements currently in a span, as shown by [`Frontend::state`]. If
+    /// every element in the span has been deleted the range is empty and is where the elements
+    /// were. Returns `None` if the span is not tracked or its list or text object is no longer in
+    /// the document.
+    pub fn resolve_span(&self, id: SpanId) -> Option<std::ops::Range<u32>> {
+        self.state.visible_root_state().resolve_span(id)
+    }
+
+    /// The index of the element `cursor` points at in [`Frontend::state`]. If the element has
+    /// been deleted this is the index the backend resolved the cursor to, which is the nearest
+    /// element on the side chosen by the backend's cursor bias, limited to the elements the
+    /// sequence has now. Returns `None` if the list or text object is no longer in the document
+    /// or is empty.
+    pub fn resolve_cursor(&self, cursor: &Cursor) -> Option<u32> {
+        self.state.visible_root_state().resolve_cursor(cursor)
+    }
+
+    /// Tell subscribers and table indexes that the values at `changed_paths` have changed
+    fn paths_changed(&mut self, changed_paths: &[Path]) {
+        let state = &self.state;
+        let resolver = self.conflict_resolver.as_deref();
+        self.subscriptions
+            .notify(changed_paths, |path| state.get_value(path, resolver));
+        self.table_indexes
+            .update(changed_paths, |path| state.get_value(path, resolver));
+    }
+
     pub fn get_object_id(&self, path: &Path) -> Option<ObjectId> {
         self.state.get_object_id(path)
     }
@@ -529,14 +858,22 @@ impl Frontend {
         self.state.resolve_path(path).map(|o| o.values())
     }
 
+    /// Every location in the document which currently holds more than one value. Each conflict
+    /// can be resolved with [`LocalChange::resolve_conflict`].
+    pub fn conflicts(&self) -> DocumentConflicts<'_> {
+        self.state.conflicts()
+    }
+
     /// Returns the value given by path, if it exists
     pub fn get_value(&self, path: &Path) -> Option<Value> {
-        self.state.get_value(path)
+        self.state
+            .get_value(path, self.conflict_resolver.as_deref())
     }
 }
 
 struct OptimisticChangeResult<O> {
     ops: Vec<Op>,
     deps: Vec<ChangeHash>,
+    changed_paths: Vec<Path>,
     closure_result: O,
 }
@@ -1,22 +1,125 @@
 use automerge_protocol as amp;
+use serde::Serialize;
+use smol_str::SmolStr;
 use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
-    error::InvalidChangeRequest,
+    error::{InvalidChangeRequest, SerdeError},
+    sequence_diff::{self, Step},
     state_tree::{
         LocalOperationResult, MultiGrapheme, MultiValue, ResolvedPath, ResolvedPathMut,
         SetOrInsertPayload, StateTree,
     },
     value::{Cursor, Primitive, Value},
-    Path, PathElement,
+    value_mut::MapMut,
+    value_serde, Path, PathElement,
 };
 
-pub trait MutableDocument {
+pub trait MutableDocument: AsMutableDocument {
     fn value_at_path(&self, path: &Path) -> Option<Value>;
     fn cursor_to_path(&self, path: &Path) -> Option<Cursor>;
     fn add_change(&mut self, change: LocalChange) -> Result<(), InvalidChangeRequest>;
+
+    /// Change the text object at `path` to `new`, inserting and deleting only the characters
+    /// which differ, so that concurrent edits to the rest of the text are preserved.
+    fn update_text(&mut self, path: &Path, new: &str) -> Result<(), InvalidChangeRequest> {
+        let old = match self.value_at_path(path) {
+            Some(Value::Text(graphemes)) => graphemes,
+            Some(_) => {
+                return Err(InvalidChangeRequest::UpdateTextForNonTextObject { path: path.clone() })
+            }
+            None => return Err(InvalidChangeRequest::NoSuchPathError { path: path.clone() }),
+        };
+        let new = new.graphemes(true).collect::<Vec<_>>();
+        let edits = sequence_diff::diff(old.len(), new.len(), |i, j| old[i] == new[j]);
+
+        sequence_diff::apply(edits, false, |step| match step {
+            Step::Keep { .. } | Step::Replace { .. } => Ok(()),
+            Step::Delete { index, .. } => {
+                self.add_change(LocalChange::delete(path.clone().index(index)))
+            }
+            Step::Insert { index, new: next } => self.add_change(LocalChange::insert(
+                path.clone().index(index),
+                Value::Primitive(Primitive::Str(new[next].into())),
+            )),
+        })
+    }
+
+    /// A handle to the root of the document, from which nested objects can be edited without
+    /// resolving their path from the root for every operation.
+    ///
+    /// The default implementation returns a handle which makes each operation through
+    /// [`MutableDocument::add_change`], so it still resolves the path for every operation.
+    fn root_mut(&mut self) -> MapMut<'_> {
+        MapMut::by_path(self.as_mutable_document())
+    }
+
+    /// Add `row`, which must be a map, to the table at `path` with a newly generated UUID as its
+    /// row ID, and return the row ID
+    fn table_insert(&mut self, path: &Path, row: Value) -> Result<SmolStr, InvalidChangeRequest> {
+        match self.value_at_path(path) {
+            Some(Value::Table(_)) => add_row(self.as_mutable_document(), path, row),
+            Some(_) => Err(InvalidChangeRequest::UnexpectedObjectType {
+                path: path.clone(),
+                expected: "a table",
+            }),
+            None => Err(InvalidChangeRequest::NoSuchPathError { path: path.clone() }),
+        }
+    }
+
+    /// Mark the current point in the change, so that the operations applied after it can be
+    /// undone with [`MutableDocument::rollback_to`] while keeping the ones applied before it.
+    ///
+    /// Undoing operations needs more than [`MutableDocument::add_change`], so the default
+    /// implementation fails with [`InvalidChangeRequest::SavepointsNotSupported`]. A document
+    /// which supports savepoints implements this and [`MutableDocument::rollback_to`] together.
+    fn savepoint(&mut self) -> Result<Savepoint, InvalidChangeRequest> {
+        Err(InvalidChangeRequest::SavepointsNotSupported)
+    }
+
+    /// Undo every operation applied since `savepoint` was created. The savepoint can be rolled
+    /// back to again, but savepoints created after it can no longer be used.
+    ///
+    /// The default implementation always fails with
+    /// [`InvalidChangeRequest::SavepointsNotSupported`].
+    fn rollback_to(&mut self, _savepoint: Savepoint) -> Result<(), InvalidChangeRequest> {
+        Err(InvalidChangeRequest::SavepointsNotSupported)
+    }
+}
+
+/// Add `row` to the table at `path`, which has already been checked to be a table
+fn add_row(
+    doc: &mut dyn MutableDocument,
+    path: &Path,
+    row: Value,
+) -> Result<SmolStr, InvalidChangeRequest> {
+    if !row.is_map() {
+        return Err(InvalidChangeRequest::InvalidTableRow {
+            path: path.clone(),
+            row,
+        });
+    }
+    let row_id = SmolStr::from(uuid::Uuid::new_v4().to_string());
+    doc.add_change(LocalChange::set(path.clone().key(row_id.clone()), row))?;
+    Ok(row_id)
+}
+
+/// Lets the default methods of [`MutableDocument`] treat `self` as a trait object. It is
+/// implemented for every sized `MutableDocument` and can't be named outside this crate.
+pub trait AsMutableDocument {
+    fn as_mutable_document(&mut self) -> &mut dyn MutableDocument;
+}
+
+impl<D: MutableDocument> AsMutableDocument for D {
+    fn as_mutable_document(&mut self) -> &mut dyn MutableDocument {
+        self
+    }
 }
 
+/// A point in a change created by [`MutableDocument::savepoint`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Savepoint(u64);
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum LocalOperation {
     Set(Value),
@@ -24,6 +127,8 @@ pub enum LocalOperation {
     Increment(i64),
     Insert(Value),
     InsertMany(Vec<Value>),
+    Move(Path),
+    ResolveConflict(amp::OpId),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -68,6 +173,14 @@ impl LocalChange {
         }
     }
 
+    /// Set the value at `path` to `value` converted with [`crate::value_serde::to_value`]
+    pub fn set_serialize<T>(path: Path, value: &T) -> Result<LocalChange, SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        Ok(LocalChange::set(path, value_serde::to_value(value)?))
+    }
+
     pub fn insert(path: Path, value: Value) -> LocalChange {
         LocalChange {
             path,
@@ -75,12 +188,44 @@ impl LocalChange {
         }
     }
 
+    /// Insert `value` converted with [`crate::value_serde::to_value`] at `path`
+    pub fn insert_serialize<T>(path: Path, value: &T) -> Result<LocalChange, SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        Ok(LocalChange::insert(path, value_serde::to_value(value)?))
+    }
+
     pub fn insert_many(path: Path, values: Vec<Value>) -> LocalChange {
         LocalChange {
             path,
             operation: LocalOperation::InsertMany(values),
         }
     }
+
+    /// Move the value at `from_path` to `to_path`.
+    ///
+    /// `to_path` refers to the document as it is once the value has been removed from
+    /// `from_path`, so moving the first element of a list to index 2 leaves it at index 2. A list
+    /// element is inserted at the index `to_path` ends with, a map key is overwritten. Unlike
+    /// deleting the value and inserting a copy, concurrent moves of the same value leave a single
+    /// copy of it in the document.
+    pub fn move_to(from_path: Path, to_path: Path) -> LocalChange {
+        LocalChange {
+            path: from_path,
+            operation: LocalOperation::Move(to_path),
+        }
+    }
+
+    /// Resolve the conflict at `path` by removing every value there except the one set by the
+    /// op `winner`, which is left where it is. Values set concurrently with this change are not
+    /// removed.
+    pub fn resolve_conflict(path: Path, winner: amp::OpId) -> LocalChange {
+        LocalChange {
+            path,
+            operation: LocalOperation::ResolveConflict(winner),
+        }
+    }
 }
 
 enum LocalOperationForRollback {
@@ -104,9 +249,29 @@ enum LocalOperationForRollback {
 /// is used to generate a `ChangeRequest` once the closure is completed.
 pub struct MutationTracker<'a> {
     state: &'a mut StateTree,
+    log: MutationLog,
+    /// The savepoints which can be rolled back to, oldest first
+    savepoints: Vec<SavepointMark>,
+    next_savepoint: u64,
+}
+
+/// The length of the log when a savepoint was created
+struct SavepointMark {
+    savepoint: Savepoint,
+    ops: usize,
+    copies_for_rollback: usize,
+    max_op: u64,
+}
+
+/// The operations generated by a `MutationTracker` and the copies needed to roll them back.
+///
+/// The methods of the log apply an operation to an object which has already been resolved, so
+/// that the `value_mut` handles can make several changes to an object while only resolving its
+/// path once.
+pub(crate) struct MutationLog {
     ops: Vec<amp::Op>,
     copies_for_rollback: Vec<(Path, LocalOperationForRollback)>,
-    pub max_op: u64,
+    max_op: u64,
     actor_id: amp::ActorId,
 }
 
@@ -114,15 +279,39 @@ impl<'a> MutationTracker<'a> {
     pub(crate) fn new(state_tree: &'a mut StateTree, max_op: u64, actor_id: amp::ActorId) -> Self {
         Self {
             state: state_tree,
-            ops: Vec::new(),
-            copies_for_rollback: Vec::new(),
-            max_op,
-            actor_id,
+            log: MutationLog {
+                ops: Vec::new(),
+                copies_for_rollback: Vec::new(),
+                max_op,
+                actor_id,
+            },
+            savepoints: Vec::new(),
+            next_savepoint: 0,
         }
     }
 
     pub fn ops(self) -> Vec<amp::Op> {
-        self.ops
+        self.log.ops
+    }
+
+    pub fn max_op(&self) -> u64 {
+        self.log.max_op
+    }
+
+    /// The paths changed by the operations applied so far. Inserting or deleting an element of a
+    /// sequence changes the path of every later element, so the sequence itself is changed.
+    pub(crate) fn changed_paths(&self) -> Vec<Path> {
+        self.log
+            .copies_for_rollback
+            .iter()
+            .map(|(path, op)| match (op, path.name()) {
+                (LocalOperationForRollback::Insert, _)
+                | (LocalOperationForRollback::InsertMany { .. }, _)
+                | (LocalOperationForRollback::Delete { .. }, Some(PathElement::Index(_)))
+                | (LocalOperationForRollback::DeleteText { .. }, _) => path.parent(),
+                _ => path.clone(),
+            })
+            .collect()
     }
 
     /// If the `value` is a map, individually assign each k,v in it to a key in
@@ -140,286 +329,257 @@ impl<'a> MutationTracker<'a> {
             }),
         }
     }
-
-    fn apply_state_change(&mut self, change: LocalOperationResult) {
-        self.max_op += change.new_ops.len() as u64;
-        self.ops.extend(change.new_ops);
-    }
-
-    fn insert_helper<I>(&mut self, path: &Path, values: I) -> Result<(), InvalidChangeRequest>
+    fn insert<I>(&mut self, path: Path, values: I) -> Result<(), InvalidChangeRequest>
     where
         I: ExactSizeIterator<Item = Value>,
     {
-        if let Some(name) = path.name() {
-            let index = match name {
-                PathElement::Index(i) => i,
-                _ => {
-                    return Err(InvalidChangeRequest::InsertWithNonSequencePath {
-                        path: path.clone(),
-                    })
-                }
-            };
-            if let Some(parent) = self.state.resolve_path_mut(&path.parent()) {
-                match parent {
-                    ResolvedPathMut::List(mut list_target) => {
-                        let payload = SetOrInsertPayload {
-                            start_op: self.max_op + 1,
-                            actor: &self.actor_id.clone(),
-                            value: values,
-                        };
-                        let res = list_target.insert_many(*index, payload)?;
-                        self.apply_state_change(res);
-                    }
-                    ResolvedPathMut::Text(mut text_target) => {
-                        let mut chars = Vec::with_capacity(values.len());
-                        for value in values {
-                            match value {
-                                Value::Primitive(Primitive::Str(s)) => {
-                                    if s.graphemes(true).count() == 1 {
-                                        chars.push(s.clone())
-                                    } else {
-                                        return Err(
-                                            InvalidChangeRequest::InsertNonTextInTextObject {
-                                                path: path.clone(),
-                                                object: Value::Primitive(Primitive::Str(s)),
-                                            },
-                                        );
-                                    }
-                                }
-                                _ => {
-                                    return Err(InvalidChangeRequest::InsertNonTextInTextObject {
-                                        path: path.clone(),
-                                        object: value.clone(),
-                                    })
-                                }
-                            }
-                        }
-                        let payload = SetOrInsertPayload {
-                            start_op: self.max_op + 1,
-                            actor: &self.actor_id.clone(),
-                            value: chars.into_iter(),
-                        };
-                        let res = text_target.insert_many(*index, payload)?;
-                        self.apply_state_change(res);
-                    }
-                    _ => return Err(InvalidChangeRequest::NoSuchPathError { path: path.clone() }),
-                };
-                Ok(())
-            } else {
-                Err(InvalidChangeRequest::InsertForNonSequenceObject { path: path.clone() })
+        if path.is_root() {
+            return Err(InvalidChangeRequest::NoSuchPathError { path });
+        }
+        match self.state.resolve_path_mut(&path.parent()) {
+            Some(mut parent) => self.log.insert(&mut parent, path, values),
+            None => Err(InvalidChangeRequest::InsertForNonSequenceObject { path }),
+        }
+    }
+
+    fn move_value(&mut self, from: Path, to: Path) -> Result<(), InvalidChangeRequest> {
+        if from.is_root() {
+            return Err(InvalidChangeRequest::CannotMoveRootObject);
+        }
+        // the index of a list element in `to` may refer to a later element of the same list once
+        // the value is removed, but a key can only refer to the removed value
+        if matches!(from.name(), Some(PathElement::Key(_))) && to != from && to.starts_with(&from) {
+            return Err(InvalidChangeRequest::MoveIntoItself { from, to });
+        }
+        let value = match self.state.resolve_path_mut(&from.parent()) {
+            Some(mut parent) => self.log.remove_for_move(&mut parent, from)?,
+            None => return Err(InvalidChangeRequest::NoSuchPathError { path: from }),
+        };
+        let result = match self.state.resolve_path_mut(&to.parent()) {
+            Some(mut parent) => self.log.place_moved(&mut parent, to, value),
+            None => Err(InvalidChangeRequest::NoSuchPathError { path: to }),
+        };
+        if result.is_err() {
+            // put the value back where it came from
+            if let Some((path, op)) = self.log.copies_for_rollback.pop() {
+                self.undo(path, op);
             }
-        } else {
-            Err(InvalidChangeRequest::NoSuchPathError { path: path.clone() })
         }
+        result
     }
 
     /// Undo the operations applied to this document.
     ///
     /// This is used in the case of an error to undo the already applied changes.
-    pub fn rollback(self) {
-        for (path, op) in self.copies_for_rollback.into_iter().rev() {
-            match op {
-                LocalOperationForRollback::Set { old } => {
-                    if let Some(key) = path.name() {
-                        if let Some(parent) = self.state.resolve_path_mut(&path.parent()) {
-                            match (key, parent) {
-                                (PathElement::Key(key), ResolvedPathMut::Root(mut map)) => {
-                                    map.rollback_set(key.clone(), old)
-                                }
-                                (PathElement::Key(key), ResolvedPathMut::Map(mut map)) => {
-                                    map.rollback_set(key.clone(), old)
-                                }
-                                (PathElement::Key(key), ResolvedPathMut::Table(mut table)) => {
-                                    table.rollback_set(key.clone(), old)
-                                }
-                                (PathElement::Key(_), ResolvedPathMut::List(_))
-                                | (PathElement::Key(_), ResolvedPathMut::Text(_))
-                                | (PathElement::Key(_), ResolvedPathMut::Character(_))
-                                | (PathElement::Key(_), ResolvedPathMut::Counter(_))
-                                | (PathElement::Key(_), ResolvedPathMut::Primitive(_)) => {
-                                    unreachable!("found non object with key")
-                                }
-                                (PathElement::Index(_), ResolvedPathMut::List(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Text(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Root(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Map(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Table(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Character(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Counter(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Primitive(_)) => {
-                                    unreachable!("found index element while rolling back a set")
-                                }
+    pub fn rollback(mut self) {
+        while let Some((path, op)) = self.log.copies_for_rollback.pop() {
+            self.undo(path, op);
+        }
+    }
+
+    /// Undo a single operation
+    fn undo(&mut self, path: Path, op: LocalOperationForRollback) {
+        match op {
+            LocalOperationForRollback::Set { old } => {
+                if let Some(key) = path.name() {
+                    if let Some(parent) = self.state.resolve_path_mut(&path.parent()) {
+                        match (key, parent) {
+                            (PathElement::Key(key), ResolvedPathMut::Root(mut map)) => {
+                                map.rollback_set(key.clone(), old)
+                            }
+                            (PathElement::Key(key), ResolvedPathMut::Map(mut map)) => {
+                                map.rollback_set(key.clone(), old)
+                            }
+                            (PathElement::Key(key), ResolvedPathMut::Table(mut table)) => {
+                                table.rollback_set(key.clone(), old)
+                            }
+                            (PathElement::Key(_), ResolvedPathMut::List(_))
+                            | (PathElement::Key(_), ResolvedPathMut::Text(_))
+                            | (PathElement::Key(_), ResolvedPathMut::Character(_))
+                            | (PathElement::Key(_), ResolvedPathMut::Counter(_))
+                            | (PathElement::Key(_), ResolvedPathMut::Primitive(_)) => {
+                                unreachable!("found non object with key")
+                            }
+                            (PathElement::Index(_), ResolvedPathMut::List(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Text(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Root(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Map(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Table(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Character(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Counter(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Primitive(_)) => {
+                                unreachable!("found index element while rolling back a set")
                             }
                         }
                     }
                 }
-                LocalOperationForRollback::SetList { old } => {
-                    if let Some(key) = path.name() {
-                        if let Some(parent) = self.state.resolve_path_mut(&path.parent()) {
-                            match (key, parent) {
-                                (PathElement::Key(_), _) => {
-                                    unreachable!("found key element while rolling back a setlist")
-                                }
-                                (PathElement::Index(i), ResolvedPathMut::List(mut list)) => {
-                                    list.rollback_set(*i as usize, old)
-                                }
-                                (PathElement::Index(_), ResolvedPathMut::Text(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Root(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Map(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Table(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Character(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Counter(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Primitive(_)) => {
-                                    unreachable!("found non list with index")
-                                }
+            }
+            LocalOperationForRollback::SetList { old } => {
+                if let Some(key) = path.name() {
+                    if let Some(parent) = self.state.resolve_path_mut(&path.parent()) {
+                        match (key, parent) {
+                            (PathElement::Key(_), _) => {
+                                unreachable!("found key element while rolling back a setlist")
+                            }
+                            (PathElement::Index(i), ResolvedPathMut::List(mut list)) => {
+                                list.rollback_set(*i as usize, old)
+                            }
+                            (PathElement::Index(_), ResolvedPathMut::Text(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Root(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Map(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Table(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Character(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Counter(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Primitive(_)) => {
+                                unreachable!("found non list with index")
                             }
                         }
                     }
                 }
-                LocalOperationForRollback::SetText { old } => {
-                    if let Some(key) = path.name() {
-                        if let Some(parent) = self.state.resolve_path_mut(&path.parent()) {
-                            match (key, parent) {
-                                (PathElement::Key(_), _) => {
-                                    unreachable!("found key element while rolling back a settext")
-                                }
-                                (PathElement::Index(i), ResolvedPathMut::Text(mut text)) => {
-                                    text.rollback_set(*i as usize, old)
-                                }
-                                (PathElement::Index(_), ResolvedPathMut::List(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Root(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Map(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Table(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Character(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Counter(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Primitive(_)) => {
-                                    unreachable!("found non text with index")
-                                }
+            }
+            LocalOperationForRollback::SetText { old } => {
+                if let Some(key) = path.name() {
+                    if let Some(parent) = self.state.resolve_path_mut(&path.parent()) {
+                        match (key, parent) {
+                            (PathElement::Key(_), _) => {
+                                unreachable!("found key element while rolling back a settext")
+                            }
+                            (PathElement::Index(i), ResolvedPathMut::Text(mut text)) => {
+                                text.rollback_set(*i as usize, old)
+                            }
+                            (PathElement::Index(_), ResolvedPathMut::List(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Root(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Map(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Table(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Character(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Counter(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Primitive(_)) => {
+                                unreachable!("found non text with index")
                             }
                         }
                     }
                 }
-                LocalOperationForRollback::Delete { old } => {
-                    if let Some(key) = path.name() {
-                        if let Some(parent) = self.state.resolve_path_mut(&path.parent()) {
-                            match (key, parent) {
-                                (PathElement::Key(key), ResolvedPathMut::Root(mut map)) => {
-                                    map.rollback_delete(key.clone(), old)
-                                }
-                                (PathElement::Key(key), ResolvedPathMut::Map(mut map)) => {
-                                    map.rollback_delete(key.clone(), old)
-                                }
-                                (PathElement::Key(key), ResolvedPathMut::Table(mut table)) => {
-                                    table.rollback_delete(key.clone(), old)
-                                }
-                                (PathElement::Key(_), ResolvedPathMut::List(_))
-                                | (PathElement::Key(_), ResolvedPathMut::Text(_))
-                                | (PathElement::Key(_), ResolvedPathMut::Character(_))
-                                | (PathElement::Key(_), ResolvedPathMut::Counter(_))
-                                | (PathElement::Key(_), ResolvedPathMut::Primitive(_)) => {
-                                    unreachable!("found non object with key")
-                                }
-                                (PathElement::Index(i), ResolvedPathMut::List(mut list)) => {
-                                    list.rollback_delete(*i as usize, old)
-                                }
-                                (PathElement::Index(_), ResolvedPathMut::Text(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Root(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Map(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Table(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Character(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Counter(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Primitive(_)) => {
-                                    unreachable!("found non list with index")
-                                }
+            }
+            LocalOperationForRollback::Delete { old } => {
+                if let Some(key) = path.name() {
+                    if let Some(parent) = self.state.resolve_path_mut(&path.parent()) {
+                        match (key, parent) {
+                            (PathElement::Key(key), ResolvedPathMut::Root(mut map)) => {
+                                map.rollback_delete(key.clone(), old)
+                            }
+                            (PathElement::Key(key), ResolvedPathMut::Map(mut map)) => {
+                                map.rollback_delete(key.clone(), old)
+                            }
+                            (PathElement::Key(key), ResolvedPathMut::Table(mut table)) => {
+                                table.rollback_delete(key.clone(), old)
+                            }
+                            (PathElement::Key(_), ResolvedPathMut::List(_))
+                            | (PathElement::Key(_), ResolvedPathMut::Text(_))
+                            | (PathElement::Key(_), ResolvedPathMut::Character(_))
+                            | (PathElement::Key(_), ResolvedPathMut::Counter(_))
+                            | (PathElement::Key(_), ResolvedPathMut::Primitive(_)) => {
+                                unreachable!("found non object with key")
+                            }
+                            (PathElement::Index(i), ResolvedPathMut::List(mut list)) => {
+                                list.rollback_delete(*i as usize, old)
+                            }
+                            (PathElement::Index(_), ResolvedPathMut::Text(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Root(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Map(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Table(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Character(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Counter(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Primitive(_)) => {
+                                unreachable!("found non list with index")
                             }
                         }
                     }
                 }
-                LocalOperationForRollback::DeleteText { old } => {
-                    if let Some(key) = path.name() {
-                        if let Some(parent) = self.state.resolve_path_mut(&path.parent()) {
-                            match (key, parent) {
-                                (PathElement::Key(_), ResolvedPathMut::Root(_))
-                                | (PathElement::Key(_), ResolvedPathMut::Map(_))
-                                | (PathElement::Key(_), ResolvedPathMut::Table(_))
-                                | (PathElement::Key(_), ResolvedPathMut::List(_))
-                                | (PathElement::Key(_), ResolvedPathMut::Text(_))
-                                | (PathElement::Key(_), ResolvedPathMut::Character(_))
-                                | (PathElement::Key(_), ResolvedPathMut::Counter(_))
-                                | (PathElement::Key(_), ResolvedPathMut::Primitive(_)) => {
-                                    unreachable!("found key for SetText")
-                                }
-                                (PathElement::Index(i), ResolvedPathMut::Text(mut text)) => {
-                                    text.rollback_delete(*i as usize, old)
-                                }
-                                (PathElement::Index(_), ResolvedPathMut::List(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Root(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Map(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Table(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Character(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Counter(_))
-                                | (PathElement::Index(_), ResolvedPathMut::Primitive(_)) => {
-                                    unreachable!("found non text with index")
-                                }
+            }
+            LocalOperationForRollback::DeleteText { old } => {
+                if let Some(key) = path.name() {
+                    if let Some(parent) = self.state.resolve_path_mut(&path.parent()) {
+                        match (key, parent) {
+                            (PathElement::Key(_), ResolvedPathMut::Root(_))
+                            | (PathElement::Key(_), ResolvedPathMut::Map(_))
+                            | (PathElement::Key(_), ResolvedPathMut::Table(_))
+                            | (PathElement::Key(_), ResolvedPathMut::List(_))
+                            | (PathElement::Key(_), ResolvedPathMut::Text(_))
+                            | (PathElement::Key(_), ResolvedPathMut::Character(_))
+                            | (PathElement::Key(_), ResolvedPathMut::Counter(_))
+                            | (PathElement::Key(_), ResolvedPathMut::Primitive(_)) => {
+                                unreachable!("found key for SetText")
+                            }
+                            (PathElement::Index(i), ResolvedPathMut::Text(mut text)) => {
+                                text.rollback_delete(*i as usize, old)
+                            }
+                            (PathElement::Index(_), ResolvedPathMut::List(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Root(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Map(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Table(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Character(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Counter(_))
+                            | (PathElement::Index(_), ResolvedPathMut::Primitive(_)) => {
+                                unreachable!("found non text with index")
                             }
                         }
                     }
                 }
-                LocalOperationForRollback::Insert => {
-                    if let Some(PathElement::Index(index)) = path.name() {
-                        if let Some(parent) = self.state.resolve_path_mut(&path.parent()) {
-                            match parent {
-                                ResolvedPathMut::List(mut list) => {
-                                    list.rollback_insert(*index as usize)
-                                }
-                                ResolvedPathMut::Text(mut text) => {
-                                    text.rollback_insert(*index as usize)
-                                }
-                                ResolvedPathMut::Root(_)
-                                | ResolvedPathMut::Map(_)
-                                | ResolvedPathMut::Table(_)
-                                | ResolvedPathMut::Character(_)
-                                | ResolvedPathMut::Counter(_)
-                                | ResolvedPathMut::Primitive(_) => {
-                                    unreachable!("Found non list object in rollback insert")
-                                }
+            }
+            LocalOperationForRollback::Insert => {
+                if let Some(PathElement::Index(index)) = path.name() {
+                    if let Some(parent) = self.state.resolve_path_mut(&path.parent()) {
+                        match parent {
+                            ResolvedPathMut::List(mut list) => {
+                                list.rollback_insert(*index as usize)
+                            }
+                            ResolvedPathMut::Text(mut text) => {
+                                text.rollback_insert(*index as usize)
+                            }
+                            ResolvedPathMut::Root(_)
+                            | ResolvedPathMut::Map(_)
+                            | ResolvedPathMut::Table(_)
+                            | ResolvedPathMut::Character(_)
+                            | ResolvedPathMut::Counter(_)
+                            | ResolvedPathMut::Primitive(_) => {
+                                unreachable!("Found non list object in rollback insert")
                             }
                         }
                     }
                 }
-                LocalOperationForRollback::InsertMany { count } => {
-                    if let Some(PathElement::Index(index)) = path.name() {
-                        if let Some(parent) = self.state.resolve_path_mut(&path.parent()) {
-                            match parent {
-                                ResolvedPathMut::List(mut list) => {
-                                    for _ in 0..count {
-                                        list.rollback_insert(*index as usize)
-                                    }
-                                }
-                                ResolvedPathMut::Text(mut text) => {
-                                    for _ in 0..count {
-                                        text.rollback_insert(*index as usize)
-                                    }
+            }
+            LocalOperationForRollback::InsertMany { count } => {
+                if let Some(PathElement::Index(index)) = path.name() {
+                    if let Some(parent) = self.state.resolve_path_mut(&path.parent()) {
+                        match parent {
+                            ResolvedPathMut::List(mut list) => {
+                                for _ in 0..count {
+                                    list.rollback_insert(*index as usize)
                                 }
-                                ResolvedPathMut::Root(_)
-                                | ResolvedPathMut::Map(_)
-                                | ResolvedPathMut::Table(_)
-                                | ResolvedPathMut::Character(_)
-                                | ResolvedPathMut::Counter(_)
-                                | ResolvedPathMut::Primitive(_) => {
-                                    unreachable!("Found non list object in rollback insert")
+                            }
+                            ResolvedPathMut::Text(mut text) => {
+                                for _ in 0..count {
+                                    text.rollback_insert(*index as usize)
                                 }
                             }
+                            ResolvedPathMut::Root(_)
+                            | ResolvedPathMut::Map(_)
+                            | ResolvedPathMut::Table(_)
+                            | ResolvedPathMut::Character(_)
+                            | ResolvedPathMut::Counter(_)
+                            | ResolvedPathMut::Primitive(_) => {
+                                unreachable!("Found non list object in rollback insert")
+                            }
                         }
                     }
                 }
-                LocalOperationForRollback::Increment { by } => {
-                    if path.name().is_some() {
-                        if let Some(ResolvedPathMut::Counter(mut counter)) =
-                            self.state.resolve_path_mut(&path)
-                        {
-                            counter.rollback_increment(by)
-                        }
+            }
+            LocalOperationForRollback::Increment { by } => {
+                if path.name().is_some() {
+                    if let Some(ResolvedPathMut::Counter(mut counter)) =
+                        self.state.resolve_path_mut(&path)
+                    {
+                        counter.rollback_increment(by)
                     }
                 }
             }
@@ -449,249 +609,390 @@ impl<'a> MutableDocument for MutationTracker<'a> {
     }
 
     fn add_change(&mut self, change: LocalChange) -> Result<(), InvalidChangeRequest> {
+        let path = change.path;
         match change.operation {
-            LocalOperation::Set(value) => {
-                //TODO double resolving is ugly here
-                if let Some(ResolvedPath::Counter(_)) = self.state.resolve_path(&change.path) {
-                    return Err(InvalidChangeRequest::CannotOverwriteCounter { path: change.path });
-                };
-                if let Some(name) = change.path.name() {
-                    if let Some(parent) = self.state.resolve_path_mut(&change.path.parent()) {
-                        let (rollback_op, res) = match (name, parent) {
-                            (
-                                PathElement::Key(ref k),
-                                ResolvedPathMut::Root(ref mut root_target),
-                            ) => {
-                                let payload = SetOrInsertPayload {
-                                    start_op: self.max_op + 1,
-                                    actor: &self.actor_id.clone(),
-                                    value,
-                                };
-                                let (old, res) = root_target.set_key(k.clone(), payload);
-                                Ok((LocalOperationForRollback::Set { old }, res))
-                            }
-                            (PathElement::Key(ref k), ResolvedPathMut::Map(ref mut maptarget)) => {
-                                let payload = SetOrInsertPayload {
-                                    start_op: self.max_op + 1,
-                                    actor: &self.actor_id.clone(),
-                                    value,
-                                };
-                                let (old, res) = maptarget.set_key(k.clone(), payload);
-                                Ok((LocalOperationForRollback::Set { old }, res))
-                            }
-                            (
-                                PathElement::Key(ref k),
-                                ResolvedPathMut::Table(ref mut tabletarget),
-                            ) => {
-                                let payload = SetOrInsertPayload {
-                                    start_op: self.max_op + 1,
-                                    actor: &self.actor_id.clone(),
-                                    value,
-                                };
-                                let (old, res) = tabletarget.set_key(k.clone(), payload);
-                                Ok((LocalOperationForRollback::Set { old }, res))
-                            }
-                            // In this case we are trying to modify a key in something which is not
-                            // an object or a table, so the path does not exist
-                            (PathElement::Key(_), _) => {
-                                Err(InvalidChangeRequest::NoSuchPathError {
-                                    path: change.path.clone(),
-                                })
-                            }
-                            (PathElement::Index(i), ResolvedPathMut::List(ref mut list_target)) => {
-                                let payload = SetOrInsertPayload {
-                                    start_op: self.max_op + 1,
-                                    actor: &self.actor_id.clone(),
-                                    value,
-                                };
-                                let (old, res) = list_target.set(*i, payload)?;
-                                Ok((LocalOperationForRollback::SetList { old }, res))
-                            }
-                            (PathElement::Index(i), ResolvedPathMut::Text(ref mut text)) => {
-                                match value {
-                                    Value::Primitive(Primitive::Str(s)) => {
-                                        if s.graphemes(true).count() == 1 {
-                                            let payload = SetOrInsertPayload {
-                                                start_op: self.max_op + 1,
-                                                actor: &self.actor_id.clone(),
-                                                value: s,
-                                            };
-                                            let (old, res) = text.set(*i, payload)?;
-                                            Ok((LocalOperationForRollback::SetText { old }, res))
-                                        } else {
-                                            Err(InvalidChangeRequest::InsertNonTextInTextObject {
-                                                path: change.path.clone(),
-                                                object: Value::Primitive(Primitive::Str(s)),
-                                            })
-                                        }
-                                    }
-                                    _ => Err(InvalidChangeRequest::InsertNonTextInTextObject {
-                                        path: change.path.clone(),
-                                        object: value.clone(),
-                                    }),
-                                }
-                            }
-                            (PathElement::Index(_), _) => {
-                                Err(InvalidChangeRequest::InsertWithNonSequencePath {
-                                    path: change.path.clone(),
-                                })
-                            }
-                        }?;
-
-                        self.copies_for_rollback.push((change.path, rollback_op));
-                        self.apply_state_change(res);
-                        Ok(())
-                    } else {
-                        Err(InvalidChangeRequest::NoSuchPathError { path: change.path })
+            LocalOperation::Set(value) if path.is_root() => self.wrap_root_assignment(value),
+            LocalOperation::Set(value) => match self.state.resolve_path_mut(&path.parent()) {
+                Some(mut parent) => self.log.set(&mut parent, path, value),
+                None => Err(InvalidChangeRequest::NoSuchPathError { path }),
+            },
+            LocalOperation::Delete if path.is_root() => {
+                Err(InvalidChangeRequest::CannotDeleteRootObject)
+            }
+            LocalOperation::Delete => match self.state.resolve_path_mut(&path.parent()) {
+                Some(mut parent) => self.log.delete(&mut parent, path),
+                None => Err(InvalidChangeRequest::NoSuchPathError { path }),
+            },
+            LocalOperation::Increment(_) if path.is_root() => {
+                Err(InvalidChangeRequest::IncrementForNonCounterObject { path })
+            }
+            LocalOperation::Increment(by) => match self.state.resolve_path_mut(&path) {
+                Some(target) => self.log.increment(target, path, by),
+                None => Err(InvalidChangeRequest::NoSuchPathError { path }),
+            },
+            LocalOperation::Insert(value) => self.insert(path, std::iter::once(value)),
+            LocalOperation::InsertMany(values) => self.insert(path, values.into_iter()),
+            LocalOperation::Move(to) => self.move_value(path, to),
+            LocalOperation::ResolveConflict(winner) => {
+                match self.state.resolve_path_mut(&path.parent()) {
+                    Some(mut parent) if !path.is_root() => {
+                        self.log.resolve_conflict(&mut parent, path, winner)
                     }
-                } else {
-                    self.wrap_root_assignment(value)
+                    _ => Err(InvalidChangeRequest::NoSuchPathError { path }),
                 }
             }
-            LocalOperation::Delete => {
-                if let Some(name) = change.path.name() {
-                    if let Some(pr) = self.state.resolve_path_mut(&change.path.parent()) {
-                        let (rollback_op, state_change) = match pr {
-                            ResolvedPathMut::Counter(_) => {
-                                return Err(InvalidChangeRequest::NoSuchPathError {
-                                    path: change.path,
-                                })
-                            }
-                            ResolvedPathMut::List(mut l) => match name {
-                                PathElement::Index(i) => {
-                                    let (old, res) = l.remove(*i)?;
-                                    (LocalOperationForRollback::Delete { old }, res)
-                                }
-                                _ => {
-                                    return Err(InvalidChangeRequest::NoSuchPathError {
-                                        path: change.path,
-                                    })
-                                }
-                            },
-                            ResolvedPathMut::Text(mut t) => match name {
-                                PathElement::Index(i) => {
-                                    let (old, res) = t.remove(*i)?;
-                                    (LocalOperationForRollback::DeleteText { old }, res)
-                                }
-                                _ => {
-                                    return Err(InvalidChangeRequest::NoSuchPathError {
-                                        path: change.path,
-                                    })
-                                }
-                            },
-                            ResolvedPathMut::Primitive(_) => {
-                                return Err(InvalidChangeRequest::NoSuchPathError {
-                                    path: change.path,
-                                })
-                            }
-                            ResolvedPathMut::Map(mut m) => match name {
-                                PathElement::Key(k) => {
-                                    let (old, res) = m.delete_key(k).ok_or_else(|| {
-                                        InvalidChangeRequest::NoSuchPathError {
-                                            path: change.path.clone(),
-                                        }
-                                    })?;
-                                    (LocalOperationForRollback::Delete { old }, res)
-                                }
-                                _ => {
-                                    return Err(InvalidChangeRequest::NoSuchPathError {
-                                        path: change.path,
-                                    })
-                                }
-                            },
-                            ResolvedPathMut::Table(mut t) => match name {
-                                PathElement::Key(k) => {
-                                    let (old, res) = t.delete_key(k).ok_or_else(|| {
-                                        InvalidChangeRequest::NoSuchPathError {
-                                            path: change.path.clone(),
-                                        }
-                                    })?;
-                                    (LocalOperationForRollback::Delete { old }, res)
-                                }
-                                _ => {
-                                    return Err(InvalidChangeRequest::NoSuchPathError {
-                                        path: change.path,
-                                    })
-                                }
-                            },
-                            ResolvedPathMut::Character(_) => {
-                                return Err(InvalidChangeRequest::NoSuchPathError {
-                                    path: change.path,
-                                })
-                            }
-                            ResolvedPathMut::Root(mut r) => match name {
-                                PathElement::Key(k) => {
-                                    let (old, res) = r.delete_key(k).ok_or_else(|| {
-                                        InvalidChangeRequest::NoSuchPathError {
-                                            path: change.path.clone(),
-                                        }
-                                    })?;
-                                    (LocalOperationForRollback::Delete { old }, res)
-                                }
-                                _ => {
-                                    return Err(InvalidChangeRequest::NoSuchPathError {
-                                        path: change.path,
-                                    })
-                                }
-                            },
-                        };
-                        self.copies_for_rollback.push((change.path, rollback_op));
-                        self.apply_state_change(state_change);
-                        Ok(())
+        }
+    }
+
+    fn table_insert(&mut self, path: &Path, row: Value) -> Result<SmolStr, InvalidChangeRequest> {
+        // unlike the default implementation this doesn't build the value of the whole table
+        match self.state.resolve_path(path) {
+            Some(ResolvedPath::Table(_)) => add_row(self, path, row),
+            Some(_) => Err(InvalidChangeRequest::UnexpectedObjectType {
+                path: path.clone(),
+                expected: "a table",
+            }),
+            None => Err(InvalidChangeRequest::NoSuchPathError { path: path.clone() }),
+        }
+    }
+
+    fn root_mut(&mut self) -> MapMut<'_> {
+        let root = self
+            .state
+            .resolve_path_mut(&Path::root())
+            .expect("the root path always resolves");
+        MapMut::new(root, Path::root(), &mut self.log)
+    }
+
+    fn savepoint(&mut self) -> Result<Savepoint, InvalidChangeRequest> {
+        let savepoint = Savepoint(self.next_savepoint);
+        self.next_savepoint += 1;
+        self.savepoints.push(SavepointMark {
+            savepoint,
+            ops: self.log.ops.len(),
+            copies_for_rollback: self.log.copies_for_rollback.len(),
+            max_op: self.log.max_op,
+        });
+        Ok(savepoint)
+    }
+
+    fn rollback_to(&mut self, savepoint: Savepoint) -> Result<(), InvalidChangeRequest> {
+        let index = self
+            .savepoints
+            .iter()
+            .position(|mark| mark.savepoint == savepoint)
+            .ok_or(InvalidChangeRequest::NoSuchSavepoint)?;
+        self.savepoints.truncate(index + 1);
+        let mark = &self.savepoints[index];
+        let (ops, copies_for_rollback, max_op) = (mark.ops, mark.copies_for_rollback, mark.max_op);
+        let undone = self.log.copies_for_rollback.split_off(copies_for_rollback);
+        for (path, op) in undone.into_iter().rev() {
+            self.undo(path, op);
+        }
+        self.log.ops.truncate(ops);
+        self.log.max_op = max_op;
+        Ok(())
+    }
+}
+
+impl MutationLog {
+    fn payload<T>(&self, value: T) -> SetOrInsertPayload<'_, T> {
+        SetOrInsertPayload {
+            start_op: self.max_op + 1,
+            actor: &self.actor_id,
+            value,
+        }
+    }
+
+    fn record(
+        &mut self,
+        path: Path,
+        rollback_op: LocalOperationForRollback,
+        change: LocalOperationResult,
+    ) {
+        self.copies_for_rollback.push((path, rollback_op));
+        self.max_op += change.new_ops.len() as u64;
+        self.ops.extend(change.new_ops);
+    }
+
+    /// Set the value at `path`, which is a child of `parent`
+    pub(crate) fn set(
+        &mut self,
+        parent: &mut ResolvedPathMut,
+        path: Path,
+        value: Value,
+    ) -> Result<(), InvalidChangeRequest> {
+        let name = match path.name() {
+            Some(name) => name.clone(),
+            None => return Err(InvalidChangeRequest::NoSuchPathError { path }),
+        };
+        if let Some(ResolvedPathMut::Counter(_)) = parent.reborrow().into_child(&name) {
+            return Err(InvalidChangeRequest::CannotOverwriteCounter { path });
+        };
+        let (rollback_op, res) = match (name, parent) {
+            (PathElement::Key(k), ResolvedPathMut::Root(ref mut root_target)) => {
+                let (old, res) = root_target.set_key(k, self.payload(value));
+                (LocalOperationForRollback::Set { old }, res)
+            }
+            (PathElement::Key(k), ResolvedPathMut::Map(ref mut maptarget)) => {
+                let (old, res) = maptarget.set_key(k, self.payload(value));
+                (LocalOperationForRollback::Set { old }, res)
+            }
+            (PathElement::Key(k), ResolvedPathMut::Table(ref mut tabletarget)) => {
+                let (old, res) = tabletarget.set_key(k, self.payload(value));
+                (LocalOperationForRollback::Set { old }, res)
+            }
+            // In this case we are trying to modify a key in something which is not
+            // an object or a table, so the path does not exist
+            (PathElement::Key(_), _) => {
+                return Err(InvalidChangeRequest::NoSuchPathError { path });
+            }
+            (PathElement::Index(i), ResolvedPathMut::List(ref mut list_target)) => {
+                let (old, res) = list_target.set(i, self.payload(value))?;
+                (LocalOperationForRollback::SetList { old }, res)
+            }
+            (PathElement::Index(i), ResolvedPathMut::Text(ref mut text)) => match value {
+                Value::Primitive(Primitive::Str(s)) => {
+                    if s.graphemes(true).count() == 1 {
+                        let (old, res) = text.set(i, self.payload(s))?;
+                        (LocalOperationForRollback::SetText { old }, res)
                     } else {
-                        Err(InvalidChangeRequest::NoSuchPathError { path: change.path })
+                        return Err(InvalidChangeRequest::InsertNonTextInTextObject {
+                            path,
+                            object: Value::Primitive(Primitive::Str(s)),
+                        });
                     }
-                } else {
-                    Err(InvalidChangeRequest::CannotDeleteRootObject)
                 }
+                _ => {
+                    return Err(InvalidChangeRequest::InsertNonTextInTextObject {
+                        path,
+                        object: value,
+                    })
+                }
+            },
+            (PathElement::Index(_), _) => {
+                return Err(InvalidChangeRequest::InsertWithNonSequencePath { path });
+            }
+        };
+        self.record(path, rollback_op, res);
+        Ok(())
+    }
+
+    /// Delete the value at `path`, which is a child of `parent`
+    pub(crate) fn delete(
+        &mut self,
+        parent: &mut ResolvedPathMut,
+        path: Path,
+    ) -> Result<(), InvalidChangeRequest> {
+        let (rollback_op, state_change) = match (path.name(), parent) {
+            (Some(PathElement::Index(i)), ResolvedPathMut::List(l)) => {
+                let (old, res) = l.remove(*i)?;
+                (LocalOperationForRollback::Delete { old }, res)
+            }
+            (Some(PathElement::Index(i)), ResolvedPathMut::Text(t)) => {
+                let (old, res) = t.remove(*i)?;
+                (LocalOperationForRollback::DeleteText { old }, res)
+            }
+            (Some(PathElement::Key(k)), ResolvedPathMut::Map(m)) => match m.delete_key(k) {
+                Some((old, res)) => (LocalOperationForRollback::Delete { old }, res),
+                None => return Err(InvalidChangeRequest::NoSuchPathError { path }),
+            },
+            (Some(PathElement::Key(k)), ResolvedPathMut::Table(t)) => match t.delete_key(k) {
+                Some((old, res)) => (LocalOperationForRollback::Delete { old }, res),
+                None => return Err(InvalidChangeRequest::NoSuchPathError { path }),
+            },
+            (Some(PathElement::Key(k)), ResolvedPathMut::Root(r)) => match r.delete_key(k) {
+                Some((old, res)) => (LocalOperationForRollback::Delete { old }, res),
+                None => return Err(InvalidChangeRequest::NoSuchPathError { path }),
+            },
+            (None, _) => return Err(InvalidChangeRequest::CannotDeleteRootObject),
+            _ => return Err(InvalidChangeRequest::NoSuchPathError { path }),
+        };
+        self.record(path, rollback_op, state_change);
+        Ok(())
+    }
+
+    /// Insert `values` into `parent`, which must be a list or a text object, starting at the
+    /// index `path` ends with
+    pub(crate) fn insert<I>(
+        &mut self,
+        parent: &mut ResolvedPathMut,
+        path: Path,
+        values: I,
+    ) -> Result<(), InvalidChangeRequest>
+    where
+        I: ExactSizeIterator<Item = Value>,
+    {
+        let index = match path.name() {
+            Some(PathElement::Index(i)) => *i,
+            Some(PathElement::Key(_)) => {
+                return Err(InvalidChangeRequest::InsertWithNonSequencePath { path })
+            }
+            None => return Err(InvalidChangeRequest::NoSuchPathError { path }),
+        };
+        let count = values.len();
+        let res = match parent {
+            ResolvedPathMut::List(list_target) => {
+                list_target.insert_many(index, self.payload(values))?
             }
-            LocalOperation::Increment(by) => {
-                if change.path.name().is_some() {
-                    if let Some(pr) = self.state.resolve_path_mut(&change.path) {
-                        match pr {
-                            ResolvedPathMut::Counter(mut counter_target) => {
-                                let res = counter_target.increment(by);
-                                self.copies_for_rollback.push((
-                                    change.path,
-                                    LocalOperationForRollback::Increment { by },
-                                ));
-                                self.apply_state_change(res);
-                                Ok(())
+            ResolvedPathMut::Text(text_target) => {
+                let mut chars = Vec::with_capacity(values.len());
+                for value in values {
+                    match value {
+                        Value::Primitive(Primitive::Str(s)) => {
+                            if s.graphemes(true).count() == 1 {
+                                chars.push(s.clone())
+                            } else {
+                                return Err(InvalidChangeRequest::InsertNonTextInTextObject {
+                                    path,
+                                    object: Value::Primitive(Primitive::Str(s)),
+                                });
                             }
-                            _ => Err(InvalidChangeRequest::IncrementForNonCounterObject {
-                                path: change.path.clone(),
-                            }),
                         }
-                    } else {
-                        Err(InvalidChangeRequest::NoSuchPathError { path: change.path })
+                        _ => {
+                            return Err(InvalidChangeRequest::InsertNonTextInTextObject {
+                                path,
+                                object: value.clone(),
+                            })
+                        }
                     }
-                } else {
-                    Err(InvalidChangeRequest::IncrementForNonCounterObject {
-                        path: change.path.clone(),
-                    })
                 }
+                text_target.insert_many(index, self.payload(chars.into_iter()))?
             }
-            LocalOperation::Insert(value) => {
-                match self.insert_helper(&change.path, std::iter::once(value)) {
-                    Ok(()) => {
-                        self.copies_for_rollback
-                            .push((change.path, LocalOperationForRollback::Insert));
-                        Ok(())
-                    }
-                    Err(e) => Err(e),
-                }
+            _ => return Err(InvalidChangeRequest::NoSuchPathError { path }),
+        };
+        let rollback_op = match count {
+            1 => LocalOperationForRollback::Insert,
+            count => LocalOperationForRollback::InsertMany { count },
+        };
+        self.record(path, rollback_op, res);
+        Ok(())
+    }
+
+    /// Remove every value at `path`, which is a child of `parent`, except the one set by `winner`
+    pub(crate) fn resolve_conflict(
+        &mut self,
+        parent: &mut ResolvedPathMut,
+        path: Path,
+        winner: amp::OpId,
+    ) -> Result<(), InvalidChangeRequest> {
+        let exists = match path.name() {
+            Some(name) => parent.reborrow().into_child(name).is_some(),
+            None => false,
+        };
+        if !exists {
+            return Err(InvalidChangeRequest::NoSuchPathError { path });
+        }
+        let resolved = match (path.name(), parent) {
+            (Some(PathElement::Key(k)), ResolvedPathMut::Root(r)) => r
+                .resolve_conflict(k, &winner)
+                .map(|(old, res)| (LocalOperationForRollback::Set { old: Some(old) }, res)),
+            (Some(PathElement::Key(k)), ResolvedPathMut::Map(m)) => m
+                .resolve_conflict(k, &winner)
+                .map(|(old, res)| (LocalOperationForRollback::Set { old: Some(old) }, res)),
+            (Some(PathElement::Key(k)), ResolvedPathMut::Table(t)) => t
+                .resolve_conflict(k, &winner)
+                .map(|(old, res)| (LocalOperationForRollback::Set { old: Some(old) }, res)),
+            (Some(PathElement::Index(i)), ResolvedPathMut::List(l)) => l
+                .resolve_conflict(*i, &winner)
+                .map(|(old, res)| (LocalOperationForRollback::SetList { old }, res)),
+            (Some(PathElement::Index(i)), ResolvedPathMut::Text(t)) => t
+                .resolve_conflict(*i, &winner)
+                .map(|(old, res)| (LocalOperationForRollback::SetText { old }, res)),
+            _ => return Err(InvalidChangeRequest::NoSuchPathError { path }),
+        };
+        match resolved {
+            Some((rollback_op, res)) => {
+                self.record(path, rollback_op, res);
+                Ok(())
             }
-            LocalOperation::InsertMany(values) => {
-                let count = values.len();
-                match self.insert_helper(&change.path, values.into_iter()) {
-                    Ok(()) => {
-                        self.copies_for_rollback
-                            .push((change.path, LocalOperationForRollback::InsertMany { count }));
-                        Ok(())
-                    }
-                    Err(e) => Err(e),
-                }
+            None => Err(InvalidChangeRequest::NoSuchConflictingValue { path, opid: winner }),
+        }
+    }
+
+    /// Remove the value at `path`, which is a child of `parent`, so that it can be placed
+    /// elsewhere by `place_moved`
+    fn remove_for_move(
+        &mut self,
+        parent: &mut ResolvedPathMut,
+        path: Path,
+    ) -> Result<MultiValue, InvalidChangeRequest> {
+        // the ops for deleting the value are discarded, the move op removes it
+        let old = match (path.name(), parent) {
+            (Some(PathElement::Index(i)), ResolvedPathMut::List(l)) => Some(l.remove(*i)?.0),
+            (Some(PathElement::Key(k)), ResolvedPathMut::Map(m)) => m.delete_key(k).map(|r| r.0),
+            (Some(PathElement::Key(k)), ResolvedPathMut::Table(t)) => t.delete_key(k).map(|r| r.0),
+            (Some(PathElement::Key(k)), ResolvedPathMut::Root(r)) => r.delete_key(k).map(|r| r.0),
+            (Some(_), ResolvedPathMut::Text(_)) => {
+                return Err(InvalidChangeRequest::MoveInTextObject { path })
+            }
+            (None, _) => return Err(InvalidChangeRequest::CannotMoveRootObject),
+            _ => None,
+        }
+        .ok_or_else(|| InvalidChangeRequest::NoSuchPathError { path: path.clone() })?;
+        self.copies_for_rollback
+            .push((path, LocalOperationForRollback::Delete { old: old.clone() }));
+        Ok(old)
+    }
+
+    /// Place `value`, which was removed by `remove_for_move`, at `path`, which is a child of
+    /// `parent`
+    fn place_moved(
+        &mut self,
+        parent: &mut ResolvedPathMut,
+        path: Path,
+        value: MultiValue,
+    ) -> Result<(), InvalidChangeRequest> {
+        let name = match path.name() {
+            Some(name) => name.clone(),
+            None => return Err(InvalidChangeRequest::CannotMoveRootObject),
+        };
+        if let Some(ResolvedPathMut::Counter(_)) = parent.reborrow().into_child(&name) {
+            return Err(InvalidChangeRequest::CannotOverwriteCounter { path });
+        };
+        let (rollback_op, res) = match (name, parent) {
+            (PathElement::Key(k), ResolvedPathMut::Root(root_target)) => {
+                let (old, res) = root_target.set_key_moved(k, self.payload(value));
+                (LocalOperationForRollback::Set { old }, res)
+            }
+            (PathElement::Key(k), ResolvedPathMut::Map(map_target)) => {
+                let (old, res) = map_target.set_key_moved(k, self.payload(value));
+                (LocalOperationForRollback::Set { old }, res)
+            }
+            (PathElement::Key(k), ResolvedPathMut::Table(table_target)) => {
+                let (old, res) = table_target.set_key_moved(k, self.payload(value));
+                (LocalOperationForRollback::Set { old }, res)
+            }
+            (PathElement::Index(i), ResolvedPathMut::List(list_target)) => {
+                let res = list_target.insert_moved(i, self.payload(value))?;
+                (LocalOperationForRollback::Insert, res)
+            }
+            (_, ResolvedPathMut::Text(_)) => {
+                return Err(InvalidChangeRequest::MoveInTextObject { path })
+            }
+            (PathElement::Key(_), _) => return Err(InvalidChangeRequest::NoSuchPathError { path }),
+            (PathElement::Index(_), _) => {
+                return Err(InvalidChangeRequest::InsertWithNonSequencePath { path })
+            }
+        };
+        self.record(path, rollback_op, res);
+        Ok(())
+    }
+
+    /// Increment `target`, the counter at `path`
+    pub(crate) fn increment(
+        &mut self,
+        target: ResolvedPathMut,
+        path: Path,
+        by: i64,
+    ) -> Result<(), InvalidChangeRequest> {
+        match target {
+            ResolvedPathMut::Counter(mut counter_target) => {
+                let res = counter_target.increment(by);
+                self.record(path, LocalOperationForRollback::Increment { by }, res);
+                Ok(())
             }
+            _ => Err(InvalidChangeRequest::IncrementForNonCounterObject { path }),
         }
     }
 }
@@ -0,0 +1,37 @@
+use crate::{Path, Value};
+
+/// A change made to the document by a patch, addressed by path rather than by object ID.
+///
+/// Events are returned in the order they apply, so the indices in an event refer to a sequence
+/// as it is after every earlier event. Applying the events in order to the document as it was
+/// before the patch produces the document as it is after the patch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchEvent {
+    /// The value at `path` was set to `value`, replacing anything that was there before
+    Put { path: Path, value: Value },
+    /// `values` were inserted into the list or text object at `path`, starting at `index`
+    Insert {
+        path: Path,
+        index: u32,
+        values: Vec<Value>,
+    },
+    /// `count` elements were removed from the list or text object at `path`, starting at `index`
+    Delete { path: Path, index: u32, count: u32 },
+    /// The key at the end of `path` was removed from its map or table
+    DeleteKey { path: Path },
+    /// The counter at `path` was incremented by `by`
+    Increment { path: Path, by: i64 },
+}
+
+impl PatchEvent {
+    /// The path of the object or value this event changes
+    pub fn path(&self) -> &Path {
+        match self {
+            PatchEvent::Put { path, .. }
+            | PatchEvent::Insert { path, .. }
+            | PatchEvent::Delete { path, .. }
+            | PatchEvent::DeleteKey { path }
+            | PatchEvent::Increment { path, .. } => path,
+        }
+    }
+}
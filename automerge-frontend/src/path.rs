@@ -1,7 +1,9 @@
-use std::fmt;
+use std::{fmt, str::FromStr};
 
 use smol_str::SmolStr;
 
+use crate::error::InvalidPath;
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(crate) enum PathElement {
     Key(SmolStr),
@@ -45,9 +47,37 @@ impl Path {
         self.0
     }
 
+    /// The element of this path directly below `ancestor`, if `ancestor` is a proper prefix of
+    /// this path
+    pub(crate) fn child_of(&self, ancestor: &Path) -> Option<&PathElement> {
+        if self.starts_with(ancestor) {
+            self.0.get(ancestor.0.len())
+        } else {
+            None
+        }
+    }
+
     pub(crate) fn is_root(&self) -> bool {
         self.0.is_empty()
     }
+
+    /// Whether `prefix` is this path or one of its ancestors
+    pub fn starts_with(&self, prefix: &Path) -> bool {
+        self.0.starts_with(&prefix.0)
+    }
+
+    /// This path as an [RFC 6901](https://tools.ietf.org/html/rfc6901) JSON Pointer
+    pub fn to_json_pointer(&self) -> String {
+        let mut pointer = String::new();
+        for element in &self.0 {
+            pointer.push('/');
+            match element {
+                PathElement::Key(k) => pointer.push_str(&k.replace('~', "~0").replace('/', "~1")),
+                PathElement::Index(i) => pointer.push_str(&i.to_string()),
+            }
+        }
+        pointer
+    }
 }
 
 impl fmt::Display for PathElement {
@@ -58,3 +88,110 @@ impl fmt::Display for PathElement {
         }
     }
 }
+
+/// Paths are displayed in the syntax used by `automerge-cli`, e.g. `$["birds"][0]`, which can be
+/// parsed back with [`Path::from_str`]
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "$")?;
+        for element in &self.0 {
+            match element {
+                PathElement::Key(k) => {
+                    // serializing a string can't fail
+                    write!(f, "[{}]", serde_json::to_string(k.as_str()).unwrap())?
+                }
+                PathElement::Index(i) => write!(f, "[{}]", i)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse either an [RFC 6901](https://tools.ietf.org/html/rfc6901) JSON Pointer such as
+/// `/birds/0`, or the syntax used by `automerge-cli` such as `$["birds"][0]`.
+///
+/// Without a document to look at, a JSON Pointer reference token is ambiguous. Tokens which are
+/// array indices according to RFC 6901 (digits without a leading zero) are parsed as indices and
+/// anything else as a key. The `$` syntax has no such ambiguity, keys are JSON strings.
+impl FromStr for Path {
+    type Err = InvalidPath;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            Ok(Path::root())
+        } else if s.starts_with('/') {
+            Ok(parse_json_pointer(s))
+        } else if let Some(rest) = s.strip_prefix('$') {
+            parse_segments(s, rest)
+        } else {
+            Err(InvalidPath {
+                path: s.to_string(),
+                position: 0,
+            })
+        }
+    }
+}
+
+fn parse_json_pointer(pointer: &str) -> Path {
+    let elements = json_pointer_tokens(pointer)
+        .map(|token| {
+            let is_index = token.bytes().all(|b| b.is_ascii_digit())
+                && !(token.len() > 1 && token.starts_with('0'));
+            match token.parse() {
+                Ok(index) if is_index => PathElement::Index(index),
+                _ => PathElement::Key(token.into()),
+            }
+        })
+        .collect();
+    Path(elements)
+}
+
+/// The reference tokens of `pointer`, which must start with a `/`, with `~1` and `~0` decoded
+pub(crate) fn json_pointer_tokens(pointer: &str) -> impl Iterator<Item = String> + '_ {
+    pointer[1..]
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+}
+
+/// Parse the `["key"][0]` segments which follow the `$` of a path
+fn parse_segments(path: &str, mut rest: &str) -> Result<Path, InvalidPath> {
+    let invalid = |rest: &str| InvalidPath {
+        path: path.to_string(),
+        position: path.len() - rest.len(),
+    };
+    let mut elements = Vec::new();
+    while !rest.is_empty() {
+        let segment = rest.strip_prefix('[').ok_or_else(|| invalid(rest))?;
+        if segment.starts_with('"') {
+            // find the closing quote, skipping escaped characters
+            let mut escaped = false;
+            let end = segment
+                .char_indices()
+                .skip(1)
+                .find(|(_, c)| {
+                    let end = *c == '"' && !escaped;
+                    escaped = *c == '\\' && !escaped;
+                    end
+                })
+                .map(|(i, _)| i + 1)
+                .ok_or_else(|| invalid(segment))?;
+            let key: String =
+                serde_json::from_str(&segment[..end]).map_err(|_| invalid(segment))?;
+            elements.push(PathElement::Key(key.into()));
+            rest = segment[end..]
+                .strip_prefix(']')
+                .ok_or_else(|| invalid(&segment[end..]))?;
+        } else {
+            let end = segment.find(']').ok_or_else(|| invalid(segment))?;
+            let index = &segment[..end];
+            if index.is_empty() || !index.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid(segment));
+            }
+            elements.push(PathElement::Index(
+                index.parse().map_err(|_| invalid(segment))?,
+            ));
+            rest = &segment[end + 1..];
+        }
+    }
+    Ok(Path(elements))
+}
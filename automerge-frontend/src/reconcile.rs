@@ -0,0 +1,422 @@
+//! Reading Rust types from a document ("hydrating") and writing them back by making only the
+//! changes needed to reconcile the document with the new value.
+//!
+//! [`Hydrate`] and [`Reconcile`] are implemented for the primitive types, `Option`, `Vec` and
+//! `HashMap`, and for the [`Text`], [`Counter`] and [`Timestamp`] newtypes which request those
+//! representations. With the `derive` feature they can be derived for structs with named fields
+//! and for enums whose variants have no fields, using `#[derive(Automerge)]`.
+//!
+//! A field marked `#[automerge(key)]` identifies a struct within a list: when a list of such
+//! structs is reconciled, elements whose key is unchanged are updated in place even if other
+//! elements were inserted or removed around them, and elements with a new key are inserted
+//! rather than overwriting whichever element was at their index.
+
+use std::{
+    any::type_name,
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    hash::{BuildHasher, Hash},
+};
+
+use smol_str::SmolStr;
+
+use crate::{
+    error::HydrateError,
+    sequence_diff::{self, Step},
+    value_ref::{RootRef, ValueRef},
+    value_serde::{Counter, Text, Timestamp},
+    InvalidChangeRequest, LocalChange, MutableDocument, Path, Primitive, Value,
+};
+
+/// A type which can be read from a document
+pub trait Hydrate: Sized {
+    fn hydrate(value: ValueRef) -> Result<Self, HydrateError>;
+
+    /// Read this type from the root of a document. Only types which are represented by maps can
+    /// be read from the root.
+    fn hydrate_root(root: RootRef) -> Result<Self, HydrateError> {
+        Err(unexpected::<Self>(root.value()))
+    }
+
+    /// The value of a field which is missing from its map. This is an error unless the type has
+    /// a natural default, such as `None`.
+    fn hydrate_missing(field: &'static str) -> Result<Self, HydrateError> {
+        Err(HydrateError::MissingField(field))
+    }
+}
+
+/// A type which can be written to a document
+pub trait Reconcile {
+    /// This value as a [`Value`], which is used when there is no existing value to reconcile with
+    fn value(&self) -> Value;
+
+    /// Make the changes needed for the value at `path`, which is currently `current`, to be this
+    /// value. By default the value is set unless it is already equal to `current`.
+    fn reconcile(
+        &self,
+        doc: &mut dyn MutableDocument,
+        path: &Path,
+        current: Option<&Value>,
+    ) -> Result<(), InvalidChangeRequest> {
+        let value = self.value();
+        if current == Some(&value) {
+            Ok(())
+        } else {
+            doc.add_change(LocalChange::set(path.clone(), value))
+        }
+    }
+
+    /// The key which identifies this value within a list, if it has one
+    fn key(&self) -> Option<Value> {
+        None
+    }
+
+    /// The key of `value`, which is an element of a list of this type in the document
+    fn key_of(_value: &Value) -> Option<Value>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+/// Reconcile the value at `path` with `value`
+pub(crate) fn reconcile<T>(
+    doc: &mut dyn MutableDocument,
+    path: &Path,
+    value: &T,
+) -> Result<(), InvalidChangeRequest>
+where
+    T: Reconcile + ?Sized,
+{
+    let current = doc.value_at_path(path);
+    value.reconcile(doc, path, current.as_ref())
+}
+
+fn unexpected<T>(found: Value) -> HydrateError {
+    HydrateError::Unexpected {
+        expected: type_name::<T>(),
+        found,
+    }
+}
+
+macro_rules! primitive_impls {
+    ($($ty:ty => $variant:ident, $hydrate:expr;)*) => {
+        $(
+            impl Hydrate for $ty {
+                fn hydrate(value: ValueRef) -> Result<Self, HydrateError> {
+                    let hydrate: fn(&Primitive) -> Option<$ty> = $hydrate;
+                    value
+                        .primitive()
+                        .and_then(hydrate)
+                        .ok_or_else(|| unexpected::<Self>(value.value()))
+                }
+            }
+
+            impl Reconcile for $ty {
+                fn value(&self) -> Value {
+                    Value::Primitive(Primitive::$variant(self.clone().into()))
+                }
+            }
+        )*
+    };
+}
+
+primitive_impls! {
+    bool => Boolean, Primitive::boolean;
+    String => Str, |p| p.str().map(String::from);
+    SmolStr => Str, |p| p.str().map(SmolStr::from);
+    i64 => Int, as_int;
+    i32 => Int, |p| as_int(p).and_then(|n| n.try_into().ok());
+    u64 => Uint, as_uint;
+    u32 => Uint, |p| as_uint(p).and_then(|n| n.try_into().ok());
+    f64 => F64, as_f64;
+    f32 => F64, |p| as_f64(p).map(|n| n as f32);
+}
+
+fn as_int(p: &Primitive) -> Option<i64> {
+    match p {
+        Primitive::Int(n) | Primitive::Counter(n) | Primitive::Timestamp(n) => Some(*n),
+        Primitive::Uint(n) => (*n).try_into().ok(),
+        _ => None,
+    }
+}
+
+fn as_uint(p: &Primitive) -> Option<u64> {
+    match p {
+        Primitive::Uint(n) => Some(*n),
+        Primitive::Int(n) | Primitive::Counter(n) | Primitive::Timestamp(n) => (*n).try_into().ok(),
+        _ => None,
+    }
+}
+
+fn as_f64(p: &Primitive) -> Option<f64> {
+    match p {
+        Primitive::F64(n) => Some(*n),
+        Primitive::Int(n) => Some(*n as f64),
+        Primitive::Uint(n) => Some(*n as f64),
+        _ => None,
+    }
+}
+
+impl Hydrate for Text {
+    fn hydrate(value: ValueRef) -> Result<Self, HydrateError> {
+        match &value {
+            ValueRef::Text(text) => Ok(Text(text.iter().map(SmolStr::as_str).collect())),
+            _ => Err(unexpected::<Self>(value.value())),
+        }
+    }
+}
+
+impl Reconcile for Text {
+    fn value(&self) -> Value {
+        Value::Text(
+            unicode_segmentation::UnicodeSegmentation::graphemes(self.0.as_str(), true)
+                .map(SmolStr::new)
+                .collect(),
+        )
+    }
+
+    fn reconcile(
+        &self,
+        doc: &mut dyn MutableDocument,
+        path: &Path,
+        current: Option<&Value>,
+    ) -> Result<(), InvalidChangeRequest> {
+        match current {
+            Some(Value::Text(_)) => doc.update_text(path, &self.0),
+            _ => doc.add_change(LocalChange::set(path.clone(), self.value())),
+        }
+    }
+}
+
+impl Hydrate for Counter {
+    fn hydrate(value: ValueRef) -> Result<Self, HydrateError> {
+        value
+            .primitive()
+            .and_then(Primitive::counter)
+            .map(Counter)
+            .ok_or_else(|| unexpected::<Self>(value.value()))
+    }
+}
+
+impl Reconcile for Counter {
+    fn value(&self) -> Value {
+        Value::Primitive(Primitive::Counter(self.0))
+    }
+
+    fn reconcile(
+        &self,
+        doc: &mut dyn MutableDocument,
+        path: &Path,
+        current: Option<&Value>,
+    ) -> Result<(), InvalidChangeRequest> {
+        match current {
+            // incrementing rather than setting the counter preserves concurrent increments
+            Some(Value::Primitive(Primitive::Counter(current))) if *current == self.0 => Ok(()),
+            Some(Value::Primitive(Primitive::Counter(current))) => {
+                doc.add_change(LocalChange::increment_by(path.clone(), self.0 - current))
+            }
+            _ => doc.add_change(LocalChange::set(path.clone(), self.value())),
+        }
+    }
+}
+
+impl Hydrate for Timestamp {
+    fn hydrate(value: ValueRef) -> Result<Self, HydrateError> {
+        value
+            .primitive()
+            .and_then(Primitive::timestamp)
+            .map(Timestamp)
+            .ok_or_else(|| unexpected::<Self>(value.value()))
+    }
+}
+
+impl Reconcile for Timestamp {
+    fn value(&self) -> Value {
+        Value::Primitive(Primitive::Timestamp(self.0))
+    }
+}
+
+impl<T: Hydrate> Hydrate for Option<T> {
+    fn hydrate(value: ValueRef) -> Result<Self, HydrateError> {
+        match value.primitive() {
+            Some(Primitive::Null) => Ok(None),
+            _ => T::hydrate(value).map(Some),
+        }
+    }
+
+    fn hydrate_missing(_field: &'static str) -> Result<Self, HydrateError> {
+        Ok(None)
+    }
+}
+
+impl<T: Reconcile> Reconcile for Option<T> {
+    fn value(&self) -> Value {
+        match self {
+            Some(value) => value.value(),
+            None => Value::Primitive(Primitive::Null),
+        }
+    }
+
+    fn reconcile(
+        &self,
+        doc: &mut dyn MutableDocument,
+        path: &Path,
+        current: Option<&Value>,
+    ) -> Result<(), InvalidChangeRequest> {
+        match self {
+            Some(value) => value.reconcile(doc, path, current),
+            None if current == Some(&Value::Primitive(Primitive::Null)) => Ok(()),
+            None => doc.add_change(LocalChange::set(path.clone(), self.value())),
+        }
+    }
+
+    fn key(&self) -> Option<Value> {
+        self.as_ref().and_then(T::key)
+    }
+
+    fn key_of(value: &Value) -> Option<Value> {
+        T::key_of(value)
+    }
+}
+
+impl<T: Hydrate> Hydrate for Vec<T> {
+    fn hydrate(value: ValueRef) -> Result<Self, HydrateError> {
+        match &value {
+            ValueRef::List(list) => list.iter().map(T::hydrate).collect(),
+            _ => Err(unexpected::<Self>(value.value())),
+        }
+    }
+}
+
+impl<T: Reconcile> Reconcile for Vec<T> {
+    fn value(&self) -> Value {
+        Value::List(self.iter().map(T::value).collect())
+    }
+
+    fn reconcile(
+        &self,
+        doc: &mut dyn MutableDocument,
+        path: &Path,
+        current: Option<&Value>,
+    ) -> Result<(), InvalidChangeRequest> {
+        match current {
+            Some(Value::List(elements)) => reconcile_list(doc, path, elements, self),
+            _ => doc.add_change(LocalChange::set(path.clone(), self.value())),
+        }
+    }
+}
+
+/// Reconcile a list with `new`. Elements are matched by key if the elements of `new` have keys,
+/// and by value otherwise. A run of unmatched elements which replaces a run of the same length is
+/// reconciled element by element, unless the elements have keys, in which case the old elements
+/// are deleted and the new ones inserted.
+fn reconcile_list<T: Reconcile>(
+    doc: &mut dyn MutableDocument,
+    path: &Path,
+    elements: &[Value],
+    new: &[T],
+) -> Result<(), InvalidChangeRequest> {
+    let new_keys = new.iter().map(T::key).collect::<Vec<_>>();
+    let keyed = new_keys.iter().any(Option::is_some);
+    let edits = if keyed {
+        let old_keys = elements.iter().map(T::key_of).collect::<Vec<_>>();
+        sequence_diff::diff(elements.len(), new.len(), |i, j| {
+            old_keys[i].is_some() && old_keys[i] == new_keys[j]
+        })
+    } else {
+        let new_values = new.iter().map(T::value).collect::<Vec<_>>();
+        sequence_diff::diff(elements.len(), new.len(), |i, j| {
+            elements[i] == new_values[j]
+        })
+    };
+
+    sequence_diff::apply(edits, !keyed, |step| match step {
+        // elements with the same key may still differ in their other fields
+        Step::Keep {
+            index,
+            old,
+            new: new_index,
+        } if keyed => {
+            new[new_index].reconcile(doc, &path.clone().index(index), Some(&elements[old]))
+        }
+        Step::Keep { .. } => Ok(()),
+        Step::Replace {
+            index,
+            old,
+            new: new_index,
+        } => new[new_index].reconcile(doc, &path.clone().index(index), Some(&elements[old])),
+        Step::Delete { index, .. } => {
+            doc.add_change(LocalChange::delete(path.clone().index(index)))
+        }
+        Step::Insert {
+            index,
+            new: new_index,
+        } => doc.add_change(LocalChange::insert(
+            path.clone().index(index),
+            new[new_index].value(),
+        )),
+    })
+}
+
+impl<K, T, S> Hydrate for HashMap<K, T, S>
+where
+    K: From<SmolStr> + Eq + Hash,
+    T: Hydrate,
+    S: BuildHasher + Default,
+{
+    fn hydrate(value: ValueRef) -> Result<Self, HydrateError> {
+        let map = match &value {
+            ValueRef::Map(map) => map.iter().collect::<Vec<_>>(),
+            ValueRef::Table(table) => table.iter().collect(),
+            _ => return Err(unexpected::<Self>(value.value())),
+        };
+        map.into_iter()
+            .map(|(key, value)| Ok((K::from(key.clone()), T::hydrate(value)?)))
+            .collect()
+    }
+
+    fn hydrate_root(root: RootRef) -> Result<Self, HydrateError> {
+        root.iter()
+            .map(|(key, value)| Ok((K::from(key.clone()), T::hydrate(value)?)))
+            .collect()
+    }
+}
+
+impl<K, T, S> Reconcile for HashMap<K, T, S>
+where
+    K: AsRef<str>,
+    T: Reconcile,
+{
+    fn value(&self) -> Value {
+        Value::Map(
+            self.iter()
+                .map(|(key, value)| (SmolStr::new(key), value.value()))
+                .collect(),
+        )
+    }
+
+    fn reconcile(
+        &self,
+        doc: &mut dyn MutableDocument,
+        path: &Path,
+        current: Option<&Value>,
+    ) -> Result<(), InvalidChangeRequest> {
+        let props = match current {
+            Some(Value::Map(props)) => props,
+            _ => return doc.add_change(LocalChange::set(path.clone(), self.value())),
+        };
+        let new_keys = self.keys().map(AsRef::as_ref).collect::<HashSet<&str>>();
+        for key in props.keys() {
+            if !new_keys.contains(&key.as_str()) {
+                doc.add_change(LocalChange::delete(path.clone().key(key.clone())))?;
+            }
+        }
+        for (key, value) in self {
+            let key = key.as_ref();
+            value.reconcile(doc, &path.clone().key(key), props.get(key))?;
+        }
+        Ok(())
+    }
+}
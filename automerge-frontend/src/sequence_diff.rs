@@ -0,0 +1,222 @@
+//! Myers' diff algorithm, used to turn a new version of a sequence into a minimal set of inserts
+//! and deletes.
+
+use std::ops::Range;
+
+/// What to do with the next element of the old or new sequence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Edit {
+    /// The next elements of both sequences are equal
+    Equal,
+    /// The next element of the old sequence is not in the new sequence
+    Delete,
+    /// The next element of the new sequence is not in the old sequence
+    Insert,
+}
+
+/// The shortest edit script which turns a sequence of `old_len` elements into a sequence of
+/// `new_len` elements, where `eq(i, j)` says whether element `i` of the old sequence is equal to
+/// element `j` of the new sequence.
+///
+/// This is the linear space variant of Myers' algorithm, which splits the sequences where the
+/// searches from either end meet and diffs the two halves separately. It takes time proportional
+/// to the length of the sequences times the length of the edit script.
+pub(crate) fn diff<F>(old_len: usize, new_len: usize, eq: F) -> Vec<Edit>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let mut edits = Vec::with_capacity(old_len.max(new_len));
+    diff_range(0..old_len, 0..new_len, &eq, &mut edits);
+    edits
+}
+
+/// Push the shortest edit script from `old` to `new` onto `edits`
+fn diff_range<F>(mut old: Range<usize>, mut new: Range<usize>, eq: &F, edits: &mut Vec<Edit>)
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let mut prefix = 0;
+    while old.start < old.end && new.start < new.end && eq(old.start, new.start) {
+        old.start += 1;
+        new.start += 1;
+        prefix += 1;
+    }
+    let mut suffix = 0;
+    while old.start < old.end && new.start < new.end && eq(old.end - 1, new.end - 1) {
+        old.end -= 1;
+        new.end -= 1;
+        suffix += 1;
+    }
+
+    edits.resize(edits.len() + prefix, Edit::Equal);
+    if old.is_empty() || new.is_empty() {
+        edits.resize(edits.len() + old.len(), Edit::Delete);
+        edits.resize(edits.len() + new.len(), Edit::Insert);
+    } else {
+        match middle(old.clone(), new.clone(), eq) {
+            Some((x, y)) => {
+                diff_range(old.start..x, new.start..y, eq, edits);
+                diff_range(x..old.end, y..new.end, eq, edits);
+            }
+            None => {
+                edits.resize(edits.len() + old.len(), Edit::Delete);
+                edits.resize(edits.len() + new.len(), Edit::Insert);
+            }
+        }
+    }
+    edits.resize(edits.len() + suffix, Edit::Equal);
+}
+
+/// Search for the shortest edit script from the start and from the end of `old` and `new` at
+/// once, returning the point in both sequences where the two searches meet. This point is on a
+/// shortest edit script. Returns `None` if the sequences have no elements in common.
+fn middle<F>(old: Range<usize>, new: Range<usize>, eq: &F) -> Option<(usize, usize)>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m + 1) / 2;
+    let offset = max;
+    let len = 2 * max + 2;
+    // `forward[offset + k]` is the furthest x reached on diagonal k from the start, and
+    // `backward[offset + k]` the furthest x reached on diagonal k from the end, counting from the
+    // end. -1 marks a diagonal not reached yet.
+    let mut forward = vec![-1_isize; len as usize];
+    let mut backward = vec![-1_isize; len as usize];
+    forward[(offset + 1) as usize] = 0;
+    backward[(offset + 1) as usize] = 0;
+    let delta = n - m;
+    // if the difference in lengths is odd the forward search finds the overlap, otherwise the
+    // backward one does
+    let odd = delta % 2 != 0;
+    // diagonals which have run off the edge of the grid at the start and end of the range
+    let (mut forward_start, mut forward_end) = (0, 0);
+    let (mut backward_start, mut backward_end) = (0, 0);
+
+    for d in 0..max {
+        let mut k = -d + forward_start;
+        while k <= d - forward_end {
+            let i = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && forward[i - 1] < forward[i + 1]) {
+                forward[i + 1]
+            } else {
+                forward[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && eq(old.start + x as usize, new.start + y as usize) {
+                x += 1;
+                y += 1;
+            }
+            forward[i] = x;
+            if x > n {
+                forward_end += 2;
+            } else if y > m {
+                forward_start += 2;
+            } else if odd {
+                let j = offset + delta - k;
+                if j >= 0 && j < len && backward[j as usize] != -1 && x >= n - backward[j as usize]
+                {
+                    return Some((old.start + x as usize, new.start + y as usize));
+                }
+            }
+            k += 2;
+        }
+
+        let mut k = -d + backward_start;
+        while k <= d - backward_end {
+            let i = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && backward[i - 1] < backward[i + 1]) {
+                backward[i + 1]
+            } else {
+                backward[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && eq(old.end - 1 - x as usize, new.end - 1 - y as usize) {
+                x += 1;
+                y += 1;
+            }
+            backward[i] = x;
+            if x > n {
+                backward_end += 2;
+            } else if y > m {
+                backward_start += 2;
+            } else if !odd {
+                let j = offset + delta - k;
+                if j >= 0 && j < len && forward[j as usize] != -1 {
+                    let forward_x = forward[j as usize];
+                    let forward_y = forward_x - (delta - k);
+                    if forward_x >= n - x {
+                        return Some((
+                            old.start + forward_x as usize,
+                            new.start + forward_y as usize,
+                        ));
+                    }
+                }
+            }
+            k += 2;
+        }
+    }
+    None
+}
+
+/// A change which turns part of the old sequence into the new one. `index` is the index the
+/// change applies at in the sequence as it is after the changes before it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Step {
+    /// Element `old` of the old sequence is equal to element `new` of the new sequence
+    Keep { index: u32, old: usize, new: usize },
+    /// Element `old` of the old sequence is replaced by element `new` of the new sequence
+    Replace { index: u32, old: usize, new: usize },
+    /// Element `old` of the old sequence is removed
+    Delete { index: u32, old: usize },
+    /// Element `new` of the new sequence is inserted
+    Insert { index: u32, new: usize },
+}
+
+/// Turn `edits` into the steps which apply them to a sequence, calling `step` for each of them.
+///
+/// If `replace` is set, the elements of a run of deletes and inserts are replaced one for one as
+/// far as possible, so that changes within elements which differ can be kept. Otherwise each
+/// run only deletes and inserts.
+pub(crate) fn apply<E, F>(edits: Vec<Edit>, replace: bool, mut step: F) -> Result<(), E>
+where
+    F: FnMut(Step) -> Result<(), E>,
+{
+    let (mut index, mut old, mut new) = (0_u32, 0, 0);
+    let mut edits = edits.into_iter().peekable();
+    while let Some(edit) = edits.next() {
+        if edit == Edit::Equal {
+            step(Step::Keep { index, old, new })?;
+            index += 1;
+            old += 1;
+            new += 1;
+            continue;
+        }
+        let (mut deletes, mut inserts) = (0, 0);
+        let mut edit = Some(edit);
+        while let Some(e) = edit.filter(|e| *e != Edit::Equal) {
+            match e {
+                Edit::Delete => deletes += 1,
+                _ => inserts += 1,
+            }
+            edit = edits.next_if(|e| *e != Edit::Equal);
+        }
+        let replaces = if replace { deletes.min(inserts) } else { 0 };
+        for _ in 0..replaces {
+            step(Step::Replace { index, old, new })?;
+            index += 1;
+            old += 1;
+            new += 1;
+        }
+        for _ in replaces..deletes {
+            step(Step::Delete { index, old })?;
+            old += 1;
+        }
+        for _ in replaces..inserts {
+            step(Step::Insert { index, new })?;
+            index += 1;
+            new += 1;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use crate::Cursor;
+
+/// Which side of its element the boundary of a [`Span`] sticks to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bias {
+    /// The boundary is just before the element, so elements inserted at the boundary are after
+    /// it
+    Before,
+    /// The boundary is just after the element, so elements inserted at the boundary are before
+    /// it
+    After,
+}
+
+/// A range of a list or text object whose boundaries are anchored to elements, so that it stays
+/// on the same elements as the sequence is edited, e.g. a selection or the text a comment is
+/// attached to.
+///
+/// Spans are tracked by a frontend, see [`Frontend::add_span`](crate::Frontend::add_span). When
+/// the element a boundary is anchored to is deleted the boundary stays where the element was.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub start: Cursor,
+    pub start_bias: Bias,
+    pub end: Cursor,
+    pub end_bias: Bias,
+}
+
+impl Span {
+    /// The span from the element at `start` to the element at `end`, including both. Elements
+    /// inserted just before `start` or just after `end` are not in the span.
+    pub fn new(start: Cursor, end: Cursor) -> Span {
+        Span {
+            start,
+            start_bias: Bias::Before,
+            end,
+            end_bias: Bias::After,
+        }
+    }
+}
+
+/// Identifies a span added with [`Frontend::add_span`](crate::Frontend::add_span)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SpanId(pub(crate) u64);
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use automerge_protocol as amp;
+use smol_str::SmolStr;
+
+use super::{MultiValue, StateTree, StateTreeComposite, StateTreeValue};
+use crate::Path;
+
+impl StateTree {
+    /// The paths in this tree which applying `diff` would change.
+    ///
+    /// This must be called before the diff is applied. A diff which updates an object that
+    /// already exists is followed into that object, so only the paths within it which actually
+    /// change are returned. A diff which replaces a value, or which inserts or removes elements
+    /// of a sequence (shifting every later element), produces the path of the value or sequence
+    /// itself.
+    pub(crate) fn changed_paths(&self, diff: &amp::RootDiff) -> Vec<Path> {
+        let mut paths = Vec::new();
+        map_changed_paths(
+            Some(&self.root_props),
+            &diff.props,
+            &Path::root(),
+            &mut paths,
+        );
+        paths
+    }
+}
+
+fn map_changed_paths(
+    props: Option<&HashMap<SmolStr, MultiValue>>,
+    prop_diffs: &HashMap<SmolStr, HashMap<amp::OpId, amp::Diff>>,
+    path: &Path,
+    paths: &mut Vec<Path>,
+) {
+    for (key, diffs) in prop_diffs {
+        let path = path.clone().key(key.clone());
+        let existing = props.and_then(|props| props.get(key));
+        value_changed_paths(existing, diffs.iter(), path, paths);
+    }
+}
+
+/// Changed paths for the values of a single key or index, given the `MultiValue` it currently
+/// holds (if any)
+fn value_changed_paths<'a, I>(
+    existing: Option<&MultiValue>,
+    diffs: I,
+    path: Path,
+    paths: &mut Vec<Path>,
+) where
+    I: ExactSizeIterator<Item = (&'a amp::OpId, &'a amp::Diff)>,
+{
+    if diffs.len() == 0 {
+        // the key was deleted
+        paths.push(path);
+        return;
+    }
+    let mut nested = Vec::new();
+    for (opid, diff) in diffs {
+        match (existing.and_then(|e| e.get(opid)), diff) {
+            (Some(StateTreeValue::Composite(composite)), amp::Diff::Map(_))
+            | (Some(StateTreeValue::Composite(composite)), amp::Diff::Table(_))
+            | (Some(StateTreeValue::Composite(composite)), amp::Diff::List(_))
+            | (Some(StateTreeValue::Composite(composite)), amp::Diff::Text(_)) => {
+                composite_changed_paths(composite, diff, &path, &mut nested)
+            }
+            _ => {
+                // a new value, so everything below this path has changed
+                paths.push(path);
+                return;
+            }
+        }
+    }
+    paths.extend(nested);
+}
+
+fn composite_changed_paths(
+    composite: &StateTreeComposite,
+    diff: &amp::Diff,
+    path: &Path,
+    paths: &mut Vec<Path>,
+) {
+    match (composite, diff) {
+        (StateTreeComposite::Map(map), amp::Diff::Map(amp::MapDiff { props, .. })) => {
+            map_changed_paths(Some(&map.props), props, path, paths)
+        }
+        (StateTreeComposite::Table(table), amp::Diff::Table(amp::TableDiff { props, .. })) => {
+            map_changed_paths(Some(&table.props), props, path, paths)
+        }
+        (StateTreeComposite::List(list), amp::Diff::List(amp::ListDiff { edits, .. })) => {
+            let only_updates = edits
+                .iter()
+                .all(|edit| matches!(edit, amp::DiffEdit::Update { .. }));
+            if !only_updates {
+                paths.push(path.clone());
+                return;
+            }
+            // group the updates by index as conflicting values for the same index each have an
+            // update
+            let mut updates: HashMap<u64, HashMap<&amp::OpId, &amp::Diff>> = HashMap::new();
+            for edit in edits {
+                if let amp::DiffEdit::Update {
+                    index,
+                    op_id,
+                    value,
+                } = edit
+                {
+                    updates.entry(*index).or_default().insert(op_id, value);
+                }
+            }
+            for (index, diffs) in updates {
+                let existing = list
+                    .elements
+                    .get(index as usize)
+                    .map(|(_, multivalue)| multivalue);
+                let path = path.clone().index(index as u32);
+                value_changed_paths(existing, diffs.into_iter(), path, paths);
+            }
+        }
+        (_, amp::Diff::Text(amp::TextDiff { edits, .. })) if edits.is_empty() => {}
+        _ => paths.push(path.clone()),
+    }
+}
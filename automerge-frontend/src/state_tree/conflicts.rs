@@ -0,0 +1,121 @@
+use automerge_protocol as amp;
+
+use super::{MultiGrapheme, MultiValue, StateTree, StateTreeComposite, StateTreeValue};
+use crate::{Path, Primitive, Value};
+
+/// A location in the document which holds more than one value, because concurrent changes set it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub path: Path,
+    /// Each value and the ID of the op which set it, sorted by op ID, so the value shown by
+    /// default is the last one
+    pub values: Vec<(amp::OpId, Value)>,
+}
+
+impl Conflict {
+    /// The ID of the op which set the value shown by default
+    pub fn default_opid(&self) -> &amp::OpId {
+        &self.values.last().expect("conflicts have values").0
+    }
+}
+
+/// An iterator over the conflicts in a document, in depth first order with the keys of maps
+/// sorted, see [`Frontend::conflicts`](crate::Frontend::conflicts)
+///
+/// Only the value shown by default is searched for conflicts, as the paths of the other values
+/// can't be read.
+pub struct DocumentConflicts<'a> {
+    stack: Vec<(Path, Location<'a>)>,
+}
+
+enum Location<'a> {
+    Value(&'a MultiValue),
+    Grapheme(&'a MultiGrapheme),
+}
+
+impl<'a> DocumentConflicts<'a> {
+    pub(super) fn new(tree: &'a StateTree) -> Self {
+        let mut conflicts = DocumentConflicts { stack: Vec::new() };
+        let mut keys = tree.root_props.iter().collect::<Vec<_>>();
+        keys.sort_by_key(|(k, _)| *k);
+        for (key, value) in keys.into_iter().rev() {
+            conflicts
+                .stack
+                .push((Path::root().key(key.clone()), Location::Value(value)));
+        }
+        conflicts
+    }
+
+    /// Add the children of `value`, which is at `path`, to the locations to visit
+    fn push_children(&mut self, path: &Path, value: &'a StateTreeValue) {
+        let composite = match value {
+            StateTreeValue::Composite(composite) => composite,
+            StateTreeValue::Leaf(_) => return,
+        };
+        // children are pushed in reverse so that they are visited in order
+        match composite {
+            StateTreeComposite::Map(map) => self.push_props(path, map.props.iter()),
+            StateTreeComposite::Table(table) => self.push_props(path, table.props.iter()),
+            StateTreeComposite::List(list) => {
+                let elements = list.elements.iter().collect::<Vec<_>>();
+                for (i, value) in elements.into_iter().enumerate().rev() {
+                    self.stack
+                        .push((path.clone().index(i as u32), Location::Value(value)));
+                }
+            }
+            StateTreeComposite::Text(text) => {
+                let graphemes = text.graphemes.iter().collect::<Vec<_>>();
+                for (i, grapheme) in graphemes.into_iter().enumerate().rev() {
+                    self.stack
+                        .push((path.clone().index(i as u32), Location::Grapheme(grapheme)));
+                }
+            }
+        }
+    }
+
+    fn push_props<I>(&mut self, path: &Path, props: I)
+    where
+        I: Iterator<Item = (&'a smol_str::SmolStr, &'a MultiValue)>,
+    {
+        let mut props = props.collect::<Vec<_>>();
+        props.sort_by_key(|(k, _)| *k);
+        for (key, value) in props.into_iter().rev() {
+            self.stack
+                .push((path.clone().key(key.clone()), Location::Value(value)));
+        }
+    }
+}
+
+impl<'a> Iterator for DocumentConflicts<'a> {
+    type Item = Conflict;
+
+    fn next(&mut self) -> Option<Conflict> {
+        while let Some((path, location)) = self.stack.pop() {
+            match location {
+                Location::Value(value) => {
+                    self.push_children(&path, value.default_statetree_value());
+                    if value.has_conflicts() {
+                        let values = value
+                            .sorted_values()
+                            .into_iter()
+                            .map(|(opid, v)| (opid.clone(), v.realise_value()))
+                            .collect();
+                        return Some(Conflict { path, values });
+                    }
+                }
+                Location::Grapheme(grapheme) if grapheme.has_conflicts() => {
+                    let values = grapheme
+                        .sorted_values()
+                        .into_iter()
+                        .map(|(opid, g)| {
+                            (opid.clone(), Value::Primitive(Primitive::Str(g.clone())))
+                        })
+                        .collect();
+                    return Some(Conflict { path, values });
+                }
+                Location::Grapheme(_) => {}
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use automerge_protocol as amp;
+
+use crate::{Bias, Span, SpanId};
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct CursorState {
+    pub(super) referring_object_id: amp::ObjectId,
+    pub(super) referring_key: amp::Key,
+    pub(super) referred_object_id: amp::ObjectId,
+    pub(super) referred_opid: amp::OpId,
+    pub(super) index: usize,
+}
+
+/// The cursors and spans which refer to sequences in a state tree
+#[derive(Debug, PartialEq, Clone)]
+pub(super) struct Cursors {
+    values: HashMap<amp::ObjectId, Vec<CursorState>>,
+    spans: HashMap<SpanId, TrackedSpan>,
+}
+
+/// A span and the positions of its boundaries in the sequence it refers to
+#[derive(Debug, PartialEq, Clone)]
+pub(super) struct TrackedSpan {
+    pub(super) object_id: amp::ObjectId,
+    pub(super) start: Anchor,
+    pub(super) end: Anchor,
+}
+
+/// A boundary of a span, which sticks to the element inserted by `elem_id`
+#[derive(Debug, PartialEq, Clone)]
+pub(super) struct Anchor {
+    pub(super) elem_id: amp::OpId,
+    pub(super) bias: Bias,
+    /// `None` if the element has not been inserted yet as far as this tree knows, e.g. the span
+    /// was added before the backend acknowledged the change which inserted it
+    pub(super) position: Option<Position>,
+}
+
+/// Where the element an anchor sticks to was as of the last diff applied to the tree
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(super) enum Position {
+    /// The element is at this index
+    At(usize),
+    /// The element was deleted, and was at this index when it was
+    Deleted(usize),
+}
+
+impl Cursors {
+    pub(super) fn new() -> Cursors {
+        Cursors {
+            values: HashMap::new(),
+            spans: HashMap::new(),
+        }
+    }
+
+    pub(super) fn new_from(cursor: CursorState) -> Cursors {
+        Cursors {
+            values: maplit::hashmap! {
+                cursor.referred_object_id.clone() => vec![cursor],
+            },
+            spans: HashMap::new(),
+        }
+    }
+
+    pub(super) fn extend(&mut self, other: Cursors) {
+        for (k, v) in other.values {
+            if let Some(c1) = self.values.get_mut(&k) {
+                c1.extend(v)
+            } else {
+                self.values.insert(k, v);
+            }
+        }
+        self.spans.extend(other.spans);
+    }
+
+    /// Start tracking `span`, given the current index of each of its elements
+    pub(super) fn track_span(
+        &mut self,
+        id: SpanId,
+        span: &Span,
+        start_index: Option<usize>,
+        end_index: Option<usize>,
+    ) {
+        self.spans.insert(
+            id,
+            TrackedSpan {
+                object_id: span.start.object.clone(),
+                start: Anchor {
+                    elem_id: span.start.elem_opid.clone(),
+                    bias: span.start_bias,
+                    position: start_index.map(Position::At),
+                },
+                end: Anchor {
+                    elem_id: span.end.elem_opid.clone(),
+                    bias: span.end_bias,
+                    position: end_index.map(Position::At),
+                },
+            },
+        );
+    }
+
+    pub(super) fn untrack_span(&mut self, id: SpanId) -> bool {
+        self.spans.remove(&id).is_some()
+    }
+
+    pub(super) fn span(&self, id: SpanId) -> Option<&TrackedSpan> {
+        self.spans.get(&id)
+    }
+
+    /// Move the anchors of the tracked spans to account for the edits in `diff`. This must be
+    /// called with every diff applied to the tree.
+    pub(super) fn apply_diff(&mut self, diff: &amp::RootDiff) {
+        if self.spans.is_empty() {
+            return;
+        }
+        for diffs in diff.props.values() {
+            for diff in diffs.values() {
+                self.apply_nested_diff(diff);
+            }
+        }
+    }
+
+    fn apply_nested_diff(&mut self, diff: &amp::Diff) {
+        match diff {
+            amp::Diff::Map(amp::MapDiff { props, .. })
+            | amp::Diff::Table(amp::TableDiff { props, .. }) => {
+                for diffs in props.values() {
+                    for diff in diffs.values() {
+                        self.apply_nested_diff(diff);
+                    }
+                }
+            }
+            amp::Diff::List(amp::ListDiff { object_id, edits })
+            | amp::Diff::Text(amp::TextDiff { object_id, edits }) => {
+                for edit in edits {
+                    for span in self.spans.values_mut() {
+                        if &span.object_id == object_id {
+                            span.start.apply_edit(edit);
+                            span.end.apply_edit(edit);
+                        }
+                    }
+                    match edit {
+                        amp::DiffEdit::SingleElementInsert { value, .. }
+                        | amp::DiffEdit::Update { value, .. } => self.apply_nested_diff(value),
+                        amp::DiffEdit::MultiElementInsert(_) | amp::DiffEdit::Remove { .. } => {}
+                    }
+                }
+            }
+            amp::Diff::Value(_) | amp::Diff::Cursor(_) => {}
+        }
+    }
+}
+
+impl Anchor {
+    fn apply_edit(&mut self, edit: &amp::DiffEdit) {
+        match edit {
+            amp::DiffEdit::SingleElementInsert { index, elem_id, .. } => {
+                self.insert(*index as usize, elem_id, 1)
+            }
+            amp::DiffEdit::MultiElementInsert(amp::MultiElementInsert {
+                index,
+                elem_id,
+                values,
+            }) => self.insert(*index as usize, elem_id, values.len()),
+            amp::DiffEdit::Remove { index, count } => {
+                let (index, count) = (*index as usize, *count as usize);
+                self.position = self.position.map(|position| match position {
+                    Position::At(i) if i >= index + count => Position::At(i - count),
+                    Position::At(i) if i >= index => Position::Deleted(index),
+                    Position::Deleted(i) if i >= index + count => Position::Deleted(i - count),
+                    Position::Deleted(i) if i > index => Position::Deleted(index),
+                    position => position,
+                });
+            }
+            amp::DiffEdit::Update { .. } => {}
+        }
+    }
+
+    /// `count` elements, with consecutive IDs starting at `first_id`, were inserted at `index`
+    fn insert(&mut self, index: usize, first_id: &amp::ElementId, count: usize) {
+        self.position = match self.position {
+            None => first_id.as_opid().and_then(|first_id| {
+                (0..count as u64)
+                    .find(|i| first_id.delta(&self.elem_id, *i))
+                    .map(|i| Position::At(index + i as usize))
+            }),
+            Some(Position::At(i)) if index <= i => Some(Position::At(i + count)),
+            // elements inserted where a deleted element was are after the boundary if it stuck to
+            // the start of the deleted element
+            Some(Position::Deleted(i))
+                if index < i || (index == i && self.bias == Bias::Before) =>
+            {
+                Some(Position::Deleted(i + count))
+            }
+            position => position,
+        };
+    }
+
+    /// The index of this boundary in a sequence with `len` elements, given the index of its
+    /// element if the element is in the sequence
+    pub(super) fn boundary(&self, index_of_elem: Option<usize>, len: usize) -> Option<usize> {
+        match (index_of_elem, self.position) {
+            (Some(i), _) => Some(match self.bias {
+                Bias::Before => i,
+                Bias::After => i + 1,
+            }),
+            // the element has been deleted, either by a diff or by a local change which the
+            // backend has not acknowledged yet, so the boundary is where it was
+            (None, Some(Position::At(i))) | (None, Some(Position::Deleted(i))) => Some(i.min(len)),
+            (None, None) => None,
+        }
+    }
+}
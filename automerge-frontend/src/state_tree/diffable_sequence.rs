@@ -156,6 +156,13 @@ where
             value: SequenceValue::New(value),
         }
     }
+
+    fn new_at(opid: OpId, value: T) -> Self {
+        Self {
+            opid,
+            value: SequenceValue::New(value),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -302,17 +309,21 @@ where
                 }
                 amp::DiffEdit::SingleElementInsert {
                     index,
-                    elem_id: _,
+                    elem_id,
                     op_id,
                     value,
                 } => {
                     let node = T::construct(op_id, value);
+                    // the element was inserted by `elem_id`, which is not the op which set its
+                    // value if the value has been updated since
+                    let element = match elem_id.as_opid() {
+                        Some(elem_id) => SequenceElement::new_at(elem_id.clone(), node),
+                        None => SequenceElement::new(node),
+                    };
                     if (index as usize) == self.underlying.len() {
-                        self.underlying
-                            .push_back(Box::new(SequenceElement::new(node)));
+                        self.underlying.push_back(Box::new(element));
                     } else {
-                        self.underlying
-                            .insert(index as usize, Box::new(SequenceElement::new(node)));
+                        self.underlying.insert(index as usize, Box::new(element));
 
                         for changed_index in changed_indices.iter_mut() {
                             if *changed_index >= index as u64 {
@@ -413,6 +424,11 @@ where
             .clone()
     }
 
+    /// The index of the element which was inserted by `elem_id`
+    pub(crate) fn index_of(&self, elem_id: &OpId) -> Option<usize> {
+        self.underlying.iter().position(|e| &e.opid == elem_id)
+    }
+
     pub(crate) fn get(&self, index: usize) -> Option<(&OpId, &T)> {
         self.underlying.get(index).map(|e| (&e.opid, e.value.get()))
     }
@@ -3,16 +3,25 @@ use std::{collections::HashMap, convert::TryInto};
 use amp::{ElementId, SortedVec};
 use automerge_protocol as amp;
 use automerge_protocol::RootDiff;
+use cursors::{CursorState, Cursors};
 use diffable_sequence::DiffableSequence;
 use multivalue::NewValueRequest;
 use smol_str::SmolStr;
 
-use crate::{error, Path, PathElement, Primitive, RootRef, Value};
+use crate::{
+    error, value_ref::Resolving, ConflictResolver, Cursor, Path, PathElement, Primitive, RootRef,
+    Span, SpanId, Value,
+};
 
+mod changed_paths;
+mod conflicts;
+mod cursors;
 mod diffable_sequence;
 mod multivalue;
+mod patch_events;
 mod resolved_path;
 
+pub use conflicts::{Conflict, DocumentConflicts};
 pub use multivalue::{MultiGrapheme, MultiValue};
 pub(crate) use resolved_path::SetOrInsertPayload;
 pub use resolved_path::{ResolvedPath, ResolvedPathMut};
@@ -76,6 +85,7 @@ impl StateTree {
     }
 
     pub fn apply_diff(&mut self, diff: CheckedRootDiff) {
+        self.cursors.apply_diff(&diff.0);
         for (prop, prop_diff) in diff.0.props {
             let mut diff_iter = prop_diff.into_iter();
             match diff_iter.next() {
@@ -153,8 +163,99 @@ impl StateTree {
         Value::Map(m)
     }
 
-    pub(crate) fn value_ref(&self) -> RootRef {
-        RootRef::new(self)
+    pub(crate) fn conflicts(&self) -> DocumentConflicts<'_> {
+        DocumentConflicts::new(self)
+    }
+
+    pub(crate) fn value_ref<'a>(
+        &'a self,
+        resolver: Option<&'a dyn ConflictResolver>,
+    ) -> RootRef<'a> {
+        RootRef::new(self, resolver.map(Resolving::root))
+    }
+
+    /// Track the boundaries of `span` as diffs are applied. Spans whose cursors are in different
+    /// objects are not tracked.
+    pub(crate) fn track_span(&mut self, id: SpanId, span: &Span) {
+        if span.start.object != span.end.object {
+            return;
+        }
+        let (start_index, end_index) = match self.find_sequence(&span.start.object) {
+            Some(sequence) => (
+                sequence
+                    .find_element(&span.start.elem_opid)
+                    .and_then(|(i, _)| i),
+                sequence
+                    .find_element(&span.end.elem_opid)
+                    .and_then(|(i, _)| i),
+            ),
+            None => (None, None),
+        };
+        self.cursors.track_span(id, span, start_index, end_index);
+    }
+
+    pub(crate) fn untrack_span(&mut self, id: SpanId) -> bool {
+        self.cursors.untrack_span(id)
+    }
+
+    /// The indices of the elements currently in the span with ID `id`, or `None` if the span is
+    /// not tracked, its sequence is not in the tree, or one of its boundaries refers to an
+    /// element this tree has never had
+    pub(crate) fn resolve_span(&self, id: SpanId) -> Option<std::ops::Range<u32>> {
+        let span = self.cursors.span(id)?;
+        let sequence = self.find_sequence(&span.object_id)?;
+        let (start_index, len) = sequence.find_element(&span.start.elem_id)?;
+        let (end_index, _) = sequence.find_element(&span.end.elem_id)?;
+        let start = span.start.boundary(start_index, len)?;
+        let end = span.end.boundary(end_index, len)?;
+        Some(start as u32..end.max(start) as u32)
+    }
+
+    /// The index `cursor` points at: the index of its element if the element is in the tree,
+    /// otherwise the index in `cursor`, which the backend resolves to a neighbour of the deleted
+    /// element, limited to the elements there are now
+    pub(crate) fn resolve_cursor(&self, cursor: &Cursor) -> Option<u32> {
+        let sequence = self.find_sequence(&cursor.object)?;
+        match sequence.find_element(&cursor.elem_opid)? {
+            (Some(index), _) => Some(index as u32),
+            (None, 0) => None,
+            (None, len) => Some(cursor.index.min(len as u32 - 1)),
+        }
+    }
+
+    /// The list or text object with ID `object_id`, searching every value including those which
+    /// are not shown because of conflicts
+    fn find_sequence(&self, object_id: &amp::ObjectId) -> Option<&StateTreeComposite> {
+        let mut stack = self.root_props.values().collect::<Vec<_>>();
+        while let Some(multivalue) = stack.pop() {
+            for (_, value) in multivalue.iter() {
+                let composite = match value {
+                    StateTreeValue::Composite(composite) => composite,
+                    StateTreeValue::Leaf(_) => continue,
+                };
+                match composite {
+                    StateTreeComposite::List(StateTreeList {
+                        object_id: id,
+                        elements,
+                    }) => {
+                        if id == object_id {
+                            return Some(composite);
+                        }
+                        stack.extend(elements.iter());
+                    }
+                    StateTreeComposite::Text(StateTreeText { object_id: id, .. }) => {
+                        if id == object_id {
+                            return Some(composite);
+                        }
+                    }
+                    StateTreeComposite::Map(StateTreeMap { props, .. })
+                    | StateTreeComposite::Table(StateTreeTable { props, .. }) => {
+                        stack.extend(props.values())
+                    }
+                }
+            }
+        }
+        None
     }
 }
 
@@ -273,6 +374,20 @@ impl StateTreeComposite {
         }
     }
 
+    /// The index of the element inserted by `elem_id`, if it is still there, and the number of
+    /// elements, if this is a list or text object
+    fn find_element(&self, elem_id: &amp::OpId) -> Option<(Option<usize>, usize)> {
+        match self {
+            Self::List(StateTreeList { elements, .. }) => {
+                Some((elements.index_of(elem_id), elements.len()))
+            }
+            Self::Text(StateTreeText { graphemes, .. }) => {
+                Some((graphemes.index_of(elem_id), graphemes.len()))
+            }
+            Self::Map(_) | Self::Table(_) => None,
+        }
+    }
+
     fn obj_type(&self) -> amp::ObjType {
         match self {
             Self::Map(..) => amp::ObjType::Map,
@@ -844,37 +959,3 @@ impl StateTreeList {
 pub fn random_op_id() -> amp::OpId {
     amp::OpId::new(1, &amp::ActorId::random())
 }
-
-#[derive(Clone, Debug, PartialEq)]
-struct CursorState {
-    referring_object_id: amp::ObjectId,
-    referring_key: amp::Key,
-    referred_object_id: amp::ObjectId,
-    referred_opid: amp::OpId,
-    index: usize,
-}
-
-#[derive(Debug, PartialEq, Clone)]
-struct Cursors(HashMap<amp::ObjectId, Vec<CursorState>>);
-
-impl Cursors {
-    fn new() -> Cursors {
-        Cursors(HashMap::new())
-    }
-
-    fn new_from(cursor: CursorState) -> Cursors {
-        Cursors(maplit::hashmap! {
-            cursor.referred_object_id.clone() => vec![cursor],
-        })
-    }
-
-    fn extend(&mut self, other: Cursors) {
-        for (k, v) in other.0 {
-            if let Some(c1) = self.0.get_mut(&k) {
-                c1.extend(v)
-            } else {
-                self.0.insert(k, v);
-            }
-        }
-    }
-}
@@ -145,7 +145,7 @@ impl MultiValue {
         }
     }
 
-    fn get(&self, opid: &amp::OpId) -> Option<&StateTreeValue> {
+    pub(crate) fn get(&self, opid: &amp::OpId) -> Option<&StateTreeValue> {
         if opid == &self.winning_value.0 {
             Some(&self.winning_value.1)
         } else {
@@ -188,11 +188,22 @@ impl MultiValue {
         self.winning_value.0.clone()
     }
 
-    fn iter(&self) -> impl std::iter::Iterator<Item = (&amp::OpId, &StateTreeValue)> {
+    pub(super) fn iter(&self) -> impl std::iter::Iterator<Item = (&amp::OpId, &StateTreeValue)> {
         std::iter::once((&(self.winning_value).0, &(self.winning_value.1)))
             .chain(self.conflicts.iter())
     }
 
+    pub(crate) fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+
+    /// Every value, sorted by the ID of the op which set it
+    pub(crate) fn sorted_values(&self) -> Vec<(&amp::OpId, &StateTreeValue)> {
+        let mut values = self.iter().collect::<Vec<_>>();
+        values.sort_by_key(|(opid, _)| *opid);
+        values
+    }
+
     pub(super) fn realise_values(&self) -> std::collections::HashMap<amp::OpId, Value> {
         self.iter()
             .map(|(opid, v)| (opid.clone(), v.realise_value()))
@@ -273,6 +284,14 @@ impl MultiValue {
         None
     }
 
+    /// The winning value, placed at a new location in the document by the move op `opid`
+    pub(super) fn moved(self, opid: amp::OpId) -> MultiValue {
+        MultiValue {
+            winning_value: (opid, self.winning_value.1),
+            conflicts: HashMap::new(),
+        }
+    }
+
     pub(super) fn only_for_opid(&self, opid: amp::OpId) -> Option<MultiValue> {
         if opid == self.winning_value.0 {
             Some(MultiValue {
@@ -287,6 +306,22 @@ impl MultiValue {
         }
     }
 
+    /// This value with only the value set by `winner`, and the IDs of the other values, if
+    /// `winner` set one of the values
+    pub(super) fn keep_only(
+        &self,
+        winner: &amp::OpId,
+    ) -> Option<(MultiValue, SortedVec<amp::OpId>)> {
+        let kept = self.only_for_opid(winner.clone())?;
+        let losers = self
+            .iter()
+            .map(|(opid, _)| opid)
+            .filter(|opid| *opid != winner)
+            .cloned()
+            .collect();
+        Some((kept, losers))
+    }
+
     pub(super) fn add_values_from(&mut self, other: MultiValue) {
         for (opid, value) in other.iter() {
             match opid.cmp(&self.winning_value.0) {
@@ -487,6 +522,33 @@ impl MultiGrapheme {
         }
     }
 
+    /// This grapheme with only the value set by `winner`, and the IDs of the other values, if
+    /// `winner` set one of the values
+    pub(super) fn keep_only(
+        &self,
+        winner: &amp::OpId,
+    ) -> Option<(MultiGrapheme, SortedVec<amp::OpId>)> {
+        let kept = self.only_for_opid(winner.clone())?;
+        let losers = self
+            .iter()
+            .map(|(opid, _)| opid)
+            .filter(|opid| *opid != winner)
+            .cloned()
+            .collect();
+        Some((kept, losers))
+    }
+
+    pub(super) fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+
+    /// Every value, sorted by the ID of the op which set it
+    pub(super) fn sorted_values(&self) -> Vec<(&amp::OpId, &SmolStr)> {
+        let mut values = self.iter().collect::<Vec<_>>();
+        values.sort_by_key(|(opid, _)| *opid);
+        values
+    }
+
     pub(super) fn add_values_from(&mut self, other: MultiGrapheme) {
         for (opid, value) in other.iter() {
             match opid.cmp(&self.winning_value.0) {
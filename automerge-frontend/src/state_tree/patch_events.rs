@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+
+use automerge_protocol as amp;
+use smol_str::SmolStr;
+
+use super::{MultiValue, StateTree, StateTreeComposite, StateTreeValue};
+use crate::{PatchEvent, Path, Primitive, Value};
+
+/// What [`StateTree::patch_events`] needs to know about the tree before a patch was applied.
+///
+/// This only covers the keys and elements the patch touches, so taking it is proportional to the
+/// size of the patch rather than the size of the document.
+#[derive(Debug, Default)]
+pub(crate) struct PatchEventsBefore {
+    root: BeforeProps,
+}
+
+type BeforeProps = HashMap<SmolStr, BeforeMultiValue>;
+
+/// The parts of a `MultiValue` which a diff touches
+#[derive(Debug)]
+struct BeforeMultiValue {
+    default_opid: amp::OpId,
+    values: HashMap<amp::OpId, BeforeValue>,
+}
+
+#[derive(Debug)]
+enum BeforeValue {
+    Counter(i64),
+    Map(BeforeProps),
+    List {
+        len: usize,
+        /// The elements, by their index before the diff, which the diff updates
+        elements: HashMap<usize, BeforeMultiValue>,
+    },
+    Text {
+        len: usize,
+    },
+    /// A value which the diff replaces rather than updates
+    Other,
+}
+
+impl StateTree {
+    /// Record what [`StateTree::patch_events`] needs to know about this tree, which must be
+    /// called before `diff` is applied.
+    pub(crate) fn patch_events_before(&self, diff: &amp::RootDiff) -> PatchEventsBefore {
+        PatchEventsBefore {
+            root: props_before(&self.root_props, &diff.props),
+        }
+    }
+
+    /// The events which describe applying `diff` to the tree `before` was taken from, producing
+    /// this tree.
+    ///
+    /// This must be called after the diff has been applied. Values are taken from this tree, so
+    /// an event never reports a value which a later part of the same diff overwrites. `before` is
+    /// only used to tell which objects already existed, and to work out by how much a counter was
+    /// incremented.
+    pub(crate) fn patch_events(
+        &self,
+        before: &PatchEventsBefore,
+        diff: &amp::RootDiff,
+    ) -> Vec<PatchEvent> {
+        let mut events = Vec::new();
+        map_events(
+            &before.root,
+            &self.root_props,
+            &diff.props,
+            &Path::root(),
+            &mut events,
+        );
+        events
+    }
+}
+
+fn props_before(
+    props: &HashMap<SmolStr, MultiValue>,
+    prop_diffs: &HashMap<SmolStr, HashMap<amp::OpId, amp::Diff>>,
+) -> BeforeProps {
+    prop_diffs
+        .iter()
+        .filter_map(|(key, diffs)| {
+            props
+                .get(key)
+                .map(|multivalue| (key.clone(), multivalue_before(multivalue, diffs.iter())))
+        })
+        .collect()
+}
+
+fn multivalue_before<'a, I>(multivalue: &MultiValue, diffs: I) -> BeforeMultiValue
+where
+    I: Iterator<Item = (&'a amp::OpId, &'a amp::Diff)>,
+{
+    let values = diffs
+        .filter_map(|(opid, diff)| {
+            multivalue
+                .get(opid)
+                .map(|value| (opid.clone(), value_before(value, diff)))
+        })
+        .collect();
+    BeforeMultiValue {
+        default_opid: multivalue.default_opid(),
+        values,
+    }
+}
+
+fn value_before(value: &StateTreeValue, diff: &amp::Diff) -> BeforeValue {
+    match (value, diff) {
+        (StateTreeValue::Leaf(Primitive::Counter(old)), _) => BeforeValue::Counter(*old),
+        (
+            StateTreeValue::Composite(StateTreeComposite::Map(map)),
+            amp::Diff::Map(amp::MapDiff { props, .. }),
+        ) => BeforeValue::Map(props_before(&map.props, props)),
+        (
+            StateTreeValue::Composite(StateTreeComposite::Table(table)),
+            amp::Diff::Table(amp::TableDiff { props, .. }),
+        ) => BeforeValue::Map(props_before(&table.props, props)),
+        (
+            StateTreeValue::Composite(StateTreeComposite::List(list)),
+            amp::Diff::List(amp::ListDiff { edits, .. }),
+        ) => {
+            let len = list.elements.len();
+            let mut elements = HashMap::new();
+            for slot in replay_edits(len, edits) {
+                if let Some(index) = slot.original_index {
+                    if let Some((_, multivalue)) = list.elements.get(index) {
+                        if !slot.updates.is_empty() {
+                            elements.insert(
+                                index,
+                                multivalue_before(multivalue, slot.updates.into_iter()),
+                            );
+                        }
+                    }
+                }
+            }
+            BeforeValue::List { len, elements }
+        }
+        (StateTreeValue::Composite(StateTreeComposite::Text(text)), amp::Diff::Text(_)) => {
+            BeforeValue::Text {
+                len: text.graphemes.len(),
+            }
+        }
+        _ => BeforeValue::Other,
+    }
+}
+
+fn map_events(
+    before: &BeforeProps,
+    after: &HashMap<SmolStr, MultiValue>,
+    prop_diffs: &HashMap<SmolStr, HashMap<amp::OpId, amp::Diff>>,
+    path: &Path,
+    events: &mut Vec<PatchEvent>,
+) {
+    // sort the keys so that the same patch always produces the same events
+    let mut keys = prop_diffs.keys().collect::<Vec<_>>();
+    keys.sort();
+    for key in keys {
+        let path = path.clone().key(key.clone());
+        match after.get(key) {
+            Some(after) => {
+                let diffs = prop_diffs[key].iter().collect::<Vec<_>>();
+                value_events(before.get(key), after, &diffs, path, events);
+            }
+            None => events.push(PatchEvent::DeleteKey { path }),
+        }
+    }
+}
+
+/// Events for the value at `path`, given the diffs for each of the values (conflicting or not)
+/// it holds
+fn value_events(
+    before: Option<&BeforeMultiValue>,
+    after: &MultiValue,
+    diffs: &[(&amp::OpId, &amp::Diff)],
+    path: Path,
+    events: &mut Vec<PatchEvent>,
+) {
+    let winner = after.default_opid();
+    let winning_diff = diffs
+        .iter()
+        .find(|(opid, _)| **opid == winner)
+        .map(|(_, diff)| *diff);
+    let winning_diff = match winning_diff {
+        Some(diff) => diff,
+        None => {
+            // only the losing values of a conflict changed, which is only visible if the winner
+            // is not the value which won before
+            if before.map(|before| &before.default_opid) != Some(&winner) {
+                events.push(PatchEvent::Put {
+                    path,
+                    value: after.default_value(),
+                });
+            }
+            return;
+        }
+    };
+
+    match (
+        before.and_then(|before| before.values.get(&winner)),
+        winning_diff,
+        after.default_statetree_value(),
+    ) {
+        (Some(BeforeValue::Counter(old)), amp::Diff::Value(amp::ScalarValue::Counter(new)), _) => {
+            if new != old {
+                events.push(PatchEvent::Increment {
+                    path,
+                    by: new - old,
+                })
+            }
+        }
+        (Some(before), _, StateTreeValue::Composite(after)) => {
+            composite_events(before, after, winning_diff, &path, events)
+        }
+        _ => events.push(PatchEvent::Put {
+            path,
+            value: after.default_value(),
+        }),
+    }
+}
+
+fn composite_events(
+    before: &BeforeValue,
+    after: &StateTreeComposite,
+    diff: &amp::Diff,
+    path: &Path,
+    events: &mut Vec<PatchEvent>,
+) {
+    match (before, after, diff) {
+        (
+            BeforeValue::Map(before),
+            StateTreeComposite::Map(after),
+            amp::Diff::Map(amp::MapDiff { props, .. }),
+        ) => map_events(before, &after.props, props, path, events),
+        (
+            BeforeValue::Map(before),
+            StateTreeComposite::Table(after),
+            amp::Diff::Table(amp::TableDiff { props, .. }),
+        ) => map_events(before, &after.props, props, path, events),
+        (
+            BeforeValue::List { len, elements },
+            StateTreeComposite::List(after),
+            amp::Diff::List(amp::ListDiff { edits, .. }),
+        ) => {
+            let slots = sequence_events(*len, edits, path, events);
+            for (index, slot) in slots.into_iter().enumerate() {
+                if slot.updates.is_empty() {
+                    continue;
+                }
+                if let Some((_, after)) = after.elements.get(index) {
+                    let before = slot.original_index.and_then(|i| elements.get(&i));
+                    let path = path.clone().index(index as u32);
+                    value_events(before, after, &slot.updates, path, events);
+                }
+            }
+        }
+        (
+            BeforeValue::Text { len },
+            StateTreeComposite::Text(after),
+            amp::Diff::Text(amp::TextDiff { edits, .. }),
+        ) => {
+            let slots = sequence_events(*len, edits, path, events);
+            for (index, slot) in slots.into_iter().enumerate() {
+                if slot.updates.is_empty() {
+                    continue;
+                }
+                if let Ok((_, grapheme)) = after.elem_at(index) {
+                    events.push(PatchEvent::Put {
+                        path: path.clone().index(index as u32),
+                        value: Value::Primitive(Primitive::Str(grapheme.clone())),
+                    });
+                }
+            }
+        }
+        _ => events.push(PatchEvent::Put {
+            path: path.clone(),
+            value: after.realise_value(),
+        }),
+    }
+}
+
+/// An element of a sequence while the edits of a diff are being replayed
+#[derive(Default)]
+struct Slot<'a> {
+    /// The index of this element before the diff, if it existed then
+    original_index: Option<usize>,
+    updates: Vec<(&'a amp::OpId, &'a amp::Diff)>,
+}
+
+/// The elements of a sequence of `original_len` elements after applying `edits`, along with any
+/// updates made to them
+fn replay_edits(original_len: usize, edits: &[amp::DiffEdit]) -> Vec<Slot<'_>> {
+    let mut slots = Vec::new();
+    replay_edits_with(original_len, edits, &mut slots, |_| {});
+    slots
+}
+
+/// Push the insert and delete events for a sequence diff, returning the elements of the
+/// sequence after the diff along with any updates made to them.
+///
+/// Updates don't change the shape of the sequence so their events are left to the caller, which
+/// can produce them after every insert and delete, when the indices match the document after the
+/// patch.
+fn sequence_events<'a>(
+    original_len: usize,
+    edits: &'a [amp::DiffEdit],
+    path: &Path,
+    events: &mut Vec<PatchEvent>,
+) -> Vec<Slot<'a>> {
+    let mut slots = Vec::new();
+    replay_edits_with(original_len, edits, &mut slots, |edit| match edit {
+        amp::DiffEdit::SingleElementInsert { index, value, .. } => {
+            let value = StateTreeValue::new_from_diff(value.clone()).realise_value();
+            push_insert(path, *index as u32, vec![value], events);
+        }
+        amp::DiffEdit::MultiElementInsert(amp::MultiElementInsert { index, values, .. }) => {
+            let values = values
+                .iter()
+                .map(|value| {
+                    StateTreeValue::new_from_diff(amp::Diff::Value(value.clone())).realise_value()
+                })
+                .collect::<Vec<_>>();
+            push_insert(path, *index as u32, values, events);
+        }
+        amp::DiffEdit::Remove { index, count } => events.push(PatchEvent::Delete {
+            path: path.clone(),
+            index: *index as u32,
+            count: *count as u32,
+        }),
+        amp::DiffEdit::Update { .. } => {}
+    });
+    slots
+}
+
+/// Replay `edits` over `original_len` elements into `slots`, calling `on_edit` with each edit
+///
+/// This runs before the patch is checked, so edits whose indices are out of range are skipped
+/// here and left for applying the patch to reject.
+fn replay_edits_with<'a, F>(
+    original_len: usize,
+    edits: &'a [amp::DiffEdit],
+    slots: &mut Vec<Slot<'a>>,
+    mut on_edit: F,
+) where
+    F: FnMut(&'a amp::DiffEdit),
+{
+    slots.extend((0..original_len).map(|i| Slot {
+        original_index: Some(i),
+        updates: Vec::new(),
+    }));
+    for edit in edits {
+        match edit {
+            amp::DiffEdit::SingleElementInsert { index, .. } => {
+                let index = *index as usize;
+                if index > slots.len() {
+                    continue;
+                }
+                slots.insert(index, Slot::default());
+            }
+            amp::DiffEdit::MultiElementInsert(amp::MultiElementInsert {
+                index, values, ..
+            }) => {
+                let index = *index as usize;
+                if index > slots.len() {
+                    continue;
+                }
+                slots.splice(index..index, values.iter().map(|_| Slot::default()));
+            }
+            amp::DiffEdit::Remove { index, count } => {
+                let start = *index as usize;
+                if start > slots.len() {
+                    continue;
+                }
+                slots.drain(start..start.saturating_add(*count as usize).min(slots.len()));
+            }
+            amp::DiffEdit::Update {
+                index,
+                op_id,
+                value,
+            } => {
+                if let Some(slot) = slots.get_mut(*index as usize) {
+                    slot.updates.push((op_id, value));
+                }
+            }
+        }
+        on_edit(edit);
+    }
+}
+
+/// Push an insert event, extending the previous event if it was an insert which ended where this
+/// one starts
+fn push_insert(path: &Path, index: u32, mut values: Vec<Value>, events: &mut Vec<PatchEvent>) {
+    if let Some(PatchEvent::Insert {
+        path: last_path,
+        index: last_index,
+        values: last_values,
+    }) = events.last_mut()
+    {
+        if last_path == path && *last_index + last_values.len() as u32 == index {
+            last_values.append(&mut values);
+            return;
+        }
+    }
+    events.push(PatchEvent::Insert {
+        path: path.clone(),
+        index,
+        values,
+    });
+}
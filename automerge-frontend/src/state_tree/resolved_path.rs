@@ -12,7 +12,7 @@ use super::{
     random_op_id, LocalOperationResult, MultiGrapheme, MultiValue, NewValueRequest, StateTree,
     StateTreeComposite, StateTreeValue,
 };
-use crate::{error, Cursor, Primitive, Value};
+use crate::{error, Cursor, PathElement, Primitive, Value};
 
 pub enum ResolvedPath<'a> {
     Root(ResolvedRoot<'a>),
@@ -253,6 +253,88 @@ impl<'a> ResolvedPathMut<'a> {
     pub(super) fn new_character(c: &'a mut MultiGrapheme) -> ResolvedPathMut<'a> {
         ResolvedPathMut::Character(ResolvedCharMut { multivalue: c })
     }
+
+    pub(crate) fn default_value(&self) -> Value {
+        match self {
+            ResolvedPathMut::Map(maptarget) => maptarget.multivalue.default_value(),
+            ResolvedPathMut::Root(root) => root.root.value(),
+            ResolvedPathMut::Table(tabletarget) => tabletarget.multivalue.default_value(),
+            ResolvedPathMut::List(listtarget) => listtarget.multivalue.default_value(),
+            ResolvedPathMut::Text(texttarget) => texttarget.multivalue.default_value(),
+            ResolvedPathMut::Counter(countertarget) => countertarget.multivalue.default_value(),
+            ResolvedPathMut::Primitive(p) => p.multivalue.default_value(),
+            ResolvedPathMut::Character(ctarget) => Value::Primitive(Primitive::Str(
+                ctarget.multivalue.default_grapheme().clone(),
+            )),
+        }
+    }
+
+    /// A shorter lived copy of this resolved path, so that a child can be resolved without
+    /// giving up the parent
+    pub(crate) fn reborrow(&mut self) -> ResolvedPathMut<'_> {
+        match self {
+            ResolvedPathMut::Root(root) => ResolvedPathMut::Root(ResolvedRootMut {
+                root: &mut *root.root,
+            }),
+            ResolvedPathMut::Map(map) => ResolvedPathMut::Map(ResolvedMapMut {
+                object_id: map.object_id.clone(),
+                multivalue: &mut *map.multivalue,
+            }),
+            ResolvedPathMut::Table(table) => ResolvedPathMut::Table(ResolvedTableMut {
+                object_id: table.object_id.clone(),
+                multivalue: &mut *table.multivalue,
+            }),
+            ResolvedPathMut::List(list) => ResolvedPathMut::List(ResolvedListMut {
+                object_id: list.object_id.clone(),
+                multivalue: &mut *list.multivalue,
+            }),
+            ResolvedPathMut::Text(text) => ResolvedPathMut::Text(ResolvedTextMut {
+                object_id: text.object_id.clone(),
+                multivalue: &mut *text.multivalue,
+            }),
+            ResolvedPathMut::Character(c) => ResolvedPathMut::Character(ResolvedCharMut {
+                multivalue: &mut *c.multivalue,
+            }),
+            ResolvedPathMut::Counter(counter) => ResolvedPathMut::Counter(ResolvedCounterMut {
+                multivalue: &mut *counter.multivalue,
+                containing_object_id: counter.containing_object_id.clone(),
+                key_in_container: counter.key_in_container.clone(),
+            }),
+            ResolvedPathMut::Primitive(p) => ResolvedPathMut::Primitive(ResolvedPrimitiveMut {
+                multivalue: &mut *p.multivalue,
+            }),
+        }
+    }
+
+    /// Resolve the child of this object at `element`, without resolving the path to this object
+    /// again
+    pub(crate) fn into_child(self, element: &PathElement) -> Option<ResolvedPathMut<'a>> {
+        let multivalue = match self {
+            ResolvedPathMut::Root(root) => {
+                return match element {
+                    PathElement::Key(k) => root.root.root_props.get_mut(k)?.resolve_path_mut(
+                        Vec::new(),
+                        amp::ObjectId::Root,
+                        amp::Key::Map(k.clone()),
+                    ),
+                    PathElement::Index(_) => None,
+                }
+            }
+            ResolvedPathMut::Map(ResolvedMapMut { multivalue, .. })
+            | ResolvedPathMut::Table(ResolvedTableMut { multivalue, .. })
+            | ResolvedPathMut::List(ResolvedListMut { multivalue, .. })
+            | ResolvedPathMut::Text(ResolvedTextMut { multivalue, .. }) => multivalue,
+            ResolvedPathMut::Character(_)
+            | ResolvedPathMut::Counter(_)
+            | ResolvedPathMut::Primitive(_) => return None,
+        };
+        match multivalue.default_statetree_value_mut() {
+            StateTreeValue::Composite(composite) => {
+                composite.resolve_path_mut(vec![element.clone()])
+            }
+            StateTreeValue::Leaf(_) => None,
+        }
+    }
 }
 
 pub(crate) struct SetOrInsertPayload<'a, T> {
@@ -293,6 +375,28 @@ impl<'a> ResolvedRootMut<'a> {
         (old, LocalOperationResult { new_ops })
     }
 
+    /// Set `key` to `payload.value`, which has been removed from elsewhere in the document
+    pub(crate) fn set_key_moved(
+        &mut self,
+        key: SmolStr,
+        payload: SetOrInsertPayload<MultiValue>,
+    ) -> (Option<MultiValue>, LocalOperationResult) {
+        let op = amp::Op {
+            action: amp::OpType::Move(payload.value.default_opid()),
+            obj: amp::ObjectId::Root,
+            key: amp::Key::Map(key.clone()),
+            insert: false,
+            pred: self
+                .root
+                .get(&key)
+                .map(|mv| vec![mv.default_opid()].into())
+                .unwrap_or_default(),
+        };
+        let opid = amp::OpId::new(payload.start_op, payload.actor);
+        let old = self.root.root_props.insert(key, payload.value.moved(opid));
+        (old, LocalOperationResult { new_ops: vec![op] })
+    }
+
     pub(crate) fn delete_key(&mut self, key: &str) -> Option<(MultiValue, LocalOperationResult)> {
         let existing_value = self.root.get(key);
         let pred = existing_value
@@ -310,6 +414,18 @@ impl<'a> ResolvedRootMut<'a> {
         self.root.remove(key).map(|old| (old, op_result))
     }
 
+    /// Remove every value at `key` except the one set by `winner`
+    pub(crate) fn resolve_conflict(
+        &mut self,
+        key: &str,
+        winner: &amp::OpId,
+    ) -> Option<(MultiValue, LocalOperationResult)> {
+        let value = self.root.root_props.get_mut(key)?;
+        let (kept, losers) = value.keep_only(winner)?;
+        let op_result = remove_losers(amp::ObjectId::Root, key.into(), losers);
+        Some((std::mem::replace(value, kept), op_result))
+    }
+
     pub(crate) fn rollback_set(&mut self, key: SmolStr, value: Option<MultiValue>) {
         match value {
             Some(old) => {
@@ -399,6 +515,28 @@ impl<'a> ResolvedMapMut<'a> {
         (old, LocalOperationResult { new_ops })
     }
 
+    /// Set `key` to `payload.value`, which has been removed from elsewhere in the document
+    pub(crate) fn set_key_moved(
+        &mut self,
+        key: SmolStr,
+        payload: SetOrInsertPayload<MultiValue>,
+    ) -> (Option<MultiValue>, LocalOperationResult) {
+        let state_tree_map = match self.multivalue.default_statetree_value_mut() {
+            StateTreeValue::Composite(StateTreeComposite::Map(map)) => map,
+            _ => unreachable!(),
+        };
+        let op = amp::Op {
+            action: amp::OpType::Move(payload.value.default_opid()),
+            obj: state_tree_map.object_id.clone(),
+            key: amp::Key::Map(key.clone()),
+            insert: false,
+            pred: state_tree_map.pred_for_key(&key),
+        };
+        let opid = amp::OpId::new(payload.start_op, payload.actor);
+        let old = state_tree_map.props.insert(key, payload.value.moved(opid));
+        (old, LocalOperationResult { new_ops: vec![op] })
+    }
+
     pub(crate) fn delete_key(&mut self, key: &str) -> Option<(MultiValue, LocalOperationResult)> {
         let state_tree_map = match self.multivalue.default_statetree_value_mut() {
             StateTreeValue::Composite(StateTreeComposite::Map(map)) => map,
@@ -416,6 +554,23 @@ impl<'a> ResolvedMapMut<'a> {
         state_tree_map.props.remove(key).map(|old| (old, op_result))
     }
 
+    /// Remove every value at `key` except the one set by `winner`
+    pub(crate) fn resolve_conflict(
+        &mut self,
+        key: &str,
+        winner: &amp::OpId,
+    ) -> Option<(MultiValue, LocalOperationResult)> {
+        let state_tree_map = match self.multivalue.default_statetree_value_mut() {
+            StateTreeValue::Composite(StateTreeComposite::Map(map)) => map,
+            _ => unreachable!(),
+        };
+        let object_id = state_tree_map.object_id.clone();
+        let value = state_tree_map.props.get_mut(key)?;
+        let (kept, losers) = value.keep_only(winner)?;
+        let op_result = remove_losers(object_id, key.into(), losers);
+        Some((std::mem::replace(value, kept), op_result))
+    }
+
     pub(crate) fn rollback_set(&mut self, key: SmolStr, value: Option<MultiValue>) {
         let state_tree_map = match self.multivalue.default_statetree_value_mut() {
             StateTreeValue::Composite(StateTreeComposite::Map(map)) => map,
@@ -474,6 +629,30 @@ impl<'a> ResolvedTableMut<'a> {
         (old, LocalOperationResult { new_ops })
     }
 
+    /// Set `key` to `payload.value`, which has been removed from elsewhere in the document
+    pub(crate) fn set_key_moved(
+        &mut self,
+        key: SmolStr,
+        payload: SetOrInsertPayload<MultiValue>,
+    ) -> (Option<MultiValue>, LocalOperationResult) {
+        let state_tree_table = match self.multivalue.default_statetree_value_mut() {
+            StateTreeValue::Composite(StateTreeComposite::Table(table)) => table,
+            _ => unreachable!(),
+        };
+        let op = amp::Op {
+            action: amp::OpType::Move(payload.value.default_opid()),
+            obj: state_tree_table.object_id.clone(),
+            key: amp::Key::Map(key.clone()),
+            insert: false,
+            pred: state_tree_table.pred_for_key(&key),
+        };
+        let opid = amp::OpId::new(payload.start_op, payload.actor);
+        let old = state_tree_table
+            .props
+            .insert(key, payload.value.moved(opid));
+        (old, LocalOperationResult { new_ops: vec![op] })
+    }
+
     pub(crate) fn delete_key(&mut self, key: &str) -> Option<(MultiValue, LocalOperationResult)> {
         let state_tree_table = match self.multivalue.default_statetree_value_mut() {
             StateTreeValue::Composite(StateTreeComposite::Table(map)) => map,
@@ -494,6 +673,23 @@ impl<'a> ResolvedTableMut<'a> {
             .map(|old| (old, op_result))
     }
 
+    /// Remove every value at `key` except the one set by `winner`
+    pub(crate) fn resolve_conflict(
+        &mut self,
+        key: &str,
+        winner: &amp::OpId,
+    ) -> Option<(MultiValue, LocalOperationResult)> {
+        let state_tree_table = match self.multivalue.default_statetree_value_mut() {
+            StateTreeValue::Composite(StateTreeComposite::Table(map)) => map,
+            _ => unreachable!(),
+        };
+        let object_id = state_tree_table.object_id.clone();
+        let value = state_tree_table.props.get_mut(key)?;
+        let (kept, losers) = value.keep_only(winner)?;
+        let op_result = remove_losers(object_id, key.into(), losers);
+        Some((std::mem::replace(value, kept), op_result))
+    }
+
     pub(crate) fn rollback_set(&mut self, key: SmolStr, value: Option<MultiValue>) {
         let state_tree_map = match self.multivalue.default_statetree_value_mut() {
             StateTreeValue::Composite(StateTreeComposite::Table(map)) => map,
@@ -529,6 +725,13 @@ pub struct ResolvedTextMut<'a> {
 }
 
 impl<'a> ResolvedTextMut<'a> {
+    pub(crate) fn len(&self) -> usize {
+        match self.multivalue.default_statetree_value() {
+            StateTreeValue::Composite(StateTreeComposite::Text(text)) => text.graphemes.len(),
+            _ => unreachable!(),
+        }
+    }
+
     #[allow(dead_code)]
     pub(crate) fn insert(
         &mut self,
@@ -660,6 +863,23 @@ impl<'a> ResolvedTextMut<'a> {
         ))
     }
 
+    /// Remove every value of the element at `index` except the one set by `winner`
+    pub(crate) fn resolve_conflict(
+        &mut self,
+        index: u32,
+        winner: &amp::OpId,
+    ) -> Option<(MultiGrapheme, LocalOperationResult)> {
+        let state_tree_text = match self.multivalue.default_statetree_value_mut() {
+            StateTreeValue::Composite(StateTreeComposite::Text(text)) => text,
+            _ => unreachable!(),
+        };
+        let object_id = state_tree_text.object_id.clone();
+        let (elemid, value) = state_tree_text.graphemes.get_mut(index as usize)?;
+        let (kept, losers) = value.keep_only(winner)?;
+        let op_result = remove_losers(object_id, elemid.clone().into(), losers);
+        Some((std::mem::replace(value, kept), op_result))
+    }
+
     pub(crate) fn rollback_set(&mut self, index: usize, value: MultiGrapheme) {
         let state_tree_text = match self.multivalue.default_statetree_value_mut() {
             StateTreeValue::Composite(StateTreeComposite::Text(text)) => text,
@@ -717,6 +937,13 @@ pub struct ResolvedListMut<'a> {
 }
 
 impl<'a> ResolvedListMut<'a> {
+    pub(crate) fn len(&self) -> usize {
+        match self.multivalue.default_statetree_value() {
+            StateTreeValue::Composite(StateTreeComposite::List(list)) => list.elements.len(),
+            _ => unreachable!(),
+        }
+    }
+
     pub(crate) fn set(
         &mut self,
         index: u32,
@@ -774,7 +1001,7 @@ impl<'a> ResolvedListMut<'a> {
     }
 
     pub(crate) fn insert_many<I>(
-        &'a mut self,
+        &mut self,
         index: u32,
         payload: SetOrInsertPayload<I>,
     ) -> Result<LocalOperationResult, error::MissingIndexError>
@@ -818,6 +1045,37 @@ impl<'a> ResolvedListMut<'a> {
         })
     }
 
+    /// Insert `payload.value`, which has been removed from elsewhere in the document, before the
+    /// element at `index`
+    pub(crate) fn insert_moved(
+        &mut self,
+        index: u32,
+        payload: SetOrInsertPayload<MultiValue>,
+    ) -> Result<LocalOperationResult, error::MissingIndexError> {
+        let state_tree_list = match self.multivalue.default_statetree_value_mut() {
+            StateTreeValue::Composite(StateTreeComposite::List(list)) => list,
+            _ => unreachable!(),
+        };
+        let current_elemid = match index {
+            0 => amp::ElementId::Head,
+            i => state_tree_list
+                .elem_at((i - 1).try_into().unwrap())?
+                .0
+                .clone()
+                .into(),
+        };
+        let op = amp::Op {
+            action: amp::OpType::Move(payload.value.default_opid()),
+            obj: state_tree_list.object_id.clone(),
+            key: current_elemid.into(),
+            insert: true,
+            pred: SortedVec::new(),
+        };
+        let opid = amp::OpId::new(payload.start_op, payload.actor);
+        state_tree_list.insert(index as usize, payload.value.moved(opid))?;
+        Ok(LocalOperationResult { new_ops: vec![op] })
+    }
+
     pub(crate) fn remove(
         &mut self,
         index: u32,
@@ -844,6 +1102,23 @@ impl<'a> ResolvedListMut<'a> {
         ))
     }
 
+    /// Remove every value of the element at `index` except the one set by `winner`
+    pub(crate) fn resolve_conflict(
+        &mut self,
+        index: u32,
+        winner: &amp::OpId,
+    ) -> Option<(MultiValue, LocalOperationResult)> {
+        let state_tree_list = match self.multivalue.default_statetree_value_mut() {
+            StateTreeValue::Composite(StateTreeComposite::List(list)) => list,
+            _ => unreachable!(),
+        };
+        let object_id = state_tree_list.object_id.clone();
+        let (elemid, value) = state_tree_list.elements.get_mut(index as usize)?;
+        let (kept, losers) = value.keep_only(winner)?;
+        let op_result = remove_losers(object_id, elemid.clone().into(), losers);
+        Some((std::mem::replace(value, kept), op_result))
+    }
+
     pub(crate) fn rollback_set(&mut self, index: usize, value: MultiValue) {
         let state_tree_list = match self.multivalue.default_statetree_value_mut() {
             StateTreeValue::Composite(StateTreeComposite::List(list)) => list,
@@ -951,7 +1226,14 @@ fn condense_insert_ops(ops: Vec<amp::Op>) -> Vec<amp::Op> {
         };
 
         for v in op_iter {
-            match (cur_multiset_start, prim_from_op_action(&v.action)) {
+            // only inserts of primitives can be condensed, not the ops which set the contents of
+            // an inserted map or table
+            let scalar = if v.insert {
+                prim_from_op_action(&v.action)
+            } else {
+                None
+            };
+            match (cur_multiset_start, scalar) {
                 (None, None) => {
                     // there is no multiset in progress & the current op
                     // could not be part of a multiset
@@ -981,7 +1263,7 @@ fn condense_insert_ops(ops: Vec<amp::Op>) -> Vec<amp::Op> {
                     cur_multiset_start = None;
                     new_ops.push(v);
                 }
-                (Some(typ), Some(scalar)) => match typ == discriminant(&scalar) {
+                (Some(typ), Some(scalar)) => match typ == discriminant(&scalar) && v.obj == obj {
                     // there is a multiset in progress & the current op could be part of it
                     true => cur_prim_vals.push(scalar),
                     false => {
@@ -1038,3 +1320,26 @@ fn prim_from_op_action(action: &amp::OpType) -> Option<amp::ScalarValue> {
         _ => None,
     }
 }
+
+/// The op which removes the values set by `losers` from `key` of `obj`, so that only the winner of
+/// the conflict there is left
+fn remove_losers(
+    obj: amp::ObjectId,
+    key: amp::Key,
+    losers: SortedVec<amp::OpId>,
+) -> LocalOperationResult {
+    if losers.is_empty() {
+        return LocalOperationResult {
+            new_ops: Vec::new(),
+        };
+    }
+    LocalOperationResult {
+        new_ops: vec![amp::Op {
+            action: amp::OpType::Del(NonZeroU32::new(1).unwrap()),
+            obj,
+            key,
+            insert: false,
+            pred: losers,
+        }],
+    }
+}
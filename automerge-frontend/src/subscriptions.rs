@@ -0,0 +1,68 @@
+use std::fmt::Debug;
+
+use crate::{Path, Value};
+
+/// Identifies a subscription created by [`crate::Frontend::subscribe`] so that it can be removed
+/// again with [`crate::Frontend::unsubscribe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+pub(crate) type Callback = Box<dyn FnMut(Option<&Value>)>;
+
+struct Subscription {
+    id: SubscriptionId,
+    path: Path,
+    callback: Callback,
+}
+
+/// The subscriptions of a frontend, in the order they were created.
+#[derive(Default)]
+pub(crate) struct Subscriptions {
+    next_id: u64,
+    subscriptions: Vec<Subscription>,
+}
+
+impl Debug for Subscriptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.subscriptions.iter().map(|s| (s.id, &s.path)))
+            .finish()
+    }
+}
+
+impl Subscriptions {
+    pub(crate) fn add(&mut self, path: Path, callback: Callback) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        self.subscriptions.push(Subscription { id, path, callback });
+        id
+    }
+
+    pub(crate) fn remove(&mut self, id: SubscriptionId) -> bool {
+        let len = self.subscriptions.len();
+        self.subscriptions.retain(|s| s.id != id);
+        self.subscriptions.len() != len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+
+    /// Call every subscription whose subtree overlaps one of `changed_paths`, that is the
+    /// subscribed path is either within a changed subtree or contains a changed path. Each
+    /// callback is called at most once and is passed the value now at its path.
+    pub(crate) fn notify<F>(&mut self, changed_paths: &[Path], get_value: F)
+    where
+        F: Fn(&Path) -> Option<Value>,
+    {
+        for subscription in &mut self.subscriptions {
+            let changed = changed_paths.iter().any(|changed| {
+                changed.starts_with(&subscription.path) || subscription.path.starts_with(changed)
+            });
+            if changed {
+                let value = get_value(&subscription.path);
+                (subscription.callback)(value.as_ref());
+            }
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ops::{Bound, RangeBounds},
+};
+
+use smol_str::SmolStr;
+
+use crate::{path::PathElement, value_ref::compare_primitives, Path, Primitive, Value};
+
+/// A secondary index of the rows of a table by the value of one of their columns, created with
+/// [`Frontend::add_table_index`](crate::Frontend::add_table_index).
+///
+/// Values are ordered as in [`TableRef::sort_by_columns`](crate::value_ref::TableRef::sort_by_columns),
+/// so numbers are compared by value and looking up `Int(3)` also finds rows where the column is
+/// `Uint(3)` or `F64(3.0)`. Rows where the column is missing, is not a primitive, or is NaN are
+/// not indexed.
+#[derive(Debug, Clone, Default)]
+pub struct TableIndex {
+    by_value: BTreeMap<IndexKey, BTreeSet<SmolStr>>,
+    by_row: HashMap<SmolStr, Primitive>,
+}
+
+impl TableIndex {
+    /// The IDs of the rows where the column is `value`, in order
+    pub fn get(&self, value: &Primitive) -> impl Iterator<Item = &SmolStr> {
+        self.by_value
+            .get(&IndexKey(value.clone()))
+            .into_iter()
+            .flatten()
+    }
+
+    /// The IDs of the rows where the column is within `range`, ordered by the value of the
+    /// column and then by ID
+    pub fn range<R>(&self, range: R) -> impl Iterator<Item = &SmolStr>
+    where
+        R: RangeBounds<Primitive>,
+    {
+        let start = key_bound(range.start_bound());
+        let end = key_bound(range.end_bound());
+        // `BTreeMap::range` panics for these rather than returning nothing
+        let empty = match (&start, &end) {
+            (Bound::Included(s), Bound::Included(e)) | (Bound::Included(s), Bound::Excluded(e)) => {
+                s > e
+            }
+            (Bound::Excluded(s), Bound::Included(e)) => s > e,
+            (Bound::Excluded(s), Bound::Excluded(e)) => s >= e,
+            _ => false,
+        };
+        let rows = if empty {
+            None
+        } else {
+            Some(self.by_value.range((start, end)))
+        };
+        rows.into_iter().flatten().flat_map(|(_, rows)| rows)
+    }
+
+    /// The value of the column in the row with ID `row`, if the row is indexed
+    pub fn value_of(&self, row: &str) -> Option<&Primitive> {
+        self.by_row.get(row)
+    }
+
+    /// The number of rows in the index
+    pub fn len(&self) -> usize {
+        self.by_row.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_row.is_empty()
+    }
+
+    /// Index `row` by `value`, replacing its previous entry
+    fn set_row(&mut self, row: &SmolStr, value: Option<Primitive>) {
+        if let Some(old) = self.by_row.remove(row) {
+            let key = IndexKey(old);
+            if let Some(rows) = self.by_value.get_mut(&key) {
+                rows.remove(row);
+                if rows.is_empty() {
+                    self.by_value.remove(&key);
+                }
+            }
+        }
+        let value = match value {
+            Some(value) => value,
+            None => return,
+        };
+        if matches!(value, Primitive::F64(f) if f.is_nan()) {
+            return;
+        }
+        self.by_value
+            .entry(IndexKey(value.clone()))
+            .or_default()
+            .insert(row.clone());
+        self.by_row.insert(row.clone(), value);
+    }
+}
+
+/// A primitive ordered by [`compare_primitives`]
+#[derive(Debug, Clone)]
+struct IndexKey(Primitive);
+
+impl Ord for IndexKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_primitives(&self.0, &other.0)
+    }
+}
+
+impl PartialOrd for IndexKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for IndexKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexKey {}
+
+fn key_bound(bound: Bound<&Primitive>) -> Bound<IndexKey> {
+    match bound {
+        Bound::Included(p) => Bound::Included(IndexKey(p.clone())),
+        Bound::Excluded(p) => Bound::Excluded(IndexKey(p.clone())),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+/// The value of `column` in `row`, if it can be indexed
+fn column_value(row: &Value, column: &str) -> Option<Primitive> {
+    match row {
+        Value::Map(props) => match props.get(column) {
+            Some(Value::Primitive(p)) => Some(p.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+struct Declared {
+    table: Path,
+    column: SmolStr,
+    index: TableIndex,
+}
+
+/// The table indexes of a frontend
+#[derive(Default)]
+pub(crate) struct TableIndexes {
+    indexes: Vec<Declared>,
+}
+
+impl std::fmt::Debug for TableIndexes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.indexes.iter().map(|i| (&i.table, &i.column)))
+            .finish()
+    }
+}
+
+impl TableIndexes {
+    /// Add an index of `column` of the table at `table`, returning false if there already was one
+    pub(crate) fn add<F>(&mut self, table: Path, column: SmolStr, get_value: F) -> bool
+    where
+        F: Fn(&Path) -> Option<Value>,
+    {
+        if self.get(&table, &column).is_some() {
+            return false;
+        }
+        let mut declared = Declared {
+            table,
+            column,
+            index: TableIndex::default(),
+        };
+        declared.rebuild(&get_value);
+        self.indexes.push(declared);
+        true
+    }
+
+    pub(crate) fn remove(&mut self, table: &Path, column: &str) -> bool {
+        let len = self.indexes.len();
+        self.indexes
+            .retain(|i| !(&i.table == table && i.column == column));
+        self.indexes.len() != len
+    }
+
+    pub(crate) fn get(&self, table: &Path, column: &str) -> Option<&TableIndex> {
+        self.indexes
+            .iter()
+            .find(|i| &i.table == table && i.column == column)
+            .map(|i| &i.index)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+
+    /// Update every index whose table overlaps one of `changed_paths`. Rows which changed are
+    /// reindexed, and the whole table is reindexed if it or one of its ancestors changed.
+    pub(crate) fn update<F>(&mut self, changed_paths: &[Path], get_value: F)
+    where
+        F: Fn(&Path) -> Option<Value>,
+    {
+        for declared in &mut self.indexes {
+            if changed_paths.iter().any(|p| declared.table.starts_with(p)) {
+                declared.rebuild(&get_value);
+                continue;
+            }
+            let rows = changed_paths
+                .iter()
+                .filter_map(|p| match p.child_of(&declared.table) {
+                    Some(PathElement::Key(row)) => Some(row),
+                    _ => None,
+                })
+                .collect::<BTreeSet<_>>();
+            for row in rows {
+                let value = get_value(&declared.table.clone().key(row.clone()))
+                    .and_then(|r| column_value(&r, &declared.column));
+                declared.index.set_row(row, value);
+            }
+        }
+    }
+
+    /// Reindex every table, e.g. because the values shown where there are conflicts changed
+    pub(crate) fn rebuild<F>(&mut self, get_value: F)
+    where
+        F: Fn(&Path) -> Option<Value>,
+    {
+        for declared in &mut self.indexes {
+            declared.rebuild(&get_value);
+        }
+    }
+}
+
+impl Declared {
+    fn rebuild<F>(&mut self, get_value: &F)
+    where
+        F: Fn(&Path) -> Option<Value>,
+    {
+        self.index = TableIndex::default();
+        if let Some(Value::Table(rows)) = get_value(&self.table) {
+            for (id, row) in &rows {
+                self.index.set_row(id, column_value(row, &self.column));
+            }
+        }
+    }
+}
@@ -0,0 +1,482 @@
+//! Handles for editing the objects in a document from within a change closure.
+//!
+//! A handle holds on to the object it edits, so each operation made through it and each child
+//! handle obtained from it only has to resolve one more element of the path, rather than the whole
+//! path from the root as [`LocalChange`](crate::LocalChange)s do.
+//!
+//! Handles obtained from a [`MutableDocument`] implemented outside this crate fall back to
+//! making each operation through [`MutableDocument::add_change`], resolving the path every time.
+//!
+//! ```
+//! # use automerge_frontend::{Frontend, InvalidChangeRequest, Path, Value};
+//! # let mut doc = Frontend::new();
+//! doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+//!     let mut root = d.root_mut();
+//!     root.set("todos", Value::List(Vec::new()))?;
+//!     let mut todos = root.list_mut("todos")?;
+//!     todos.push(Value::from("water the plants"))?;
+//!     todos.push(Value::from("feed the cat"))?;
+//!     Ok(())
+//! })
+//! .unwrap();
+//! assert_eq!(
+//!     doc.get_value(&Path::root().key("todos").index(1)),
+//!     Some(Value::from("feed the cat"))
+//! );
+//! ```
+
+use smol_str::SmolStr;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    error::InvalidChangeRequest, mutation::MutationLog, state_tree::ResolvedPathMut, LocalChange,
+    MutableDocument, Path, PathElement, Primitive, Value,
+};
+
+/// A handle to a map, a table or the root of the document.
+///
+/// Tables are edited by row ID like any other map.
+pub struct MapMut<'a> {
+    target: Target<'a>,
+    path: Path,
+}
+
+/// A handle to a list
+pub struct ListMut<'a> {
+    target: Target<'a>,
+    path: Path,
+}
+
+/// A handle to a text object
+pub struct TextMut<'a> {
+    target: Target<'a>,
+    path: Path,
+}
+
+/// The object a handle edits
+enum Target<'a> {
+    /// An object in the state tree of a change, along with the log the operations made to it are
+    /// recorded in
+    Resolved {
+        object: ResolvedPathMut<'a>,
+        log: &'a mut MutationLog,
+    },
+    /// A document which can only be changed by path
+    ByPath(&'a mut dyn MutableDocument),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Map,
+    Table,
+    List,
+    Text,
+    Other,
+}
+
+impl Kind {
+    fn of_resolved(object: &ResolvedPathMut) -> Kind {
+        match object {
+            ResolvedPathMut::Root(_) | ResolvedPathMut::Map(_) => Kind::Map,
+            ResolvedPathMut::Table(_) => Kind::Table,
+            ResolvedPathMut::List(_) => Kind::List,
+            ResolvedPathMut::Text(_) => Kind::Text,
+            ResolvedPathMut::Character(_)
+            | ResolvedPathMut::Counter(_)
+            | ResolvedPathMut::Primitive(_) => Kind::Other,
+        }
+    }
+
+    fn of_value(value: &Value) -> Kind {
+        match value {
+            Value::Map(_) => Kind::Map,
+            Value::Table(_) => Kind::Table,
+            Value::List(_) => Kind::List,
+            Value::Text(_) => Kind::Text,
+            Value::Primitive(_) => Kind::Other,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Kind::Map => "a map",
+            Kind::Table => "a table",
+            Kind::List => "a list",
+            Kind::Text => "a text object",
+            Kind::Other => "a primitive value",
+        }
+    }
+}
+
+impl<'a> Target<'a> {
+    fn value(&self, path: &Path) -> Value {
+        match self {
+            Target::Resolved { object, .. } => object.default_value(),
+            Target::ByPath(doc) => doc
+                .value_at_path(path)
+                .expect("the object a handle refers to can't be removed through the handle"),
+        }
+    }
+
+    fn len(&self, path: &Path) -> usize {
+        match self {
+            Target::Resolved {
+                object: ResolvedPathMut::List(list),
+                ..
+            } => list.len(),
+            Target::Resolved {
+                object: ResolvedPathMut::Text(text),
+                ..
+            } => text.len(),
+            Target::Resolved { .. } => unreachable!("length of a handle to a non sequence"),
+            Target::ByPath(doc) => match doc.value_at_path(path) {
+                Some(Value::List(elements)) => elements.len(),
+                Some(Value::Text(graphemes)) => graphemes.len(),
+                _ => unreachable!("length of a handle to a non sequence"),
+            },
+        }
+    }
+
+    fn set(&mut self, path: Path, value: Value) -> Result<(), InvalidChangeRequest> {
+        match self {
+            Target::Resolved { object, log } => log.set(object, path, value),
+            Target::ByPath(doc) => doc.add_change(LocalChange::set(path, value)),
+        }
+    }
+
+    fn delete(&mut self, path: Path) -> Result<(), InvalidChangeRequest> {
+        match self {
+            Target::Resolved { object, log } => log.delete(object, path),
+            Target::ByPath(doc) => doc.add_change(LocalChange::delete(path)),
+        }
+    }
+
+    fn insert(&mut self, path: Path, values: Vec<Value>) -> Result<(), InvalidChangeRequest> {
+        match self {
+            Target::Resolved { object, log } => log.insert(object, path, values.into_iter()),
+            Target::ByPath(doc) => doc.add_change(LocalChange::insert_many(path, values)),
+        }
+    }
+
+    /// Increment the counter at `element` of this object, which is at `path`
+    fn increment(
+        &mut self,
+        element: PathElement,
+        path: Path,
+        by: i64,
+    ) -> Result<(), InvalidChangeRequest> {
+        match self {
+            Target::Resolved { object, log } => match object.reborrow().into_child(&element) {
+                Some(counter) => log.increment(counter, path, by),
+                None => Err(InvalidChangeRequest::NoSuchPathError { path }),
+            },
+            Target::ByPath(doc) => doc.add_change(LocalChange::increment_by(path, by)),
+        }
+    }
+
+    /// The object at `path`, which is the child of this object at `element`, and its kind
+    fn into_child(self, element: &PathElement, path: &Path) -> Option<(Target<'a>, Kind)> {
+        match self {
+            Target::Resolved { object, log } => object.into_child(element).map(move |child| {
+                let kind = Kind::of_resolved(&child);
+                (Target::Resolved { object: child, log }, kind)
+            }),
+            Target::ByPath(doc) => {
+                let kind = Kind::of_value(&doc.value_at_path(path)?);
+                Some((Target::ByPath(doc), kind))
+            }
+        }
+    }
+}
+
+/// A handle to any value, used to follow a path one element at a time when the kind of each
+/// object along it isn't known in advance
+pub(crate) enum ObjectMut<'a> {
+    Map(MapMut<'a>),
+    List(ListMut<'a>),
+    Text(TextMut<'a>),
+    /// A counter, a primitive or a character of a text object, none of which have children
+    Leaf,
+}
+
+impl<'a> ObjectMut<'a> {
+    fn new(target: Target<'a>, path: Path, kind: Kind) -> ObjectMut<'a> {
+        match kind {
+            Kind::Map | Kind::Table => ObjectMut::Map(MapMut { target, path }),
+            Kind::List => ObjectMut::List(ListMut { target, path }),
+            Kind::Text => ObjectMut::Text(TextMut { target, path }),
+            Kind::Other => ObjectMut::Leaf,
+        }
+    }
+
+    /// The value at `element` in this object, if there is one
+    pub(crate) fn into_child(self, element: &PathElement) -> Option<ObjectMut<'a>> {
+        let (target, path) = match self {
+            ObjectMut::Map(MapMut { target, path })
+            | ObjectMut::List(ListMut { target, path })
+            | ObjectMut::Text(TextMut { target, path }) => (target, path),
+            ObjectMut::Leaf => return None,
+        };
+        let path = child_path(path, element);
+        let (target, kind) = target.into_child(element, &path)?;
+        Some(ObjectMut::new(target, path, kind))
+    }
+}
+
+impl<'a> From<MapMut<'a>> for ObjectMut<'a> {
+    fn from(map: MapMut<'a>) -> Self {
+        ObjectMut::Map(map)
+    }
+}
+
+fn child_path(path: Path, element: &PathElement) -> Path {
+    match element {
+        PathElement::Key(k) => path.key(k.clone()),
+        PathElement::Index(i) => path.index(*i),
+    }
+}
+
+/// Resolve the child of `target` at `element` and check that it is the expected kind of object
+fn child<'a>(
+    target: Target<'a>,
+    path: Path,
+    element: PathElement,
+    expected: Kind,
+) -> Result<(Target<'a>, Path), InvalidChangeRequest> {
+    let path = child_path(path, &element);
+    match target.into_child(&element, &path) {
+        Some((child, kind)) if kind == expected => Ok((child, path)),
+        Some(_) => Err(InvalidChangeRequest::UnexpectedObjectType {
+            path,
+            expected: expected.description(),
+        }),
+        None => Err(InvalidChangeRequest::NoSuchPathError { path }),
+    }
+}
+
+impl<'a> MapMut<'a> {
+    pub(crate) fn new(
+        object: ResolvedPathMut<'a>,
+        path: Path,
+        log: &'a mut MutationLog,
+    ) -> MapMut<'a> {
+        MapMut {
+            target: Target::Resolved { object, log },
+            path,
+        }
+    }
+
+    /// A handle to the root of `doc` which makes every operation through
+    /// [`MutableDocument::add_change`]
+    pub(crate) fn by_path(doc: &'a mut dyn MutableDocument) -> MapMut<'a> {
+        MapMut {
+            target: Target::ByPath(doc),
+            path: Path::root(),
+        }
+    }
+
+    /// The path to this object
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The current value of this object
+    pub fn value(&self) -> Value {
+        self.target.value(&self.path)
+    }
+
+    /// Set `key` to `value`
+    pub fn set<K, V>(&mut self, key: K, value: V) -> Result<(), InvalidChangeRequest>
+    where
+        K: Into<SmolStr>,
+        V: Into<Value>,
+    {
+        let path = self.path.clone().key(key);
+        self.target.set(path, value.into())
+    }
+
+    /// Delete `key`
+    pub fn delete<K: Into<SmolStr>>(&mut self, key: K) -> Result<(), InvalidChangeRequest> {
+        let path = self.path.clone().key(key);
+        self.target.delete(path)
+    }
+
+    /// Increment the counter at `key` by a (possibly negative) amount `by`
+    pub fn increment<K: Into<SmolStr>>(
+        &mut self,
+        key: K,
+        by: i64,
+    ) -> Result<(), InvalidChangeRequest> {
+        let key = key.into();
+        let path = self.path.clone().key(key.clone());
+        self.target.increment(PathElement::Key(key), path, by)
+    }
+
+    /// A handle to the map at `key`
+    pub fn map_mut<K: Into<SmolStr>>(self, key: K) -> Result<MapMut<'a>, InvalidChangeRequest> {
+        let element = PathElement::Key(key.into());
+        let (target, path) = child(self.target, self.path, element, Kind::Map)?;
+        Ok(MapMut { target, path })
+    }
+
+    /// A handle to the table at `key`
+    pub fn table_mut<K: Into<SmolStr>>(self, key: K) -> Result<MapMut<'a>, InvalidChangeRequest> {
+        let element = PathElement::Key(key.into());
+        let (target, path) = child(self.target, self.path, element, Kind::Table)?;
+        Ok(MapMut { target, path })
+    }
+
+    /// A handle to the list at `key`
+    pub fn list_mut<K: Into<SmolStr>>(self, key: K) -> Result<ListMut<'a>, InvalidChangeRequest> {
+        let element = PathElement::Key(key.into());
+        let (target, path) = child(self.target, self.path, element, Kind::List)?;
+        Ok(ListMut { target, path })
+    }
+
+    /// A handle to the text object at `key`
+    pub fn text_mut<K: Into<SmolStr>>(self, key: K) -> Result<TextMut<'a>, InvalidChangeRequest> {
+        let element = PathElement::Key(key.into());
+        let (target, path) = child(self.target, self.path, element, Kind::Text)?;
+        Ok(TextMut { target, path })
+    }
+}
+
+impl<'a> ListMut<'a> {
+    /// The path to this list
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The current value of this list
+    pub fn value(&self) -> Value {
+        self.target.value(&self.path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.target.len(&self.path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Set the element at `index` to `value`
+    pub fn set<V: Into<Value>>(
+        &mut self,
+        index: u32,
+        value: V,
+    ) -> Result<(), InvalidChangeRequest> {
+        let path = self.path.clone().index(index);
+        self.target.set(path, value.into())
+    }
+
+    /// Insert `value` before the element at `index`
+    pub fn insert<V: Into<Value>>(
+        &mut self,
+        index: u32,
+        value: V,
+    ) -> Result<(), InvalidChangeRequest> {
+        self.insert_many(index, vec![value.into()])
+    }
+
+    /// Insert `values` before the element at `index`
+    pub fn insert_many(
+        &mut self,
+        index: u32,
+        values: Vec<Value>,
+    ) -> Result<(), InvalidChangeRequest> {
+        let path = self.path.clone().index(index);
+        self.target.insert(path, values)
+    }
+
+    /// Append `value` to the end of the list
+    pub fn push<V: Into<Value>>(&mut self, value: V) -> Result<(), InvalidChangeRequest> {
+        self.insert(self.len() as u32, value)
+    }
+
+    /// Remove the element at `index`
+    pub fn remove(&mut self, index: u32) -> Result<(), InvalidChangeRequest> {
+        let path = self.path.clone().index(index);
+        self.target.delete(path)
+    }
+
+    /// A handle to the map at `index`
+    pub fn map_mut(self, index: u32) -> Result<MapMut<'a>, InvalidChangeRequest> {
+        let element = PathElement::Index(index);
+        let (target, path) = child(self.target, self.path, element, Kind::Map)?;
+        Ok(MapMut { target, path })
+    }
+
+    /// A handle to the list at `index`
+    pub fn list_mut(self, index: u32) -> Result<ListMut<'a>, InvalidChangeRequest> {
+        let element = PathElement::Index(index);
+        let (target, path) = child(self.target, self.path, element, Kind::List)?;
+        Ok(ListMut { target, path })
+    }
+
+    /// A handle to the text object at `index`
+    pub fn text_mut(self, index: u32) -> Result<TextMut<'a>, InvalidChangeRequest> {
+        let element = PathElement::Index(index);
+        let (target, path) = child(self.target, self.path, element, Kind::Text)?;
+        Ok(TextMut { target, path })
+    }
+}
+
+impl<'a> TextMut<'a> {
+    /// The path to this text object
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The current value of this text object
+    pub fn value(&self) -> Value {
+        self.target.value(&self.path)
+    }
+
+    /// The number of grapheme clusters in the text
+    pub fn len(&self) -> usize {
+        self.target.len(&self.path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Delete `delete` grapheme clusters starting at `index` and then insert the grapheme
+    /// clusters of `insert` at `index`
+    pub fn splice(
+        &mut self,
+        index: u32,
+        delete: u32,
+        insert: &str,
+    ) -> Result<(), InvalidChangeRequest> {
+        for _ in 0..delete {
+            let path = self.path.clone().index(index);
+            self.target.delete(path)?;
+        }
+        let graphemes = insert
+            .graphemes(true)
+            .map(|g| Value::Primitive(Primitive::Str(g.into())))
+            .collect::<Vec<_>>();
+        if graphemes.is_empty() {
+            return Ok(());
+        }
+        let path = self.path.clone().index(index);
+        self.target.insert(path, graphemes)
+    }
+
+    /// Insert `text` at `index`
+    pub fn insert(&mut self, index: u32, text: &str) -> Result<(), InvalidChangeRequest> {
+        self.splice(index, 0, text)
+    }
+
+    /// Append `text` to the end of the text object
+    pub fn push_str(&mut self, text: &str) -> Result<(), InvalidChangeRequest> {
+        self.splice(self.len() as u32, 0, text)
+    }
+
+    /// Remove the grapheme cluster at `index`
+    pub fn remove(&mut self, index: u32) -> Result<(), InvalidChangeRequest> {
+        self.splice(index, 1, "")
+    }
+}
@@ -10,9 +10,14 @@ pub use root::RootRef;
 pub use table::TableRef;
 pub use text::TextRef;
 
+pub(crate) use table::compare_primitives;
+
+use std::{borrow::Cow, fmt};
+
 use crate::{
-    state_tree::{StateTreeComposite, StateTreeValue},
-    Primitive, Value,
+    path::PathElement,
+    state_tree::{MultiValue, StateTreeComposite, StateTreeValue},
+    ConflictResolver, Path, Primitive, Resolution, Value,
 };
 
 /// A ValueRef represents a way to interact with the frontend's state lazily rather than creating
@@ -22,10 +27,13 @@ use crate::{
 ///
 /// A `ValueRef` can be obtained from the [`value_ref`](crate::Frontend::value_ref) method on a
 /// [`Frontend`](crate::Frontend).
+///
+/// Conflicts are resolved by the frontend's [`ConflictResolver`], if it has one. A primitive
+/// merged from conflicting values by the resolver is owned by the `ValueRef`.
 
 #[derive(Clone, Debug)]
 pub enum ValueRef<'a> {
-    Primitive(&'a Primitive),
+    Primitive(Cow<'a, Primitive>),
     Map(MapRef<'a>),
     Table(TableRef<'a>),
     List(ListRef<'a>),
@@ -33,18 +41,39 @@ pub enum ValueRef<'a> {
 }
 
 impl<'a> ValueRef<'a> {
-    pub(crate) fn new(stv: &'a StateTreeValue) -> Self {
+    pub(crate) fn new(stv: &'a StateTreeValue, resolving: Option<Resolving<'a>>) -> Self {
         match stv {
-            StateTreeValue::Leaf(p) => Self::Primitive(p),
-            StateTreeValue::Composite(StateTreeComposite::Map(m)) => Self::Map(MapRef::new(m)),
+            StateTreeValue::Leaf(p) => Self::Primitive(Cow::Borrowed(p)),
+            StateTreeValue::Composite(StateTreeComposite::Map(m)) => {
+                Self::Map(MapRef::new(m, resolving))
+            }
             StateTreeValue::Composite(StateTreeComposite::Table(t)) => {
-                Self::Table(TableRef::new(t))
+                Self::Table(TableRef::new(t, resolving))
+            }
+            StateTreeValue::Composite(StateTreeComposite::List(l)) => {
+                Self::List(ListRef::new(l, resolving))
             }
-            StateTreeValue::Composite(StateTreeComposite::List(l)) => Self::List(ListRef::new(l)),
             StateTreeValue::Composite(StateTreeComposite::Text(t)) => Self::Text(TextRef::new(t)),
         }
     }
 
+    /// The value at `elements` below this value
+    fn get_path(self, elements: &[PathElement]) -> Option<Value> {
+        let (first, rest) = match elements.split_first() {
+            Some(split) => split,
+            None => return Some(self.value()),
+        };
+        match (self, first) {
+            (ValueRef::Map(m), PathElement::Key(k)) => m.get(k)?.get_path(rest),
+            (ValueRef::Table(t), PathElement::Key(k)) => t.get(k)?.get_path(rest),
+            (ValueRef::List(l), PathElement::Index(i)) => l.get(*i as usize)?.get_path(rest),
+            (ValueRef::Text(t), PathElement::Index(i)) if rest.is_empty() => t
+                .get(*i as usize)
+                .map(|g| Value::Primitive(Primitive::Str(g.clone()))),
+            _ => None,
+        }
+    }
+
     pub fn map(&self) -> Option<&MapRef<'a>> {
         match self {
             Self::Map(m) => Some(m),
@@ -75,14 +104,14 @@ impl<'a> ValueRef<'a> {
 
     pub fn primitive(&self) -> Option<&Primitive> {
         match self {
-            Self::Primitive(p) => Some(p),
+            Self::Primitive(p) => Some(p.as_ref()),
             _ => None,
         }
     }
 
     pub fn value(&self) -> Value {
         match self {
-            ValueRef::Primitive(p) => Value::from((*p).clone()),
+            ValueRef::Primitive(p) => Value::from(p.clone().into_owned()),
             ValueRef::Map(m) => m.value(),
             ValueRef::Table(t) => t.value(),
             ValueRef::List(l) => l.value(),
@@ -90,3 +119,75 @@ impl<'a> ValueRef<'a> {
         }
     }
 }
+
+/// The conflict resolver used to read a document and the path of the value being read
+#[derive(Clone)]
+pub(crate) struct Resolving<'a> {
+    resolver: &'a dyn ConflictResolver,
+    path: Path,
+}
+
+impl<'a> fmt::Debug for Resolving<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resolving")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl<'a> Resolving<'a> {
+    pub(crate) fn root(resolver: &'a dyn ConflictResolver) -> Self {
+        Resolving {
+            resolver,
+            path: Path::root(),
+        }
+    }
+
+    fn child(&self, element: PathElement) -> Self {
+        let path = match element {
+            PathElement::Key(k) => self.path.clone().key(k),
+            PathElement::Index(i) => self.path.clone().index(i),
+        };
+        Resolving {
+            resolver: self.resolver,
+            path,
+        }
+    }
+}
+
+/// The value to show for `mv`, which is at `element` of the object read using `resolving`
+pub(crate) fn resolve<'a, F>(
+    mv: &'a MultiValue,
+    resolving: &Option<Resolving<'a>>,
+    element: F,
+) -> ValueRef<'a>
+where
+    F: FnOnce() -> PathElement,
+{
+    let resolving = match resolving {
+        Some(resolving) => resolving.child(element()),
+        None => return ValueRef::new(mv.default_statetree_value(), None),
+    };
+    if !mv.has_conflicts() {
+        return ValueRef::new(mv.default_statetree_value(), Some(resolving));
+    }
+    let candidates = mv
+        .sorted_values()
+        .into_iter()
+        .map(|(opid, v)| {
+            (
+                opid.clone(),
+                ValueRef::new(v, Some(resolving.clone())).value(),
+            )
+        })
+        .collect::<Vec<_>>();
+    match resolving.resolver.resolve(&resolving.path, &candidates) {
+        Resolution::Winner(opid) => {
+            let winner = mv
+                .get(&opid)
+                .unwrap_or_else(|| mv.default_statetree_value());
+            ValueRef::new(winner, Some(resolving))
+        }
+        Resolution::Merged(p) => ValueRef::Primitive(Cow::Owned(p)),
+    }
+}
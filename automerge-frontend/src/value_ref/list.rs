@@ -1,13 +1,19 @@
-use crate::{state_tree::StateTreeList, value_ref::ValueRef, Value};
+use crate::{
+    path::PathElement,
+    state_tree::StateTreeList,
+    value_ref::{resolve, Resolving, ValueRef},
+    Value,
+};
 
 #[derive(Clone, Debug)]
 pub struct ListRef<'a> {
     stl: &'a StateTreeList,
+    resolving: Option<Resolving<'a>>,
 }
 
 impl<'a> ListRef<'a> {
-    pub(crate) fn new(stl: &'a StateTreeList) -> Self {
-        Self { stl }
+    pub(crate) fn new(stl: &'a StateTreeList, resolving: Option<Resolving<'a>>) -> Self {
+        Self { stl, resolving }
     }
 
     pub fn len(&self) -> usize {
@@ -22,17 +28,22 @@ impl<'a> ListRef<'a> {
         self.stl
             .elements
             .get(index)
-            .map(|(_, mv)| ValueRef::new(mv.default_statetree_value()))
+            .map(|(_, mv)| resolve(mv, &self.resolving, || PathElement::Index(index as u32)))
     }
 
     pub fn iter(&self) -> impl Iterator<Item = ValueRef<'a>> {
+        let resolving = self.resolving.clone();
         self.stl
             .elements
             .iter()
-            .map(|mv| ValueRef::new(mv.default_statetree_value()))
+            .enumerate()
+            .map(move |(i, mv)| resolve(mv, &resolving, || PathElement::Index(i as u32)))
     }
 
     pub fn value(&self) -> Value {
+        if self.resolving.is_some() {
+            return Value::List(self.iter().map(|v| v.value()).collect());
+        }
         let mut v = Vec::new();
         for e in self.stl.elements.iter() {
             v.push(e.default_value())
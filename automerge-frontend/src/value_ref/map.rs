@@ -2,16 +2,22 @@ use std::collections::HashMap;
 
 use smol_str::SmolStr;
 
-use crate::{state_tree::StateTreeMap, value_ref::ValueRef, Value};
+use crate::{
+    path::PathElement,
+    state_tree::StateTreeMap,
+    value_ref::{resolve, Resolving, ValueRef},
+    Value,
+};
 
 #[derive(Clone, Debug)]
 pub struct MapRef<'a> {
     stm: &'a StateTreeMap,
+    resolving: Option<Resolving<'a>>,
 }
 
 impl<'a> MapRef<'a> {
-    pub(crate) fn new(stm: &'a StateTreeMap) -> Self {
-        Self { stm }
+    pub(crate) fn new(stm: &'a StateTreeMap, resolving: Option<Resolving<'a>>) -> Self {
+        Self { stm, resolving }
     }
 
     pub fn contains_key(&self, key: &str) -> bool {
@@ -30,7 +36,7 @@ impl<'a> MapRef<'a> {
         self.stm
             .props
             .get(key)
-            .map(|mv| ValueRef::new(mv.default_statetree_value()))
+            .map(|mv| resolve(mv, &self.resolving, || PathElement::Key(key.into())))
     }
 
     pub fn keys(&self) -> impl Iterator<Item = &SmolStr> {
@@ -38,20 +44,21 @@ impl<'a> MapRef<'a> {
     }
 
     pub fn values(&self) -> impl Iterator<Item = ValueRef<'a>> {
-        self.stm
-            .props
-            .values()
-            .map(|v| ValueRef::new(v.default_statetree_value()))
+        self.iter().map(|(_, v)| v)
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = (&SmolStr, ValueRef<'a>)> {
+    pub fn iter(&self) -> impl Iterator<Item = (&'a SmolStr, ValueRef<'a>)> {
+        let resolving = self.resolving.clone();
         self.stm
             .props
             .iter()
-            .map(|(k, v)| (k, ValueRef::new(v.default_statetree_value())))
+            .map(move |(k, v)| (k, resolve(v, &resolving, || PathElement::Key(k.clone()))))
     }
 
     pub fn value(&self) -> Value {
+        if self.resolving.is_some() {
+            return Value::Map(self.iter().map(|(k, v)| (k.clone(), v.value())).collect());
+        }
         let mut m = HashMap::new();
         for (k, v) in &self.stm.props {
             m.insert(k.clone(), v.default_value());
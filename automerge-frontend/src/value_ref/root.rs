@@ -1,16 +1,17 @@
 use smol_str::SmolStr;
 
-use super::ValueRef;
-use crate::{state_tree::StateTree, Value};
+use super::{resolve, Resolving, ValueRef};
+use crate::{path::PathElement, state_tree::StateTree, Path, Value};
 
 #[derive(Clone, Debug)]
 pub struct RootRef<'a> {
     st: &'a StateTree,
+    resolving: Option<Resolving<'a>>,
 }
 
 impl<'a> RootRef<'a> {
-    pub(crate) fn new(st: &'a StateTree) -> Self {
-        Self { st }
+    pub(crate) fn new(st: &'a StateTree, resolving: Option<Resolving<'a>>) -> Self {
+        Self { st, resolving }
     }
 
     pub fn contains_key(&self, key: &str) -> bool {
@@ -29,7 +30,7 @@ impl<'a> RootRef<'a> {
         self.st
             .root_props
             .get(key)
-            .map(|mv| ValueRef::new(mv.default_statetree_value()))
+            .map(|mv| resolve(mv, &self.resolving, || PathElement::Key(key.into())))
     }
 
     pub fn keys(&self) -> impl Iterator<Item = &SmolStr> {
@@ -37,20 +38,31 @@ impl<'a> RootRef<'a> {
     }
 
     pub fn values(&self) -> impl Iterator<Item = ValueRef<'a>> {
-        self.st
-            .root_props
-            .values()
-            .map(|v| ValueRef::new(v.default_statetree_value()))
+        self.iter().map(|(_, v)| v)
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = (&SmolStr, ValueRef<'a>)> {
+    pub fn iter(&self) -> impl Iterator<Item = (&'a SmolStr, ValueRef<'a>)> {
+        let resolving = self.resolving.clone();
         self.st
             .root_props
             .iter()
-            .map(|(k, v)| (k, ValueRef::new(v.default_statetree_value())))
+            .map(move |(k, v)| (k, resolve(v, &resolving, || PathElement::Key(k.clone()))))
     }
 
     pub fn value(&self) -> Value {
+        if self.resolving.is_some() {
+            return Value::Map(self.iter().map(|(k, v)| (k.clone(), v.value())).collect());
+        }
         self.st.value()
     }
+
+    /// The value at `path`, if there is one
+    pub(crate) fn get_path(&self, path: &Path) -> Option<Value> {
+        let elements = path.clone().elements();
+        match elements.split_first() {
+            None => Some(self.value()),
+            Some((PathElement::Key(k), rest)) => self.get(k)?.get_path(rest),
+            Some((PathElement::Index(_), _)) => None,
+        }
+    }
 }
@@ -1,17 +1,27 @@
-use std::collections::HashMap;
+use std::{cmp::Ordering, collections::HashMap};
 
 use smol_str::SmolStr;
 
-use crate::{state_tree::StateTreeTable, value_ref::ValueRef, Value};
+use crate::{
+    path::PathElement,
+    state_tree::StateTreeTable,
+    value_ref::{resolve, Resolving, ValueRef},
+    Primitive, Value,
+};
 
+/// A table, whose rows are maps keyed by a row ID.
+///
+/// Rows are added with [`MutableDocument::table_insert`](crate::MutableDocument::table_insert).
+/// The methods which return several rows return them in order of row ID, unless they sort them.
 #[derive(Clone, Debug)]
 pub struct TableRef<'a> {
     stt: &'a StateTreeTable,
+    resolving: Option<Resolving<'a>>,
 }
 
 impl<'a> TableRef<'a> {
-    pub(crate) fn new(stt: &'a StateTreeTable) -> Self {
-        Self { stt }
+    pub(crate) fn new(stt: &'a StateTreeTable, resolving: Option<Resolving<'a>>) -> Self {
+        Self { stt, resolving }
     }
 
     pub fn contains_key(&self, key: &str) -> bool {
@@ -30,7 +40,7 @@ impl<'a> TableRef<'a> {
         self.stt
             .props
             .get(key)
-            .map(|mv| ValueRef::new(mv.default_statetree_value()))
+            .map(|mv| resolve(mv, &self.resolving, || PathElement::Key(key.into())))
     }
 
     pub fn keys(&self) -> impl Iterator<Item = &SmolStr> {
@@ -38,24 +48,127 @@ impl<'a> TableRef<'a> {
     }
 
     pub fn values(&self) -> impl Iterator<Item = ValueRef<'a>> {
-        self.stt
-            .props
-            .values()
-            .map(|v| ValueRef::new(v.default_statetree_value()))
+        self.iter().map(|(_, v)| v)
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = (&SmolStr, ValueRef<'a>)> {
+    pub fn iter(&self) -> impl Iterator<Item = (&'a SmolStr, ValueRef<'a>)> {
+        let resolving = self.resolving.clone();
         self.stt
             .props
             .iter()
-            .map(|(k, v)| (k, ValueRef::new(v.default_statetree_value())))
+            .map(move |(k, v)| (k, resolve(v, &resolving, || PathElement::Key(k.clone()))))
+    }
+
+    /// The rows for which `predicate` returns true
+    pub fn filter<F>(&self, mut predicate: F) -> Vec<(&'a SmolStr, ValueRef<'a>)>
+    where
+        F: FnMut(&SmolStr, &ValueRef<'a>) -> bool,
+    {
+        let mut rows = self
+            .iter()
+            .filter(|(id, row)| predicate(id, row))
+            .collect::<Vec<_>>();
+        rows.sort_by_key(|(id, _)| *id);
+        rows
+    }
+
+    /// The rows whose `column` is `value`
+    pub fn lookup(&self, column: &str, value: &Value) -> Vec<(&'a SmolStr, ValueRef<'a>)> {
+        self.filter(|_, row| column_of(row, column).map(|v| v.value()).as_ref() == Some(value))
+    }
+
+    /// Every row, sorted by `compare`
+    pub fn sort_by<F>(&self, mut compare: F) -> Vec<(&'a SmolStr, ValueRef<'a>)>
+    where
+        F: FnMut(&ValueRef<'a>, &ValueRef<'a>) -> Ordering,
+    {
+        let mut rows = self.filter(|_, _| true);
+        // the rows are in order of ID, so rows which compare equal stay in that order
+        rows.sort_by(|(_, a), (_, b)| compare(a, b));
+        rows
+    }
+
+    /// Every row, sorted by the value of the first of `columns`, then by the next, and so on.
+    ///
+    /// Null sorts before booleans, then numbers, then strings and then bytes. Rows where a column
+    /// is missing or is not a primitive sort after the others.
+    pub fn sort_by_columns(&self, columns: &[&str]) -> Vec<(&'a SmolStr, ValueRef<'a>)> {
+        self.sort_by(|a, b| {
+            columns
+                .iter()
+                .map(|column| {
+                    compare_columns(
+                        column_of(a, column).as_ref().and_then(ValueRef::primitive),
+                        column_of(b, column).as_ref().and_then(ValueRef::primitive),
+                    )
+                })
+                .find(|ordering| *ordering != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        })
     }
 
     pub fn value(&self) -> Value {
+        if self.resolving.is_some() {
+            return Value::Table(self.iter().map(|(k, v)| (k.clone(), v.value())).collect());
+        }
         let mut m = HashMap::new();
         for (k, v) in &self.stt.props {
             m.insert(k.clone(), v.default_value());
         }
-        Value::Map(m)
+        Value::Table(m)
+    }
+}
+
+/// The value of `column` in `row`, if `row` is a map
+fn column_of<'a>(row: &ValueRef<'a>, column: &str) -> Option<ValueRef<'a>> {
+    row.map()?.get(column)
+}
+
+fn compare_columns(a: Option<&Primitive>, b: Option<&Primitive>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => compare_primitives(a, b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// The order used to sort and index primitive column values: values of the same kind are
+/// compared naturally, numbers of any type by their value, and different kinds by [`rank`]
+pub(crate) fn compare_primitives(a: &Primitive, b: &Primitive) -> Ordering {
+    match (number(a), number(b)) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => match (a, b) {
+            (Primitive::Boolean(a), Primitive::Boolean(b)) => a.cmp(b),
+            (Primitive::Str(a), Primitive::Str(b)) => a.cmp(b),
+            (Primitive::Bytes(a), Primitive::Bytes(b)) => a.cmp(b),
+            (a, b) => rank(a).cmp(&rank(b)),
+        },
+    }
+}
+
+/// The value of a number, as a float so that numbers of different types can be compared
+fn number(p: &Primitive) -> Option<f64> {
+    match p {
+        Primitive::Int(i) | Primitive::Counter(i) | Primitive::Timestamp(i) => Some(*i as f64),
+        Primitive::Uint(u) => Some(*u as f64),
+        Primitive::F64(f) => Some(*f),
+        _ => None,
+    }
+}
+
+/// The order of the different kinds of primitive
+fn rank(p: &Primitive) -> u8 {
+    match p {
+        Primitive::Null => 0,
+        Primitive::Boolean(_) => 1,
+        Primitive::Int(_)
+        | Primitive::Uint(_)
+        | Primitive::F64(_)
+        | Primitive::Counter(_)
+        | Primitive::Timestamp(_) => 2,
+        Primitive::Str(_) => 3,
+        Primitive::Bytes(_) => 4,
+        Primitive::Cursor(_) => 5,
     }
 }
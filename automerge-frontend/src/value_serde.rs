@@ -0,0 +1,127 @@
+//! Conversion between Rust types and [`Value`]s using serde.
+//!
+//! [`to_value`] serializes any `Serialize` type into a [`Value`], which can then be set in a
+//! document with [`LocalChange::set`](crate::LocalChange::set), or both at once with
+//! [`LocalChange::set_serialize`](crate::LocalChange::set_serialize). [`from_value`] and
+//! [`from_value_ref`] go the other way. [`RootRef`](crate::value_ref::RootRef) also implements
+//! `Deserializer`, so the whole document can be read with
+//! `T::deserialize(frontend.value_ref())`.
+//!
+//! Structs and maps become [`Value::Map`]s, sequences become [`Value::List`]s and strings become
+//! [`Primitive::Str`](crate::Primitive::Str)s. The [`Text`], [`Table`], [`Counter`] and
+//! [`Timestamp`] newtypes, or the [`text`], [`table`], [`counter`] and [`timestamp`] modules used
+//! with `#[serde(with = "...")]`, request the other representations. With any other serde format
+//! these are serialized as the type they wrap.
+
+mod de;
+mod ser;
+
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{error::SerdeError, value_ref::ValueRef, Value};
+
+const TEXT: &str = "$automerge::Text";
+const TABLE: &str = "$automerge::Table";
+const COUNTER: &str = "$automerge::Counter";
+const TIMESTAMP: &str = "$automerge::Timestamp";
+
+/// Serialize `value` into a [`Value`]
+pub fn to_value<T>(value: &T) -> Result<Value, SerdeError>
+where
+    T: Serialize + ?Sized,
+{
+    value.serialize(ser::ValueSerializer)
+}
+
+/// Deserialize a `T` from `value`
+pub fn from_value<'a, T>(value: &'a Value) -> Result<T, SerdeError>
+where
+    T: Deserialize<'a>,
+{
+    T::deserialize(value)
+}
+
+/// Deserialize a `T` from `value`, reading directly from the document's state
+pub fn from_value_ref<T>(value: ValueRef) -> Result<T, SerdeError>
+where
+    T: DeserializeOwned,
+{
+    T::deserialize(value)
+}
+
+/// A string stored as a text object rather than as a string primitive
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Text(pub String);
+
+/// A map stored as a table rather than as a map
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Table<T>(pub T);
+
+/// An integer stored as a counter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Counter(pub i64);
+
+/// An integer stored as a timestamp
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Timestamp(pub i64);
+
+macro_rules! newtype_impls {
+    ($name:ident $(<$param:ident>)?, $inner:ty, $token:ident, $module:ident) => {
+        impl<$($param: Serialize)?> Serialize for $name<$($param)?> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct($token, &self.0)
+            }
+        }
+
+        impl<'de, $($param: Deserialize<'de>)?> Deserialize<'de> for $name<$($param)?> {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                $module::deserialize(deserializer).map($name)
+            }
+        }
+
+        #[doc = concat!(
+            "Serialize a field as a [`", stringify!($name), "`](crate::value_serde::", stringify!($name), "), ",
+            "for use with `#[serde(with = \"automerge_frontend::value_serde::", stringify!($module), "\")]`"
+        )]
+        pub mod $module {
+            use std::{fmt, marker::PhantomData};
+
+            use serde::{de, Deserialize, Deserializer, Serializer};
+
+            pub fn serialize<$($param: serde::Serialize,)? S: Serializer>(
+                value: &$inner,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct(super::$token, value)
+            }
+
+            pub fn deserialize<'de, $($param: Deserialize<'de>,)? D: Deserializer<'de>>(
+                deserializer: D,
+            ) -> Result<$inner, D::Error> {
+                struct Visitor<T>(PhantomData<T>);
+
+                impl<'de, T: Deserialize<'de>> de::Visitor<'de> for Visitor<T> {
+                    type Value = T;
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        f.write_str(stringify!($module))
+                    }
+
+                    fn visit_newtype_struct<D: Deserializer<'de>>(
+                        self,
+                        deserializer: D,
+                    ) -> Result<T, D::Error> {
+                        T::deserialize(deserializer)
+                    }
+                }
+
+                deserializer.deserialize_newtype_struct(super::$token, Visitor(PhantomData))
+            }
+        }
+    };
+}
+
+newtype_impls!(Text, String, TEXT, text);
+newtype_impls!(Table<T>, T, TABLE, table);
+newtype_impls!(Counter, i64, COUNTER, counter);
+newtype_impls!(Timestamp, i64, TIMESTAMP, timestamp);
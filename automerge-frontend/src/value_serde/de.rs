@@ -0,0 +1,406 @@
+use std::borrow::Cow;
+
+use serde::{
+    de::{self, value::BorrowedStrDeserializer, IntoDeserializer, Unexpected, Visitor},
+    forward_to_deserialize_any, Deserializer,
+};
+use smol_str::SmolStr;
+
+use crate::{
+    error::SerdeError,
+    value_ref::{RootRef, ValueRef},
+    Primitive, Value,
+};
+
+impl<'de> Deserializer<'de> for &'de Value {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self {
+            Value::Map(props) | Value::Table(props) => visitor.visit_map(MapAccess::new(
+                props.iter().map(|(k, v)| (k.as_str(), v)),
+                props.len(),
+            )),
+            Value::List(elements) => {
+                visitor.visit_seq(SeqAccess::new(elements.iter(), elements.len()))
+            }
+            Value::Text(graphemes) => visitor.visit_string(graphemes.concat()),
+            Value::Primitive(p) => visit_primitive(p, visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self {
+            Value::Primitive(Primitive::Null) => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self {
+            Value::Primitive(Primitive::Str(variant)) => visitor.visit_enum(EnumAccess {
+                variant: Cow::Borrowed(variant.as_str()),
+                value: None::<&Value>,
+            }),
+            Value::Map(props) if props.len() == 1 => {
+                let (variant, value) = props.iter().next().unwrap();
+                visitor.visit_enum(EnumAccess {
+                    variant: Cow::Borrowed(variant.as_str()),
+                    value: Some(value),
+                })
+            }
+            _ => Err(de::Error::invalid_type(unexpected(self), &"an enum")),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> Deserializer<'de> for ValueRef<'de> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self {
+            ValueRef::Map(map) => visitor.visit_map(MapAccess::new(
+                map.iter().map(|(k, v)| (k.as_str(), v)),
+                map.len(),
+            )),
+            ValueRef::Table(table) => visitor.visit_map(MapAccess::new(
+                table.iter().map(|(k, v)| (k.as_str(), v)),
+                table.len(),
+            )),
+            ValueRef::List(list) => visitor.visit_seq(SeqAccess::new(list.iter(), list.len())),
+            ValueRef::Text(text) => {
+                visitor.visit_string(text.iter().map(SmolStr::as_str).collect())
+            }
+            ValueRef::Primitive(Cow::Borrowed(p)) => visit_primitive(p, visitor),
+            ValueRef::Primitive(Cow::Owned(p)) => visit_owned_primitive(p, visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.primitive() {
+            Some(Primitive::Null) => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self {
+            ValueRef::Primitive(Cow::Borrowed(Primitive::Str(variant))) => {
+                visitor.visit_enum(EnumAccess {
+                    variant: Cow::Borrowed(variant.as_str()),
+                    value: None::<ValueRef>,
+                })
+            }
+            ValueRef::Primitive(Cow::Owned(Primitive::Str(variant))) => {
+                visitor.visit_enum(EnumAccess {
+                    variant: Cow::Owned(variant.to_string()),
+                    value: None::<ValueRef>,
+                })
+            }
+            ValueRef::Map(map) if map.len() == 1 => {
+                let (variant, value) = map.iter().next().unwrap();
+                visitor.visit_enum(EnumAccess {
+                    variant: Cow::Borrowed(variant.as_str()),
+                    value: Some(value),
+                })
+            }
+            value => Err(de::Error::invalid_type(
+                unexpected(&value.value()),
+                &"an enum",
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> Deserializer<'de> for RootRef<'de> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_map(MapAccess::new(
+            self.iter().map(|(k, v)| (k.as_str(), v)),
+            self.len(),
+        ))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+fn visit_primitive<'de, V: Visitor<'de>>(
+    primitive: &'de Primitive,
+    visitor: V,
+) -> Result<V::Value, SerdeError> {
+    match primitive {
+        Primitive::Bytes(b) => visitor.visit_borrowed_bytes(b),
+        Primitive::Str(s) => visitor.visit_borrowed_str(s),
+        p => visit_scalar(p, visitor),
+    }
+}
+
+/// Visit a primitive merged from conflicting values by a conflict resolver, which can't be
+/// borrowed from the document
+fn visit_owned_primitive<'de, V: Visitor<'de>>(
+    primitive: Primitive,
+    visitor: V,
+) -> Result<V::Value, SerdeError> {
+    match primitive {
+        Primitive::Bytes(b) => visitor.visit_byte_buf(b),
+        Primitive::Str(s) => visitor.visit_string(s.to_string()),
+        p => visit_scalar(&p, visitor),
+    }
+}
+
+fn visit_scalar<'de, V: Visitor<'de>>(
+    primitive: &Primitive,
+    visitor: V,
+) -> Result<V::Value, SerdeError> {
+    match primitive {
+        Primitive::Bytes(b) => visitor.visit_bytes(b),
+        Primitive::Str(s) => visitor.visit_str(s),
+        Primitive::Int(n) | Primitive::Counter(n) | Primitive::Timestamp(n) => {
+            visitor.visit_i64(*n)
+        }
+        Primitive::Uint(n) => visitor.visit_u64(*n),
+        Primitive::F64(n) => visitor.visit_f64(*n),
+        Primitive::Boolean(b) => visitor.visit_bool(*b),
+        Primitive::Cursor(_) => Err(de::Error::invalid_type(
+            Unexpected::Other("a cursor"),
+            &visitor,
+        )),
+        Primitive::Null => visitor.visit_unit(),
+    }
+}
+
+fn unexpected(value: &Value) -> Unexpected<'_> {
+    match value {
+        Value::Map(_) | Value::Table(_) => Unexpected::Map,
+        Value::List(_) => Unexpected::Seq,
+        Value::Text(_) => Unexpected::Other("text"),
+        Value::Primitive(p) => match p {
+            Primitive::Bytes(b) => Unexpected::Bytes(b),
+            Primitive::Str(s) => Unexpected::Str(s),
+            Primitive::Int(n) | Primitive::Counter(n) | Primitive::Timestamp(n) => {
+                Unexpected::Signed(*n)
+            }
+            Primitive::Uint(n) => Unexpected::Unsigned(*n),
+            Primitive::F64(n) => Unexpected::Float(*n),
+            Primitive::Boolean(b) => Unexpected::Bool(*b),
+            Primitive::Cursor(_) => Unexpected::Other("a cursor"),
+            Primitive::Null => Unexpected::Unit,
+        },
+    }
+}
+
+struct SeqAccess<I> {
+    elements: I,
+    remaining: usize,
+}
+
+impl<I> SeqAccess<I> {
+    fn new(elements: I, len: usize) -> Self {
+        SeqAccess {
+            elements,
+            remaining: len,
+        }
+    }
+}
+
+impl<'de, I, D> de::SeqAccess<'de> for SeqAccess<I>
+where
+    I: Iterator<Item = D>,
+    D: Deserializer<'de, Error = SerdeError>,
+{
+    type Error = SerdeError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, SerdeError>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.elements.next() {
+            Some(element) => {
+                self.remaining -= 1;
+                seed.deserialize(element).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+struct MapAccess<I, D> {
+    entries: I,
+    remaining: usize,
+    value: Option<D>,
+}
+
+impl<I, D> MapAccess<I, D> {
+    fn new(entries: I, len: usize) -> Self {
+        MapAccess {
+            entries,
+            remaining: len,
+            value: None,
+        }
+    }
+}
+
+impl<'de, I, D> de::MapAccess<'de> for MapAccess<I, D>
+where
+    I: Iterator<Item = (&'de str, D)>,
+    D: Deserializer<'de, Error = SerdeError>,
+{
+    type Error = SerdeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, SerdeError>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.remaining -= 1;
+                self.value = Some(value);
+                seed.deserialize(BorrowedStrDeserializer::new(key))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let value = self
+            .value
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        seed.deserialize(value)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// An enum, represented either by the name of a unit variant or by a map from the name of the
+/// variant to its contents
+struct EnumAccess<'de, D> {
+    variant: Cow<'de, str>,
+    value: Option<D>,
+}
+
+impl<'de, D> de::EnumAccess<'de> for EnumAccess<'de, D>
+where
+    D: Deserializer<'de, Error = SerdeError>,
+{
+    type Error = SerdeError;
+    type Variant = VariantAccess<D>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, VariantAccess<D>), SerdeError>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, VariantAccess(self.value)))
+    }
+}
+
+struct VariantAccess<D>(Option<D>);
+
+impl<'de, D> de::VariantAccess<'de> for VariantAccess<D>
+where
+    D: Deserializer<'de, Error = SerdeError>,
+{
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        match self.0 {
+            Some(value) => de::Deserialize::deserialize(value),
+            None => Ok(()),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, SerdeError>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.0 {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"a newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self.0 {
+            Some(value) => value.deserialize_seq(visitor),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"a tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self.0 {
+            Some(value) => value.deserialize_map(visitor),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"a struct variant",
+            )),
+        }
+    }
+}
@@ -0,0 +1,549 @@
+use std::collections::HashMap;
+
+use serde::{ser, Serialize};
+use smol_str::SmolStr;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{COUNTER, TABLE, TEXT, TIMESTAMP};
+use crate::{error::SerdeError, Primitive, Value};
+
+/// A serializer whose output is a [`Value`]
+pub(super) struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeTupleVariant;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeStructVariant;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, SerdeError> {
+        Ok(Value::Primitive(Primitive::Boolean(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, SerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, SerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, SerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, SerdeError> {
+        Ok(Value::Primitive(Primitive::Int(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, SerdeError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, SerdeError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, SerdeError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, SerdeError> {
+        Ok(Value::Primitive(Primitive::Uint(v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, SerdeError> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, SerdeError> {
+        Ok(Value::Primitive(Primitive::F64(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, SerdeError> {
+        Ok(Value::Primitive(Primitive::Str(SmolStr::new(
+            v.to_string(),
+        ))))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, SerdeError> {
+        Ok(Value::Primitive(Primitive::Str(SmolStr::new(v))))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, SerdeError> {
+        Ok(Value::Primitive(Primitive::Bytes(v.to_vec())))
+    }
+
+    fn serialize_none(self) -> Result<Value, SerdeError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Value, SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, SerdeError> {
+        Ok(Value::Primitive(Primitive::Null))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, SerdeError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value, SerdeError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Value, SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = value.serialize(self)?;
+        match (name, value) {
+            (TEXT, Value::Primitive(Primitive::Str(s))) => {
+                Ok(Value::Text(s.graphemes(true).map(SmolStr::new).collect()))
+            }
+            (TABLE, Value::Map(props)) => Ok(Value::Table(props)),
+            (COUNTER, Value::Primitive(Primitive::Int(n))) => {
+                Ok(Value::Primitive(Primitive::Counter(n)))
+            }
+            (TIMESTAMP, Value::Primitive(Primitive::Int(n))) => {
+                Ok(Value::Primitive(Primitive::Timestamp(n)))
+            }
+            (COUNTER, Value::Primitive(Primitive::Uint(n))) if n <= i64::MAX as u64 => {
+                Ok(Value::Primitive(Primitive::Counter(n as i64)))
+            }
+            (TIMESTAMP, Value::Primitive(Primitive::Uint(n))) if n <= i64::MAX as u64 => {
+                Ok(Value::Primitive(Primitive::Timestamp(n as i64)))
+            }
+            (TEXT, value) | (TABLE, value) | (COUNTER, value) | (TIMESTAMP, value) => {
+                Err(SerdeError::InvalidRepresentation {
+                    representation: representation_name(name),
+                    value,
+                })
+            }
+            (_, value) => Ok(value),
+        }
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let mut props = HashMap::new();
+        props.insert(SmolStr::new(variant), value.serialize(self)?);
+        Ok(Value::Map(props))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList, SerdeError> {
+        Ok(SerializeList {
+            elements: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeList, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeList, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeTupleVariant, SerdeError> {
+        Ok(SerializeTupleVariant {
+            variant,
+            elements: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap, SerdeError> {
+        Ok(SerializeMap {
+            props: HashMap::new(),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap, SerdeError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SerializeStructVariant, SerdeError> {
+        Ok(SerializeStructVariant {
+            variant,
+            props: HashMap::new(),
+        })
+    }
+}
+
+fn representation_name(name: &'static str) -> &'static str {
+    match name {
+        TEXT => "text",
+        TABLE => "a table",
+        COUNTER => "a counter",
+        _ => "a timestamp",
+    }
+}
+
+pub(super) struct SerializeList {
+    elements: Vec<Value>,
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.elements.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(Value::List(self.elements))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/// A tuple variant, serialized as a map from the variant name to a list of the fields
+pub(super) struct SerializeTupleVariant {
+    variant: &'static str,
+    elements: Vec<Value>,
+}
+
+impl ser::SerializeTupleVariant for SerializeTupleVariant {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.elements.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        let mut props = HashMap::new();
+        props.insert(SmolStr::new(self.variant), Value::List(self.elements));
+        Ok(Value::Map(props))
+    }
+}
+
+pub(super) struct SerializeMap {
+    props: HashMap<SmolStr, Value>,
+    next_key: Option<SmolStr>,
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.next_key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self
+            .next_key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        self.props.insert(key, value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(Value::Map(self.props))
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.props
+            .insert(SmolStr::new(key), value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(Value::Map(self.props))
+    }
+}
+
+/// A struct variant, serialized as a map from the variant name to a map of the fields
+pub(super) struct SerializeStructVariant {
+    variant: &'static str,
+    props: HashMap<SmolStr, Value>,
+}
+
+impl ser::SerializeStructVariant for SerializeStructVariant {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.props
+            .insert(SmolStr::new(key), value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        let mut props = HashMap::new();
+        props.insert(SmolStr::new(self.variant), Value::Map(self.props));
+        Ok(Value::Map(props))
+    }
+}
+
+/// Serializes map keys, which must be strings. As in JSON, integer keys are converted to strings.
+struct KeySerializer;
+
+impl ser::Serializer for KeySerializer {
+    type Ok = SmolStr;
+    type Error = SerdeError;
+
+    type SerializeSeq = ser::Impossible<SmolStr, SerdeError>;
+    type SerializeTuple = ser::Impossible<SmolStr, SerdeError>;
+    type SerializeTupleStruct = ser::Impossible<SmolStr, SerdeError>;
+    type SerializeTupleVariant = ser::Impossible<SmolStr, SerdeError>;
+    type SerializeMap = ser::Impossible<SmolStr, SerdeError>;
+    type SerializeStruct = ser::Impossible<SmolStr, SerdeError>;
+    type SerializeStructVariant = ser::Impossible<SmolStr, SerdeError>;
+
+    fn serialize_bool(self, _v: bool) -> Result<SmolStr, SerdeError> {
+        Err(SerdeError::KeyMustBeAString)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<SmolStr, SerdeError> {
+        Ok(SmolStr::new(v.to_string()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<SmolStr, SerdeError> {
+        Ok(SmolStr::new(v.to_string()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<SmolStr, SerdeError> {
+        Ok(SmolStr::new(v.to_string()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<SmolStr, SerdeError> {
+        Ok(SmolStr::new(v.to_string()))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<SmolStr, SerdeError> {
+        Ok(SmolStr::new(v.to_string()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<SmolStr, SerdeError> {
+        Ok(SmolStr::new(v.to_string()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<SmolStr, SerdeError> {
+        Ok(SmolStr::new(v.to_string()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<SmolStr, SerdeError> {
+        Ok(SmolStr::new(v.to_string()))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<SmolStr, SerdeError> {
+        Err(SerdeError::KeyMustBeAString)
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<SmolStr, SerdeError> {
+        Err(SerdeError::KeyMustBeAString)
+    }
+
+    fn serialize_char(self, v: char) -> Result<SmolStr, SerdeError> {
+        Ok(SmolStr::new(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<SmolStr, SerdeError> {
+        Ok(SmolStr::new(v))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<SmolStr, SerdeError> {
+        Err(SerdeError::KeyMustBeAString)
+    }
+
+    fn serialize_none(self) -> Result<SmolStr, SerdeError> {
+        Err(SerdeError::KeyMustBeAString)
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<SmolStr, SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(SerdeError::KeyMustBeAString)
+    }
+
+    fn serialize_unit(self) -> Result<SmolStr, SerdeError> {
+        Err(SerdeError::KeyMustBeAString)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<SmolStr, SerdeError> {
+        Err(SerdeError::KeyMustBeAString)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<SmolStr, SerdeError> {
+        Ok(SmolStr::new(variant))
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<SmolStr, SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<SmolStr, SerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(SerdeError::KeyMustBeAString)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerdeError> {
+        Err(SerdeError::KeyMustBeAString)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerdeError> {
+        Err(SerdeError::KeyMustBeAString)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerdeError> {
+        Err(SerdeError::KeyMustBeAString)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        Err(SerdeError::KeyMustBeAString)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerdeError> {
+        Err(SerdeError::KeyMustBeAString)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, SerdeError> {
+        Err(SerdeError::KeyMustBeAString)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerdeError> {
+        Err(SerdeError::KeyMustBeAString)
+    }
+}
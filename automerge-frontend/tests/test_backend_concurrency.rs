@@ -730,3 +730,76 @@ fn test_deps_are_filled_in_if_frontend_does_not_have_latest_patch() {
     };
     assert_eq!(change4, expected_change4);
 }
+
+#[test]
+fn rollback_state_matches_backend_after_overwriting_a_conflict() {
+    let mut backend = Backend::new();
+    let mut doc = Frontend::new();
+    let mut remote_backend = Backend::new();
+    let mut remote_doc = Frontend::new();
+
+    // the remote and local actors make conflicting changes to the same key
+    for (doc, backend, value) in [
+        (&mut doc, &mut backend, 1),
+        (&mut remote_doc, &mut remote_backend, 2),
+    ]
+    .iter_mut()
+    {
+        let change = doc
+            .change::<_, _, InvalidChangeRequest>(None, |d| {
+                d.add_change(LocalChange::set(
+                    Path::root().key("number"),
+                    Primitive::Int(*value),
+                ))
+            })
+            .unwrap()
+            .1
+            .unwrap();
+        let (patch, _) = backend.apply_local_change(change).unwrap();
+        doc.apply_patch(patch).unwrap();
+    }
+    let remote_changes = remote_backend
+        .get_changes(&[])
+        .into_iter()
+        .cloned()
+        .collect();
+    let patch = backend.apply_changes(remote_changes).unwrap();
+    doc.apply_patch(patch).unwrap();
+
+    // overwriting the value only overwrites the winner, so the backend keeps the other value
+    let change = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(
+                Path::root().key("number"),
+                Primitive::Int(3),
+            ))
+        })
+        .unwrap()
+        .1
+        .unwrap();
+    let (patch, _) = backend.apply_local_change(change).unwrap();
+    doc.apply_patch(patch).unwrap();
+
+    // a change which fails is rolled back to the state the backend acknowledged
+    let failed = doc.change::<_, (), _>(None, |d| {
+        d.add_change(LocalChange::set(
+            Path::root().key("number"),
+            Primitive::Int(4),
+        ))?;
+        Err(InvalidChangeRequest::NoSuchSavepoint)
+    });
+    assert!(failed.is_err());
+    let mut from_backend = Frontend::new();
+    from_backend
+        .apply_patch(backend.get_patch().unwrap())
+        .unwrap();
+    assert_eq!(doc.state(), from_backend.state());
+
+    // a change which does nothing starts from the same state as the backend's last patch
+    let change = doc
+        .change::<_, _, InvalidChangeRequest>(None, |_| Ok(()))
+        .unwrap()
+        .1;
+    assert_eq!(change, None);
+    assert_eq!(doc.state(), from_backend.state());
+}
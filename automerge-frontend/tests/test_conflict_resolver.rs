@@ -0,0 +1,114 @@
+use automerge_backend::Backend;
+use automerge_frontend::{
+    Frontend, InvalidChangeRequest, LocalChange, Path, Primitive, Resolution, Value,
+};
+use automerge_protocol as amp;
+use maplit::hashmap;
+
+/// A frontend with the changes made by actors 1 and 2 each setting the keys in `values`
+fn conflicted(values: &[(&str, Value, Value)]) -> Frontend {
+    let mut backend = Backend::new();
+    for actor in 1..=2 {
+        let mut doc = Frontend::new_with_actor_id(&[actor]);
+        let (_, change) = doc
+            .change::<_, _, InvalidChangeRequest>(None, |d| {
+                for (key, first, second) in values {
+                    let value = if actor == 1 { first } else { second };
+                    d.add_change(LocalChange::set(Path::root().key(*key), value.clone()))?;
+                }
+                Ok(())
+            })
+            .unwrap();
+        backend.apply_changes(vec![change.unwrap().into()]).unwrap();
+    }
+    let mut doc = Frontend::new();
+    doc.apply_patch(backend.get_patch().unwrap()).unwrap();
+    doc
+}
+
+fn int(i: i64) -> Value {
+    Value::Primitive(Primitive::Int(i))
+}
+
+/// A resolver which prefers values set by actor 1
+fn prefer_first(_: &Path, candidates: &[(amp::OpId, Value)]) -> Resolution {
+    let first = amp::ActorId::from(&[1][..]);
+    let (winner, _) = candidates
+        .iter()
+        .find(|(opid, _)| opid.1 == first)
+        .unwrap_or_else(|| candidates.last().unwrap());
+    Resolution::Winner(winner.clone())
+}
+
+#[test]
+fn resolvers_choose_the_visible_value() {
+    let mut doc = conflicted(&[("title", "first".into(), "second".into())]);
+    let title = Path::root().key("title");
+    assert_eq!(doc.get_value(&title), Some("second".into()));
+
+    doc.set_conflict_resolver(Some(Box::new(prefer_first)));
+    assert_eq!(doc.get_value(&title), Some("first".into()));
+    assert_eq!(
+        doc.state(),
+        &Value::from(hashmap! {"title" => Value::from("first")})
+    );
+    assert_eq!(
+        doc.value_ref()
+            .get("title")
+            .and_then(|v| v.primitive().cloned()),
+        Some(Primitive::Str("first".into()))
+    );
+    // every value is still a conflict
+    assert_eq!(doc.get_conflicts(&title).unwrap().len(), 2);
+
+    doc.set_conflict_resolver(None);
+    assert_eq!(doc.get_value(&title), Some("second".into()));
+}
+
+#[test]
+fn resolvers_can_merge_conflicting_values() {
+    let mut doc = conflicted(&[("score", int(7), int(3)), ("name", "a".into(), "b".into())]);
+    doc.set_conflict_resolver(Some(Box::new(
+        |path: &Path, candidates: &[(amp::OpId, Value)]| {
+            if path == &Path::root().key("score") {
+                let max = candidates
+                    .iter()
+                    .filter_map(|(_, v)| match v {
+                        Value::Primitive(Primitive::Int(i)) => Some(*i),
+                        _ => None,
+                    })
+                    .max()
+                    .unwrap();
+                Resolution::Merged(Primitive::Int(max))
+            } else {
+                Resolution::Winner(candidates.last().unwrap().0.clone())
+            }
+        },
+    )));
+    assert_eq!(doc.get_value(&Path::root().key("score")), Some(int(7)));
+    assert_eq!(doc.get_value(&Path::root().key("name")), Some("b".into()));
+    assert_eq!(
+        doc.value_ref().get("score").unwrap().value(),
+        Value::Primitive(Primitive::Int(7))
+    );
+}
+
+#[test]
+fn paths_follow_the_resolved_value() {
+    let mut doc = conflicted(&[(
+        "settings",
+        Value::from(hashmap! {"theme" => "light"}),
+        Value::from(hashmap! {"theme" => "dark"}),
+    )]);
+    let theme = Path::root().key("settings").key("theme");
+    assert_eq!(doc.get_value(&theme), Some("dark".into()));
+
+    doc.set_conflict_resolver(Some(Box::new(prefer_first)));
+    assert_eq!(doc.get_value(&theme), Some("light".into()));
+    assert_eq!(
+        doc.state(),
+        &Value::from(hashmap! {
+            "settings" => Value::from(hashmap! {"theme" => "light"}),
+        })
+    );
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use automerge_backend::Backend;
+use automerge_frontend::{
+    Conflict, Frontend, InvalidChangeRequest, LocalChange, Path, Primitive, Value,
+};
+use automerge_protocol as amp;
+
+fn text(s: &str) -> Value {
+    Value::Text(s.chars().map(|c| c.to_string().into()).collect())
+}
+
+fn apply(doc: &mut Frontend, backend: &mut Backend, change: LocalChange) -> amp::Change {
+    let (_, change) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| d.add_change(change))
+        .unwrap();
+    let change = change.unwrap();
+    backend.apply_changes(vec![change.clone().into()]).unwrap();
+    change
+}
+
+/// A document where actors 1 and 2 concurrently set the title, a setting, the second todo and
+/// the first character of the notes
+fn conflicted() -> (Frontend, Backend) {
+    let mut backend = Backend::new();
+    let mut base = Frontend::new_with_actor_id(&[0]);
+    for (key, value) in [
+        ("title", Value::from("untitled")),
+        ("settings", Value::Map(HashMap::new())),
+        ("todos", Value::List(vec!["a".into(), "b".into()])),
+        ("notes", text("hi")),
+    ] {
+        apply(
+            &mut base,
+            &mut backend,
+            LocalChange::set(Path::root().key(key), value),
+        );
+    }
+    let base_patch = backend.get_patch().unwrap();
+
+    let mut changes = Vec::new();
+    for actor in 1..=2_u8 {
+        let mut doc = Frontend::new_with_actor_id(&[actor]);
+        doc.apply_patch(base_patch.clone()).unwrap();
+        let name = format!("{}", actor);
+        let (_, change) = doc
+            .change::<_, _, InvalidChangeRequest>(None, |d| {
+                d.add_change(LocalChange::set(Path::root().key("title"), name.as_str()))?;
+                d.add_change(LocalChange::set(
+                    Path::root().key("settings").key("theme"),
+                    name.as_str(),
+                ))?;
+                d.add_change(LocalChange::set(
+                    Path::root().key("todos").index(1),
+                    name.as_str(),
+                ))?;
+                d.add_change(LocalChange::set(
+                    Path::root().key("notes").index(0),
+                    name.as_str(),
+                ))
+            })
+            .unwrap();
+        changes.push(change.unwrap().into());
+    }
+    backend.apply_changes(changes).unwrap();
+
+    let mut doc = Frontend::new_with_actor_id(&[3]);
+    doc.apply_patch(backend.get_patch().unwrap()).unwrap();
+    (doc, backend)
+}
+
+fn opid(counter: u64, actor: u8) -> amp::OpId {
+    amp::OpId(counter, amp::ActorId::from(&[actor][..]))
+}
+
+#[test]
+fn conflicts_are_listed_with_their_paths() {
+    let (doc, _) = conflicted();
+    let conflicts = doc.conflicts().collect::<Vec<_>>();
+    let paths = conflicts.iter().map(|c| c.path.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        vec![
+            Path::root().key("notes").index(0),
+            Path::root().key("settings").key("theme"),
+            Path::root().key("title"),
+            Path::root().key("todos").index(1),
+        ]
+    );
+    assert_eq!(
+        conflicts[2],
+        Conflict {
+            path: Path::root().key("title"),
+            values: vec![(opid(9, 1), "1".into()), (opid(9, 2), "2".into())],
+        }
+    );
+    assert_eq!(conflicts[2].default_opid(), &opid(9, 2));
+    for conflict in &conflicts {
+        let expected = conflict.values.iter().cloned().collect::<HashMap<_, _>>();
+        assert_eq!(doc.get_conflicts(&conflict.path), Some(expected));
+    }
+}
+
+#[test]
+fn resolving_conflicts_keeps_the_chosen_value() {
+    let (mut doc, mut backend) = conflicted();
+    let winners = doc
+        .conflicts()
+        .map(|c| (c.path, c.values[0].clone()))
+        .collect::<Vec<_>>();
+    for (path, (winner, _)) in &winners {
+        let change = apply(
+            &mut doc,
+            &mut backend,
+            LocalChange::resolve_conflict(path.clone(), winner.clone()),
+        );
+        assert_eq!(change.operations.len(), 1);
+    }
+    assert_eq!(doc.conflicts().count(), 0);
+    for (path, (_, value)) in &winners {
+        assert_eq!(doc.get_value(path).as_ref(), Some(value));
+    }
+
+    // the backend agrees
+    let mut reloaded = Frontend::new();
+    reloaded.apply_patch(backend.get_patch().unwrap()).unwrap();
+    assert_eq!(reloaded.conflicts().count(), 0);
+    assert_eq!(reloaded.state(), doc.state());
+}
+
+#[test]
+fn objects_keep_their_identity_when_their_conflict_is_resolved() {
+    let mut backend = Backend::new();
+    let mut changes = Vec::new();
+    for actor in 1..=2_u8 {
+        let mut doc = Frontend::new_with_actor_id(&[actor]);
+        let (_, change) = doc
+            .change::<_, _, InvalidChangeRequest>(None, |d| {
+                d.add_change(LocalChange::set(
+                    Path::root().key("profile"),
+                    Value::Map(HashMap::new()),
+                ))?;
+                d.add_change(LocalChange::set(
+                    Path::root().key("profile").key("name"),
+                    format!("{}", actor).as_str(),
+                ))
+            })
+            .unwrap();
+        changes.push(change.unwrap().into());
+    }
+    backend.apply_changes(changes).unwrap();
+    let mut doc = Frontend::new_with_actor_id(&[3]);
+    doc.apply_patch(backend.get_patch().unwrap()).unwrap();
+    let profile = Path::root().key("profile");
+    assert_eq!(doc.get_conflicts(&profile).unwrap().len(), 2);
+
+    apply(
+        &mut doc,
+        &mut backend,
+        LocalChange::resolve_conflict(profile.clone(), opid(1, 1)),
+    );
+    assert_eq!(
+        doc.get_object_id(&profile),
+        Some(amp::ObjectId::from(opid(1, 1)))
+    );
+    // later changes edit the object which was kept
+    apply(
+        &mut doc,
+        &mut backend,
+        LocalChange::set(
+            profile.clone().key("age"),
+            Value::Primitive(Primitive::Int(3)),
+        ),
+    );
+    let mut reloaded = Frontend::new();
+    reloaded.apply_patch(backend.get_patch().unwrap()).unwrap();
+    assert_eq!(reloaded.state(), doc.state());
+    assert_eq!(
+        reloaded.get_value(&profile.key("name")),
+        Some(Value::from("1"))
+    );
+}
+
+#[test]
+fn resolving_with_an_unknown_value_fails() {
+    let (mut doc, _) = conflicted();
+    let result = doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.add_change(LocalChange::resolve_conflict(
+            Path::root().key("title"),
+            opid(1, 9),
+        ))
+    });
+    assert_eq!(
+        result,
+        Err(InvalidChangeRequest::NoSuchConflictingValue {
+            path: Path::root().key("title"),
+            opid: opid(1, 9),
+        })
+    );
+    let result = doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.add_change(LocalChange::resolve_conflict(
+            Path::root().key("missing"),
+            opid(8, 1),
+        ))
+    });
+    assert_eq!(
+        result,
+        Err(InvalidChangeRequest::NoSuchPathError {
+            path: Path::root().key("missing"),
+        })
+    );
+}
@@ -1,8 +1,10 @@
 use std::convert::TryInto;
 
 use amp::RootDiff;
-use automerge_backend::Backend;
-use automerge_frontend::{Frontend, InvalidChangeRequest, LocalChange, Path, Primitive, Value};
+use automerge_backend::{Backend, CursorBias};
+use automerge_frontend::{
+    Cursor, Frontend, InvalidChangeRequest, LocalChange, Path, Primitive, Value,
+};
 use automerge_protocol as amp;
 use maplit::hashmap;
 use unicode_segmentation::UnicodeSegmentation;
@@ -289,3 +291,75 @@ fn test_delete_cursor_and_adding_again() {
 }
 
 //TODO test removing a cursors
+
+/// A backend with "abcde" in `text` and a cursor pointing at the "c", after the elements in
+/// `deleted` have been removed, and a frontend with the resulting patch
+fn cursor_after_deleting(bias: CursorBias, deleted: &[u32]) -> (Frontend, Cursor) {
+    let mut writer = Frontend::new_with_actor_id(&[1]);
+    let mut backend = Backend::new();
+    backend.set_cursor_bias(bias);
+    let (_, change) = writer
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(
+                Path::root().key("text"),
+                Value::Text("abcde".graphemes(true).map(|s| s.into()).collect()),
+            ))?;
+            let cursor = d
+                .cursor_to_path(&Path::root().key("text").index(2))
+                .unwrap();
+            d.add_change(LocalChange::set(Path::root().key("cursor"), cursor))
+        })
+        .unwrap();
+    backend.apply_changes(vec![change.unwrap().into()]).unwrap();
+    for index in deleted {
+        let (_, change) = writer
+            .change::<_, _, InvalidChangeRequest>(None, |d| {
+                d.add_change(LocalChange::delete(Path::root().key("text").index(*index)))
+            })
+            .unwrap();
+        backend.apply_changes(vec![change.unwrap().into()]).unwrap();
+    }
+    let mut reader = Frontend::new();
+    reader.apply_patch(backend.get_patch().unwrap()).unwrap();
+    let cursor = match reader.get_value(&Path::root().key("cursor")) {
+        Some(Value::Primitive(Primitive::Cursor(c))) => c,
+        _ => panic!("value was not a cursor"),
+    };
+    (reader, cursor)
+}
+
+#[test]
+fn test_cursor_on_deleted_element_resolves_to_neighbour() {
+    // deleting the "c"
+    let (frontend, cursor) = cursor_after_deleting(CursorBias::Right, &[2]);
+    assert_eq!(cursor.index, 2);
+    assert_eq!(frontend.resolve_cursor(&cursor), Some(2));
+    let (frontend, cursor) = cursor_after_deleting(CursorBias::Left, &[2]);
+    assert_eq!(cursor.index, 1);
+    assert_eq!(frontend.resolve_cursor(&cursor), Some(1));
+
+    // deleting "cde" leaves no neighbour on the right
+    let (frontend, cursor) = cursor_after_deleting(CursorBias::Right, &[2, 2, 2]);
+    assert_eq!(frontend.resolve_cursor(&cursor), Some(1));
+    // deleting "abc" leaves no neighbour on the left
+    let (frontend, cursor) = cursor_after_deleting(CursorBias::Left, &[0, 0, 0]);
+    assert_eq!(frontend.resolve_cursor(&cursor), Some(0));
+    // deleting everything leaves nothing to point at
+    let (frontend, cursor) = cursor_after_deleting(CursorBias::Left, &[0, 0, 0, 0, 0]);
+    assert_eq!(frontend.resolve_cursor(&cursor), None);
+}
+
+#[test]
+fn test_resolve_cursor_follows_local_changes() {
+    let (mut frontend, cursor) = cursor_after_deleting(CursorBias::Right, &[]);
+    assert_eq!(frontend.resolve_cursor(&cursor), Some(2));
+    frontend
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::insert(
+                Path::root().key("text").index(0),
+                "z".into(),
+            ))
+        })
+        .unwrap();
+    assert_eq!(frontend.resolve_cursor(&cursor), Some(3));
+}
@@ -907,3 +907,36 @@ fn test_inserts_at_end_of_lists() {
     });
     assert_eq!(value, expected_value);
 }
+
+#[test]
+fn test_inserting_a_map_into_a_list_sets_its_keys() {
+    let mut doc = Frontend::new();
+    doc.change::<_, _, InvalidChangeRequest>(None, |doc| {
+        doc.add_change(LocalChange::set(
+            Path::root().key("birds"),
+            Value::List(Vec::new()),
+        ))
+    })
+    .unwrap();
+
+    let request = doc
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::insert(
+                Path::root().key("birds").index(0),
+                hashmap! {"name" => "goldfinch", "colour" => "yellow"}.into(),
+            ))
+        })
+        .unwrap()
+        .1
+        .unwrap();
+
+    let map_id: amp::ObjectId = doc.actor_id.op_id_at(2).into();
+    assert_eq!(request.operations.len(), 3);
+    assert!(request.operations[0].insert);
+    for op in &request.operations[1..] {
+        assert_eq!(op.obj, map_id);
+        assert!(!op.insert);
+        assert!(matches!(op.action, amp::OpType::Set(_)));
+        assert!(matches!(op.key, amp::Key::Map(_)));
+    }
+}
@@ -0,0 +1,142 @@
+use automerge_backend::Backend;
+use automerge_frontend::{
+    Frontend, InvalidChangeRequest, JsonPatchError, JsonPatchOp, LocalChange, MutableDocument,
+    Path, Primitive, Value,
+};
+use serde_json::json;
+
+type Edit = fn(&mut dyn MutableDocument) -> Result<(), InvalidChangeRequest>;
+
+fn ops(json: serde_json::Value) -> Vec<JsonPatchOp> {
+    serde_json::from_value(json).unwrap()
+}
+
+fn doc_with(json: serde_json::Value) -> Frontend {
+    let mut doc = Frontend::new();
+    doc.apply_json_patch(&ops(json!([{"op": "replace", "path": "", "value": json}])))
+        .unwrap();
+    doc
+}
+
+#[test]
+fn apply_json_patch_operations() {
+    let mut doc = doc_with(json!({"birds": ["magpie", "wren"], "fish": {"count": 3}}));
+    doc.apply_json_patch(&ops(json!([
+        {"op": "add", "path": "/birds/1", "value": "sparrow"},
+        {"op": "add", "path": "/birds/-", "value": "robin"},
+        {"op": "remove", "path": "/birds/0"},
+        {"op": "replace", "path": "/fish/count", "value": 4},
+        {"op": "copy", "from": "/fish", "path": "/more~1fish"},
+        {"op": "move", "from": "/birds/2", "path": "/favourite"},
+        {"op": "test", "path": "/more~1fish/count", "value": 4.0},
+    ])))
+    .unwrap();
+    assert_eq!(
+        doc.state().to_json(),
+        json!({
+            "birds": ["sparrow", "wren"],
+            "fish": {"count": 4.0},
+            "more/fish": {"count": 4.0},
+            "favourite": "robin",
+        })
+    );
+}
+
+#[test]
+fn failed_test_leaves_the_document_unchanged() {
+    let mut doc = doc_with(json!({"birds": ["magpie"]}));
+    let before = doc.state().clone();
+    let result = doc.apply_json_patch(&ops(json!([
+        {"op": "add", "path": "/birds/-", "value": "wren"},
+        {"op": "test", "path": "/birds/0", "value": "sparrow"},
+    ])));
+    assert_eq!(
+        result,
+        Err(JsonPatchError::TestFailed {
+            path: "/birds/0".to_string(),
+            expected: json!("sparrow"),
+        })
+    );
+    assert_eq!(doc.state(), &before);
+}
+
+#[test]
+fn invalid_operations_are_rejected() {
+    let mut doc = doc_with(json!({"birds": ["magpie"]}));
+    assert_eq!(
+        doc.apply_json_patch(&ops(json!([{"op": "remove", "path": "/fish"}]))),
+        Err(JsonPatchError::NoSuchPath("/fish".to_string()))
+    );
+    assert_eq!(
+        doc.apply_json_patch(&ops(json!([{"op": "remove", "path": "/birds/01"}]))),
+        Err(JsonPatchError::InvalidPointer("/birds/01".to_string()))
+    );
+    assert!(matches!(
+        doc.apply_json_patch(&ops(
+            json!([{"op": "move", "from": "/birds", "path": "/birds/0"}])
+        )),
+        Err(JsonPatchError::MoveIntoChild { .. })
+    ));
+}
+
+#[test]
+fn applied_patches_convert_to_json_patch() {
+    let mut backend = Backend::new();
+    let mut remote = Frontend::new();
+    let mut doc = Frontend::new();
+    let mut mirror = Frontend::new();
+
+    let edits: Vec<Edit> = vec![
+        |d| {
+            d.add_change(LocalChange::set(
+                Path::root().key("birds"),
+                Value::from_json(&json!({"list": ["magpie"], "name": "birds"})),
+            ))?;
+            d.add_change(LocalChange::set(
+                Path::root().key("count"),
+                Value::Primitive(Primitive::Counter(1)),
+            ))?;
+            d.add_change(LocalChange::set(
+                Path::root().key("text"),
+                Value::Text(vec!["a".into()]),
+            ))
+        },
+        |d| {
+            let list = Path::root().key("birds").key("list");
+            d.add_change(LocalChange::insert(list.clone().index(1), "wren".into()))?;
+            d.add_change(LocalChange::delete(list.index(0)))?;
+            d.add_change(LocalChange::delete(Path::root().key("birds").key("name")))?;
+            d.add_change(LocalChange::increment_by(Path::root().key("count"), 2))?;
+            d.add_change(LocalChange::insert(
+                Path::root().key("text").index(1),
+                "b".into(),
+            ))
+        },
+    ];
+
+    let mut exported = Vec::new();
+    for edit in edits {
+        let change = remote.change(None, edit).unwrap().1.unwrap();
+        let patch = backend.apply_changes(vec![change.into()]).unwrap();
+        let json_patch = doc.apply_patch_with_json_patch(patch).unwrap();
+        mirror.apply_json_patch(&json_patch).unwrap();
+        remote.apply_patch(backend.get_patch().unwrap()).unwrap();
+        exported.push(json_patch);
+    }
+
+    assert_eq!(
+        serde_json::to_value(&exported[1]).unwrap(),
+        json!([
+            {"op": "add", "path": "/birds/list/1", "value": "wren"},
+            {"op": "remove", "path": "/birds/list/0"},
+            {"op": "remove", "path": "/birds/name"},
+            {"op": "replace", "path": "/count", "value": 3},
+            {"op": "replace", "path": "/text", "value": "ab"},
+        ])
+    );
+    // JSON has no counters, so compare the documents as JSON
+    assert_eq!(
+        mirror.state().to_json(),
+        Value::from_json(&doc.state().to_json()).to_json()
+    );
+}
@@ -0,0 +1,327 @@
+use automerge_backend::{Backend, Change};
+use automerge_frontend::{Frontend, InvalidChangeRequest, LocalChange, Path, Primitive, Value};
+use automerge_protocol as amp;
+use maplit::hashmap;
+
+fn list(items: &[&str]) -> Value {
+    Value::List(items.iter().map(|i| Value::from(*i)).collect())
+}
+
+/// The changes which create a document with `root` as its root
+fn base(root: Value) -> Vec<Change> {
+    let mut doc = Frontend::new_with_actor_id(&[0]);
+    let (_, change) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(Path::root(), root))
+        })
+        .unwrap();
+    vec![change.unwrap().into()]
+}
+
+/// A peer with the document made by `base`
+fn peer(actor: u8, base: &[Change]) -> Frontend {
+    let mut backend = Backend::new();
+    let mut doc = Frontend::new_with_actor_id(&[actor]);
+    doc.apply_patch(backend.apply_changes(base.to_vec()).unwrap())
+        .unwrap();
+    doc
+}
+
+fn make_change(doc: &mut Frontend, change: LocalChange) -> amp::Change {
+    doc.change::<_, _, InvalidChangeRequest>(None, |d| d.add_change(change))
+        .unwrap()
+        .1
+        .unwrap()
+}
+
+/// Apply `changes` one at a time on top of `base`, checking that the state built from the
+/// incremental patches is the same as the state built from scratch and from a saved document
+fn merge(base: &[Change], changes: &[&amp::Change]) -> Value {
+    let mut backend = Backend::new();
+    let mut doc = Frontend::new();
+    doc.apply_patch(backend.apply_changes(base.to_vec()).unwrap())
+        .unwrap();
+    for change in changes {
+        let patch = backend
+            .apply_changes(vec![(*change).clone().into()])
+            .unwrap();
+        doc.apply_patch(patch).unwrap();
+    }
+
+    let mut from_scratch = Frontend::new();
+    from_scratch
+        .apply_patch(backend.get_patch().unwrap())
+        .unwrap();
+    assert_eq!(doc.state(), from_scratch.state());
+
+    let loaded = Backend::load(backend.save().unwrap()).unwrap();
+    let mut from_saved = Frontend::new();
+    from_saved.apply_patch(loaded.get_patch().unwrap()).unwrap();
+    assert_eq!(doc.state(), from_saved.state());
+    doc.state().clone()
+}
+
+#[test]
+fn move_within_a_list() {
+    let base = base(Value::from(
+        hashmap! {"todos" => list(&["a", "b", "c", "d"])},
+    ));
+    let mut doc = peer(1, &base);
+    let todos = Path::root().key("todos");
+    let change = make_change(
+        &mut doc,
+        LocalChange::move_to(todos.clone().index(0), todos.clone().index(2)),
+    );
+    assert_eq!(doc.get_value(&todos), Some(list(&["b", "c", "a", "d"])));
+    assert_eq!(change.operations.len(), 1);
+    assert!(matches!(change.operations[0].action, amp::OpType::Move(_)));
+
+    let merged = merge(&base, &[&change]);
+    assert_eq!(merged, doc.state().clone());
+
+    // the patch for the local change leaves the optimistic state as it was
+    let mut backend = Backend::new();
+    backend.apply_changes(base).unwrap();
+    let (patch, _) = backend.apply_local_change(change).unwrap();
+    doc.apply_patch(patch).unwrap();
+    assert_eq!(doc.state(), &merged);
+}
+
+#[test]
+fn concurrent_moves_of_the_same_element_keep_one_copy() {
+    let base = base(Value::from(
+        hashmap! {"todos" => list(&["a", "b", "c", "d"])},
+    ));
+    let todos = Path::root().key("todos");
+    let mut first = peer(1, &base);
+    let mut second = peer(2, &base);
+    let to_end = make_change(
+        &mut first,
+        LocalChange::move_to(todos.clone().index(0), todos.clone().index(3)),
+    );
+    let to_middle = make_change(
+        &mut second,
+        LocalChange::move_to(todos.clone().index(0), todos.clone().index(1)),
+    );
+
+    let one_way = merge(&base, &[&to_end, &to_middle]);
+    let other_way = merge(&base, &[&to_middle, &to_end]);
+    assert_eq!(one_way, other_way);
+    // the moves have the same counter so the move by the greater actor wins
+    assert_eq!(
+        one_way,
+        Value::from(hashmap! {"todos" => list(&["b", "a", "c", "d"])})
+    );
+}
+
+#[test]
+fn moves_of_objects_between_parents_do_not_create_cycles() {
+    let base = base(Value::from(hashmap! {
+        "a" => Value::from(hashmap! {"name" => "a"}),
+        "b" => Value::from(hashmap! {"name" => "b"}),
+    }));
+    let mut first = peer(1, &base);
+    let mut second = peer(2, &base);
+    let a_into_b = make_change(
+        &mut first,
+        LocalChange::move_to(Path::root().key("a"), Path::root().key("b").key("child")),
+    );
+    let b_into_a = make_change(
+        &mut second,
+        LocalChange::move_to(Path::root().key("b"), Path::root().key("a").key("child")),
+    );
+
+    let one_way = merge(&base, &[&a_into_b, &b_into_a]);
+    let other_way = merge(&base, &[&b_into_a, &a_into_b]);
+    assert_eq!(one_way, other_way);
+    // the move with the lower ID takes effect first, so the other would make a cycle
+    assert_eq!(
+        one_way,
+        Value::from(hashmap! {
+            "b" => Value::from(hashmap! {
+                "name" => Value::from("b"),
+                "child" => Value::from(hashmap! {"name" => "a"}),
+            }),
+        })
+    );
+}
+
+#[test]
+fn edits_follow_a_moved_value() {
+    let base = base(Value::from(hashmap! {
+        "todo" => Value::from(hashmap! {"done" => Value::Primitive(Primitive::Boolean(false))}),
+        "count" => Value::Primitive(Primitive::Counter(1)),
+        "archive" => Value::Map(std::collections::HashMap::new()),
+    }));
+    let mut first = peer(1, &base);
+    let mut second = peer(2, &base);
+    let archive = make_change(
+        &mut first,
+        LocalChange::move_to(
+            Path::root().key("todo"),
+            Path::root().key("archive").key("todo"),
+        ),
+    );
+    let moved_count = make_change(
+        &mut first,
+        LocalChange::move_to(Path::root().key("count"), Path::root().key("total")),
+    );
+    let done = make_change(
+        &mut second,
+        LocalChange::set(
+            Path::root().key("todo").key("done"),
+            Value::Primitive(Primitive::Boolean(true)),
+        ),
+    );
+    let increment = make_change(
+        &mut second,
+        LocalChange::increment_by(Path::root().key("count"), 2),
+    );
+
+    let expected = Value::from(hashmap! {
+        "archive" => Value::from(hashmap! {
+            "todo" => Value::from(hashmap! {"done" => Value::Primitive(Primitive::Boolean(true))}),
+        }),
+        "total" => Value::Primitive(Primitive::Counter(3)),
+    });
+    assert_eq!(
+        merge(&base, &[&archive, &moved_count, &done, &increment]),
+        expected
+    );
+    assert_eq!(
+        merge(&base, &[&done, &increment, &archive, &moved_count]),
+        expected
+    );
+}
+
+#[test]
+fn deleting_a_value_which_was_moved_concurrently_deletes_it() {
+    let base = base(Value::from(hashmap! {"todos" => list(&["a", "b", "c"])}));
+    let todos = Path::root().key("todos");
+    let mut first = peer(1, &base);
+    let mut second = peer(2, &base);
+    let moved = make_change(
+        &mut first,
+        LocalChange::move_to(todos.clone().index(0), todos.clone().index(2)),
+    );
+    let deleted = make_change(&mut second, LocalChange::delete(todos.clone().index(0)));
+
+    let expected = Value::from(hashmap! {"todos" => list(&["b", "c"])});
+    assert_eq!(merge(&base, &[&moved, &deleted]), expected);
+    assert_eq!(merge(&base, &[&deleted, &moved]), expected);
+}
+
+#[test]
+fn invalid_moves_are_rolled_back() {
+    let base = base(Value::from(hashmap! {
+        "a" => Value::from(hashmap! {"todos" => list(&["x"])}),
+        "text" => Value::Text(vec!["h".into(), "i".into()]),
+    }));
+    let mut doc = peer(1, &base);
+    let before = doc.state().clone();
+
+    let result = doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.add_change(LocalChange::move_to(
+            Path::root().key("a"),
+            Path::root().key("a").key("todos").index(0),
+        ))
+    });
+    assert_eq!(
+        result,
+        Err(InvalidChangeRequest::MoveIntoItself {
+            from: Path::root().key("a"),
+            to: Path::root().key("a").key("todos").index(0),
+        })
+    );
+
+    let result = doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.add_change(LocalChange::move_to(
+            Path::root().key("a").key("todos").index(0),
+            Path::root().key("text").index(0),
+        ))
+    });
+    assert_eq!(
+        result,
+        Err(InvalidChangeRequest::MoveInTextObject {
+            path: Path::root().key("text").index(0),
+        })
+    );
+    assert_eq!(doc.state(), &before);
+}
+
+#[test]
+fn compacting_moves_keeps_the_document_the_same() {
+    let base = base(Value::from(
+        hashmap! {"todos" => list(&["a", "b", "c", "d"])},
+    ));
+    let todos = Path::root().key("todos");
+    let mut first = peer(1, &base);
+    let mut second = peer(2, &base);
+    let to_end = make_change(
+        &mut first,
+        LocalChange::move_to(todos.clone().index(0), todos.clone().index(3)),
+    );
+    let to_middle = make_change(
+        &mut second,
+        LocalChange::move_to(todos.clone().index(1), todos.clone().index(2)),
+    );
+
+    let mut compacted = Backend::new();
+    compacted.apply_changes(base.clone()).unwrap();
+    compacted
+        .apply_changes(vec![to_end.clone().into(), to_middle.clone().into()])
+        .unwrap();
+    // both peers have seen both moves
+    compacted.compact_moves(&compacted.get_heads());
+
+    // a later move depends on the compacted ones, so it is applied after them
+    let mut third = Frontend::new_with_actor_id(&[3]);
+    third.apply_patch(compacted.get_patch().unwrap()).unwrap();
+    let later = make_change(
+        &mut third,
+        LocalChange::move_to(todos.clone().index(3), todos.clone().index(0)),
+    );
+    compacted.apply_changes(vec![later.clone().into()]).unwrap();
+
+    let mut doc = Frontend::new();
+    doc.apply_patch(compacted.get_patch().unwrap()).unwrap();
+    assert_eq!(doc.state(), &merge(&base, &[&to_end, &to_middle, &later]));
+}
+
+#[test]
+fn changes_with_moves_are_chunk_types_older_versions_reject() {
+    // the chunk types which predate moves
+    let known = [0, 1, 2];
+    let message = "x".repeat(300);
+    let base = base(Value::from(
+        hashmap! {"todos" => list(&["a", "b", "c", "d"])},
+    ));
+    assert!(known.contains(&base[0].raw_bytes()[8]));
+
+    let mut doc = peer(1, &base);
+    let todos = Path::root().key("todos");
+    let change = doc
+        .change::<_, _, InvalidChangeRequest>(Some(message), |d| {
+            d.add_change(LocalChange::move_to(
+                todos.clone().index(0),
+                todos.clone().index(2),
+            ))
+        })
+        .unwrap()
+        .1
+        .unwrap();
+    let mut change = Change::from(change);
+    assert!(!known.contains(&change.raw_bytes()[8]));
+    let hash = change.hash;
+    change.compress();
+    assert!(!known.contains(&change.raw_bytes()[8]));
+    let decoded = Change::from_bytes(change.raw_bytes().to_vec()).unwrap();
+    assert_eq!(decoded.hash, hash);
+
+    let mut backend = Backend::new();
+    backend.apply_changes(base).unwrap();
+    backend.apply_changes(vec![decoded]).unwrap();
+    let saved = backend.save().unwrap();
+    assert!(!known.contains(&saved[8]));
+    assert_eq!(Backend::load(saved).unwrap().get_heads(), vec![hash]);
+}
@@ -209,3 +209,85 @@ fn test_delete_non_existent_map_key() {
 
     assert_eq!(cr, InvalidChangeRequest::NoSuchPathError { path })
 }
+
+fn text_value(s: &str) -> Value {
+    Value::Text(s.chars().map(|c| c.to_string().into()).collect())
+}
+
+#[test]
+fn test_update_text_only_changes_differing_characters() {
+    let mut frontend = Frontend::new();
+    let text = Path::root().key("text");
+    frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::set(text.clone(), text_value("the quick fox")))
+        })
+        .unwrap();
+
+    let (_, change) = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.update_text(&text, "a quick brown fox")
+        })
+        .unwrap();
+    // "the" becomes "a" and "brown " is inserted
+    assert_eq!(change.unwrap().operations.len(), 3 + 1 + 6);
+    assert_eq!(
+        frontend.get_value(&text),
+        Some(text_value("a quick brown fox"))
+    );
+
+    let (_, change) = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.update_text(&text, "a quick brown fox")
+        })
+        .unwrap();
+    assert_eq!(change, None);
+}
+
+#[test]
+fn test_update_text_with_many_edits_only_changes_differing_characters() {
+    let mut frontend = Frontend::new();
+    let text = Path::root().key("text");
+    let old = "a".repeat(2000);
+    frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::set(text.clone(), text_value(&old)))
+        })
+        .unwrap();
+
+    // a "b" after every "a" is 2000 inserts, and none of the "a"s are deleted
+    let new = "ab".repeat(2000);
+    let (_, change) = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| doc.update_text(&text, &new))
+        .unwrap();
+    assert_eq!(change.unwrap().operations.len(), 2000);
+    assert_eq!(frontend.get_value(&text), Some(text_value(&new)));
+}
+
+#[test]
+fn test_update_text_of_non_text_object() {
+    let mut frontend = Frontend::new();
+    frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::set(Path::root().key("bird"), "magpie"))
+        })
+        .unwrap();
+    let result = frontend.change::<_, _, InvalidChangeRequest>(None, |doc| {
+        doc.update_text(&Path::root().key("bird"), "wren")
+    });
+    assert_eq!(
+        result,
+        Err(InvalidChangeRequest::UpdateTextForNonTextObject {
+            path: Path::root().key("bird")
+        })
+    );
+    let result = frontend.change::<_, _, InvalidChangeRequest>(None, |doc| {
+        doc.update_text(&Path::root().key("fish"), "carp")
+    });
+    assert_eq!(
+        result,
+        Err(InvalidChangeRequest::NoSuchPathError {
+            path: Path::root().key("fish")
+        })
+    );
+}
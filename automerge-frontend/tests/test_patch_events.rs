@@ -0,0 +1,273 @@
+use std::convert::TryInto;
+
+use automerge_backend::Backend;
+use automerge_frontend::{
+    Frontend, InvalidChangeRequest, InvalidPatch, LocalChange, MutableDocument, PatchEvent, Path,
+    Primitive, Value,
+};
+use automerge_protocol as amp;
+use maplit::hashmap;
+
+/// A document which receives the changes made by a second, remote, document
+struct Peers {
+    backend: Backend,
+    doc: Frontend,
+    remote: Frontend,
+}
+
+impl Peers {
+    fn new() -> Self {
+        Self {
+            backend: Backend::new(),
+            doc: Frontend::new(),
+            remote: Frontend::new(),
+        }
+    }
+
+    /// Make a change on the remote document, returning the events the local document sees
+    fn remote_change<F>(&mut self, change_closure: F) -> Vec<PatchEvent>
+    where
+        F: FnOnce(&mut dyn MutableDocument) -> Result<(), InvalidChangeRequest>,
+    {
+        let change = self.remote.change(None, change_closure).unwrap().1.unwrap();
+        let patch = self.backend.apply_changes(vec![change.into()]).unwrap();
+        let events = self.doc.apply_patch_with_events(patch).unwrap();
+        self.remote
+            .apply_patch(self.backend.get_patch().unwrap())
+            .unwrap();
+        events
+    }
+}
+
+fn int(i: i64) -> Value {
+    Value::Primitive(Primitive::Int(i))
+}
+
+#[test]
+fn map_changes_produce_put_and_delete_key_events() {
+    let mut peers = Peers::new();
+    let events = peers.remote_change(|d| {
+        d.add_change(LocalChange::set(
+            Path::root().key("birds"),
+            Value::from_json(&serde_json::json!({"wrens": 3})),
+        ))
+    });
+    assert_eq!(
+        events,
+        vec![PatchEvent::Put {
+            path: Path::root().key("birds"),
+            value: Value::from_json(&serde_json::json!({"wrens": 3})),
+        }]
+    );
+
+    let events = peers.remote_change(|d| {
+        d.add_change(LocalChange::set(
+            Path::root().key("birds").key("sparrows"),
+            int(15),
+        ))?;
+        d.add_change(LocalChange::delete(Path::root().key("birds").key("wrens")))
+    });
+    assert_eq!(
+        events,
+        vec![
+            PatchEvent::Put {
+                path: Path::root().key("birds").key("sparrows"),
+                value: int(15),
+            },
+            PatchEvent::DeleteKey {
+                path: Path::root().key("birds").key("wrens"),
+            },
+        ]
+    );
+}
+
+#[test]
+fn counter_increments_produce_increment_events() {
+    let mut peers = Peers::new();
+    peers.remote_change(|d| {
+        d.add_change(LocalChange::set(
+            Path::root().key("count"),
+            Value::Primitive(Primitive::Counter(1)),
+        ))
+    });
+    let events = peers
+        .remote_change(|d| d.add_change(LocalChange::increment_by(Path::root().key("count"), 5)));
+    assert_eq!(
+        events,
+        vec![PatchEvent::Increment {
+            path: Path::root().key("count"),
+            by: 5,
+        }]
+    );
+}
+
+#[test]
+fn list_edits_produce_insert_and_delete_events() {
+    let mut peers = Peers::new();
+    let list = Path::root().key("list");
+    peers.remote_change(|d| d.add_change(LocalChange::set(list.clone(), Value::List(Vec::new()))));
+
+    let events = peers.remote_change(|d| {
+        for i in 0..3 {
+            d.add_change(LocalChange::insert(
+                list.clone().index(i),
+                int(i as i64 + 1),
+            ))?;
+        }
+        Ok(())
+    });
+    assert_eq!(
+        events,
+        vec![PatchEvent::Insert {
+            path: list.clone(),
+            index: 0,
+            values: vec![int(1), int(2), int(3)],
+        }]
+    );
+
+    let events = peers.remote_change(|d| d.add_change(LocalChange::delete(list.clone().index(1))));
+    assert_eq!(
+        events,
+        vec![PatchEvent::Delete {
+            path: list.clone(),
+            index: 1,
+            count: 1,
+        }]
+    );
+
+    let events =
+        peers.remote_change(|d| d.add_change(LocalChange::insert(list.clone().index(0), int(0))));
+    assert_eq!(
+        events,
+        vec![PatchEvent::Insert {
+            path: list,
+            index: 0,
+            values: vec![int(0)],
+        }]
+    );
+    assert_eq!(
+        peers.doc.get_value(&Path::root().key("list")),
+        Some(Value::List(vec![int(0), int(1), int(3)]))
+    );
+}
+
+#[test]
+fn nested_changes_use_indices_after_the_patch() {
+    let mut peers = Peers::new();
+    let list = Path::root().key("list");
+    peers.remote_change(|d| {
+        d.add_change(LocalChange::set(
+            list.clone(),
+            Value::from_json(&serde_json::json!([{"name": "magpie"}])),
+        ))
+    });
+
+    let events = peers.remote_change(|d| {
+        d.add_change(LocalChange::set(
+            list.clone().index(0).key("name"),
+            Value::from("wren"),
+        ))?;
+        d.add_change(LocalChange::insert(list.clone().index(0), int(0)))
+    });
+    assert_eq!(
+        events,
+        vec![
+            PatchEvent::Insert {
+                path: list.clone(),
+                index: 0,
+                values: vec![int(0)],
+            },
+            PatchEvent::Put {
+                path: list.index(1).key("name"),
+                value: Value::from("wren"),
+            },
+        ]
+    );
+}
+
+#[test]
+fn text_edits_produce_insert_events() {
+    let mut peers = Peers::new();
+    let text = Path::root().key("text");
+    peers.remote_change(|d| {
+        d.add_change(LocalChange::set(
+            text.clone(),
+            Value::Text("ab".chars().map(|c| c.to_string().into()).collect()),
+        ))
+    });
+    let events = peers.remote_change(|d| {
+        d.add_change(LocalChange::insert(text.clone().index(1), Value::from("x")))
+    });
+    assert_eq!(
+        events,
+        vec![PatchEvent::Insert {
+            path: text,
+            index: 1,
+            values: vec![Value::from("x")],
+        }]
+    );
+}
+
+#[test]
+fn out_of_range_edits_are_rejected_as_invalid() {
+    let mut peers = Peers::new();
+    let birds = Path::root().key("birds");
+    peers.remote_change(|d| {
+        d.add_change(LocalChange::set(
+            birds.clone(),
+            Value::from(vec!["chaffinch", "goldfinch"]),
+        ))
+    });
+    let before = peers.doc.state().clone();
+    let object_id = peers.doc.get_object_id(&birds).unwrap();
+    let op_id = peers
+        .doc
+        .get_conflicts(&birds)
+        .unwrap()
+        .keys()
+        .next()
+        .unwrap()
+        .clone();
+    let actor = amp::ActorId::random();
+
+    for edit in [
+        amp::DiffEdit::SingleElementInsert {
+            index: 5,
+            elem_id: actor.op_id_at(1).into(),
+            op_id: actor.op_id_at(1),
+            value: amp::Diff::Value("wren".into()),
+        },
+        amp::DiffEdit::MultiElementInsert(amp::MultiElementInsert {
+            index: 5,
+            elem_id: actor.op_id_at(1).into(),
+            values: vec!["wren".into(), "robin".into()].try_into().unwrap(),
+        }),
+        amp::DiffEdit::Remove { index: 5, count: 1 },
+    ]
+    .iter()
+    {
+        let patch = amp::Patch {
+            actor: None,
+            seq: None,
+            max_op: 10,
+            pending_changes: 0,
+            deps: Vec::new(),
+            clock: hashmap! {actor.clone() => 1},
+            diffs: amp::RootDiff {
+                props: hashmap! {
+                    "birds".into() => hashmap! {
+                        op_id.clone() => amp::Diff::List(amp::ListDiff {
+                            object_id: object_id.clone(),
+                            edits: vec![edit.clone()],
+                        })
+                    }
+                },
+            },
+        };
+        assert!(matches!(
+            peers.doc.apply_patch_with_events(patch),
+            Err(InvalidPatch::InvalidIndex { .. })
+        ));
+        assert_eq!(peers.doc.state(), &before);
+    }
+}
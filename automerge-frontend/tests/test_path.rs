@@ -0,0 +1,64 @@
+use automerge_frontend::{InvalidPath, Path};
+
+#[test]
+fn parse_json_pointers() {
+    assert_eq!("".parse::<Path>(), Ok(Path::root()));
+    assert_eq!(
+        "/birds/0/a~1b~0c/01/".parse::<Path>(),
+        Ok(Path::root()
+            .key("birds")
+            .index(0)
+            .key("a/b~c")
+            .key("01")
+            .key(""))
+    );
+}
+
+#[test]
+fn parse_bracketed_paths() {
+    assert_eq!("$".parse::<Path>(), Ok(Path::root()));
+    assert_eq!(
+        r#"$["birds"][10]["say \"hi\" ]["]"#.parse::<Path>(),
+        Ok(Path::root().key("birds").index(10).key(r#"say "hi" ]["#))
+    );
+}
+
+#[test]
+fn reject_invalid_paths() {
+    assert_eq!(
+        "birds".parse::<Path>(),
+        Err(InvalidPath {
+            path: "birds".to_string(),
+            position: 0
+        })
+    );
+    assert_eq!(
+        r#"$["birds"][x]"#.parse::<Path>(),
+        Err(InvalidPath {
+            path: r#"$["birds"][x]"#.to_string(),
+            position: 11
+        })
+    );
+    assert!(r#"$["birds"#.parse::<Path>().is_err());
+    assert!("$[1".parse::<Path>().is_err());
+}
+
+#[test]
+fn display_round_trips() {
+    let paths = vec![
+        Path::root(),
+        Path::root().key("birds").index(0),
+        Path::root().key("a \"quoted\" [key]").key("/0").index(3),
+    ];
+    for path in paths {
+        assert_eq!(path.to_string().parse::<Path>(), Ok(path));
+    }
+    assert_eq!(
+        Path::root().key("birds").index(0).to_string(),
+        r#"$["birds"][0]"#
+    );
+    assert_eq!(
+        Path::root().key("a/b").index(2).to_json_pointer(),
+        "/a~1b/2"
+    );
+}
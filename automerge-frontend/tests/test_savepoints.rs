@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use automerge_backend::Backend;
+use automerge_frontend::{
+    Cursor, Frontend, InvalidChangeRequest, LocalChange, MutableDocument, Path, Primitive,
+    Savepoint, Value,
+};
+use maplit::hashmap;
+
+fn int(i: i64) -> Value {
+    Value::Primitive(Primitive::Int(i))
+}
+
+/// Add `amount` to the balance of `account`, failing if the balance would be negative
+fn transfer(
+    d: &mut dyn MutableDocument,
+    account: &str,
+    amount: i64,
+) -> Result<(), InvalidChangeRequest> {
+    let path = Path::root().key("accounts").key(account);
+    let balance = match d.value_at_path(&path) {
+        Some(Value::Primitive(Primitive::Int(i))) => i,
+        _ => 0,
+    };
+    d.add_change(LocalChange::set(path.clone(), int(balance + amount)))?;
+    if balance + amount < 0 {
+        return Err(InvalidChangeRequest::UnexpectedObjectType {
+            path,
+            expected: "a positive balance",
+        });
+    }
+    Ok(())
+}
+
+#[test]
+fn rolling_back_keeps_earlier_operations() {
+    let mut doc = Frontend::new_with_actor_id(&[1]);
+    let (results, change) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(
+                Path::root().key("accounts"),
+                Value::Map(HashMap::new()),
+            ))?;
+            d.add_change(LocalChange::set(Path::root().key("log"), vec!["opened"]))?;
+            let mut results = Vec::new();
+            for (account, amount) in [("alice", 10), ("bob", -5), ("carol", 3)] {
+                let savepoint = d.savepoint()?;
+                let result = transfer(d, account, amount).and_then(|_| {
+                    d.add_change(LocalChange::insert(
+                        Path::root().key("log").index(1),
+                        account.into(),
+                    ))
+                });
+                if result.is_err() {
+                    d.rollback_to(savepoint)?;
+                }
+                results.push(result.is_ok());
+            }
+            Ok(results)
+        })
+        .unwrap();
+    assert_eq!(results, vec![true, false, true]);
+    let expected = Value::from(hashmap! {
+        "accounts" => Value::from(hashmap! {"alice" => int(10), "carol" => int(3)}),
+        "log" => Value::from(vec!["opened", "carol", "alice"]),
+    });
+    assert_eq!(doc.state(), &expected);
+
+    // the change only contains the operations which were kept, and the backend agrees
+    let change = change.unwrap();
+    assert_eq!(change.operations.len(), 7);
+    let mut backend = Backend::new();
+    backend.apply_local_change(change).unwrap();
+    let mut reloaded = Frontend::new();
+    reloaded.apply_patch(backend.get_patch().unwrap()).unwrap();
+    assert_eq!(reloaded.state(), &expected);
+}
+
+#[test]
+fn rolling_back_everything_makes_no_change() {
+    let mut doc = Frontend::new_with_actor_id(&[1]);
+    let (_, change) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            let savepoint = d.savepoint()?;
+            d.add_change(LocalChange::set(Path::root().key("title"), "draft"))?;
+            d.rollback_to(savepoint)
+        })
+        .unwrap();
+    assert_eq!(change, None);
+    assert_eq!(doc.state(), &Value::Map(HashMap::new()));
+}
+
+#[test]
+fn later_savepoints_are_discarded_by_rolling_back() {
+    let mut doc = Frontend::new_with_actor_id(&[1]);
+    let (_, change) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            let outer = d.savepoint()?;
+            d.add_change(LocalChange::set(Path::root().key("a"), 1))?;
+            let inner = d.savepoint()?;
+            d.add_change(LocalChange::set(Path::root().key("b"), 2))?;
+            d.rollback_to(inner)?;
+            assert_eq!(d.value_at_path(&Path::root().key("b")), None);
+            assert_eq!(d.value_at_path(&Path::root().key("a")), Some(int(1)));
+
+            d.add_change(LocalChange::set(Path::root().key("c"), 3))?;
+            d.rollback_to(outer)?;
+            assert_eq!(
+                d.rollback_to(inner),
+                Err(InvalidChangeRequest::NoSuchSavepoint)
+            );
+            // the outer savepoint can be used again
+            d.add_change(LocalChange::set(Path::root().key("d"), 4))?;
+            d.rollback_to(outer)?;
+            d.add_change(LocalChange::set(Path::root().key("e"), 5))
+        })
+        .unwrap();
+    assert_eq!(doc.state(), &Value::from(hashmap! {"e" => int(5)}));
+    let change = change.unwrap();
+    assert_eq!(change.start_op, 1);
+    assert_eq!(change.operations.len(), 1);
+}
+
+/// A document implemented outside of this crate, which forwards savepoints to the document it
+/// wraps
+struct ForwardingDocument<'a>(&'a mut dyn MutableDocument);
+
+impl<'a> MutableDocument for ForwardingDocument<'a> {
+    fn value_at_path(&self, path: &Path) -> Option<Value> {
+        self.0.value_at_path(path)
+    }
+
+    fn cursor_to_path(&self, path: &Path) -> Option<Cursor> {
+        self.0.cursor_to_path(path)
+    }
+
+    fn add_change(&mut self, change: LocalChange) -> Result<(), InvalidChangeRequest> {
+        self.0.add_change(change)
+    }
+
+    fn savepoint(&mut self) -> Result<Savepoint, InvalidChangeRequest> {
+        self.0.savepoint()
+    }
+
+    fn rollback_to(&mut self, savepoint: Savepoint) -> Result<(), InvalidChangeRequest> {
+        self.0.rollback_to(savepoint)
+    }
+}
+
+#[test]
+fn savepoints_can_be_forwarded_by_other_documents() {
+    let mut doc = Frontend::new_with_actor_id(&[1]);
+    let (_, change) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            let mut d = ForwardingDocument(d);
+            d.add_change(LocalChange::set(Path::root().key("a"), 1))?;
+            let savepoint = d.savepoint()?;
+            d.add_change(LocalChange::set(Path::root().key("b"), 2))?;
+            d.rollback_to(savepoint)
+        })
+        .unwrap();
+    assert_eq!(doc.state(), &Value::from(hashmap! {"a" => int(1)}));
+    assert_eq!(change.unwrap().operations.len(), 1);
+}
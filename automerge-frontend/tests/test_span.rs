@@ -0,0 +1,166 @@
+use automerge_backend::Backend;
+use automerge_frontend::{
+    Bias, Cursor, Frontend, InvalidChangeRequest, LocalChange, Path, Span, Value,
+};
+use automerge_protocol as amp;
+use unicode_segmentation::UnicodeSegmentation;
+
+fn text(s: &str) -> Value {
+    Value::Text(s.graphemes(true).map(|g| g.into()).collect())
+}
+
+fn notes() -> Path {
+    Path::root().key("notes")
+}
+
+fn cursor(doc: &mut Frontend, index: u32) -> Cursor {
+    doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        Ok(d.cursor_to_path(&notes().index(index)).unwrap())
+    })
+    .unwrap()
+    .0
+}
+
+/// A writer and a reader which both have "hello world" in `notes`
+fn documents() -> (Frontend, Frontend, Backend) {
+    let mut backend = Backend::new();
+    let mut writer = Frontend::new_with_actor_id(&[1]);
+    let (_, change) = writer
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(notes(), text("hello world")))
+        })
+        .unwrap();
+    backend.apply_changes(vec![change.unwrap().into()]).unwrap();
+    let mut reader = Frontend::new_with_actor_id(&[2]);
+    reader.apply_patch(backend.get_patch().unwrap()).unwrap();
+    (writer, reader, backend)
+}
+
+/// Make `changes` with `writer` and apply them to `reader`
+fn edit(
+    writer: &mut Frontend,
+    reader: &mut Frontend,
+    backend: &mut Backend,
+    changes: Vec<LocalChange>,
+) {
+    let (_, change) = writer
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            changes.into_iter().try_for_each(|c| d.add_change(c))
+        })
+        .unwrap();
+    let patch = backend.apply_changes(vec![change.unwrap().into()]).unwrap();
+    reader.apply_patch(patch).unwrap();
+}
+
+/// Insert `s` at `index` one grapheme at a time
+fn insert(index: u32, s: &str) -> Vec<LocalChange> {
+    s.graphemes(true)
+        .enumerate()
+        .map(|(i, g)| LocalChange::insert(notes().index(index + i as u32), g.into()))
+        .collect()
+}
+
+fn delete(index: u32) -> Vec<LocalChange> {
+    vec![LocalChange::delete(notes().index(index))]
+}
+
+#[test]
+fn spans_follow_their_elements() {
+    let (mut writer, mut reader, mut backend) = documents();
+    // "world"
+    let (start, end) = (cursor(&mut reader, 6), cursor(&mut reader, 10));
+    let world = reader.add_span(Span::new(start, end));
+    assert_eq!(reader.resolve_span(world), Some(6..11));
+
+    edit(&mut writer, &mut reader, &mut backend, insert(6, "big "));
+    assert_eq!(reader.resolve_span(world), Some(10..15));
+    edit(&mut writer, &mut reader, &mut backend, insert(0, "oh "));
+    assert_eq!(reader.resolve_span(world), Some(13..18));
+    // text inserted at the end of the span is not in it
+    edit(&mut writer, &mut reader, &mut backend, insert(18, "!"));
+    assert_eq!(reader.resolve_span(world), Some(13..18));
+
+    assert!(reader.remove_span(world));
+    assert_eq!(reader.resolve_span(world), None);
+    assert!(!reader.remove_span(world));
+}
+
+#[test]
+fn biases_choose_which_side_of_a_boundary_insertions_go() {
+    let (mut writer, mut reader, mut backend) = documents();
+    // the boundaries of "world" stick to the space before it and the end of the "d"
+    let span = Span {
+        start: cursor(&mut reader, 5),
+        start_bias: Bias::After,
+        end: cursor(&mut reader, 10),
+        end_bias: Bias::After,
+    };
+    let world = reader.add_span(span);
+    assert_eq!(reader.resolve_span(world), Some(6..11));
+
+    edit(&mut writer, &mut reader, &mut backend, insert(6, "big "));
+    assert_eq!(reader.resolve_span(world), Some(6..15));
+}
+
+#[test]
+fn boundaries_stay_where_deleted_elements_were() {
+    let (mut writer, mut reader, mut backend) = documents();
+    let (start, end) = (cursor(&mut reader, 6), cursor(&mut reader, 10));
+    let world = reader.add_span(Span::new(start, end));
+
+    // deleting the "w" moves the start to the "o"
+    edit(&mut writer, &mut reader, &mut backend, delete(6));
+    assert_eq!(reader.resolve_span(world), Some(6..10));
+    // deleting the rest leaves an empty span where "world" was
+    for _ in 0..4 {
+        edit(&mut writer, &mut reader, &mut backend, delete(6));
+    }
+    assert_eq!(reader.get_value(&notes()), Some(text("hello ")));
+    assert_eq!(reader.resolve_span(world), Some(6..6));
+
+    edit(&mut writer, &mut reader, &mut backend, insert(0, "oh "));
+    assert_eq!(reader.resolve_span(world), Some(9..9));
+
+    // deleting the text object means the span can't be resolved
+    edit(
+        &mut writer,
+        &mut reader,
+        &mut backend,
+        vec![LocalChange::delete(notes())],
+    );
+    assert_eq!(reader.resolve_span(world), None);
+}
+
+#[test]
+fn spans_see_local_changes() {
+    let (_, mut reader, mut backend) = documents();
+    let (start, end) = (cursor(&mut reader, 0), cursor(&mut reader, 4));
+    let hello = reader.add_span(Span::new(start, end));
+
+    let (_, change) = reader
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            insert(0, "oh ")
+                .into_iter()
+                .try_for_each(|c| d.add_change(c))
+        })
+        .unwrap();
+    assert_eq!(reader.resolve_span(hello), Some(3..8));
+
+    // the span still resolves once the backend acknowledges the change
+    let patch = backend.apply_local_change(change.unwrap()).unwrap().0;
+    reader.apply_patch(patch).unwrap();
+    assert_eq!(reader.resolve_span(hello), Some(3..8));
+}
+
+#[test]
+fn spans_across_objects_never_resolve() {
+    let (_, mut reader, _) = documents();
+    let start = cursor(&mut reader, 0);
+    let end = Cursor::new(
+        0,
+        amp::ObjectId::Root,
+        amp::OpId(1, amp::ActorId::from(&[9][..])),
+    );
+    let span = reader.add_span(Span::new(start, end));
+    assert_eq!(reader.resolve_span(span), None);
+}
@@ -0,0 +1,154 @@
+use std::{cell::RefCell, rc::Rc};
+
+use automerge_backend::Backend;
+use automerge_frontend::{Frontend, InvalidChangeRequest, LocalChange, Path, Primitive, Value};
+
+/// Subscribe to `path`, returning the values the subscription was called with
+fn record(doc: &mut Frontend, path: Path) -> Rc<RefCell<Vec<Option<Value>>>> {
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let recorded = calls.clone();
+    doc.subscribe(path, move |value| {
+        recorded.borrow_mut().push(value.cloned())
+    });
+    calls
+}
+
+fn set(doc: &mut Frontend, path: Path, value: Value) -> automerge_protocol::Change {
+    doc.change::<_, _, InvalidChangeRequest>(None, |d| d.add_change(LocalChange::set(path, value)))
+        .unwrap()
+        .1
+        .unwrap()
+}
+
+#[test]
+fn local_changes_notify_overlapping_subscriptions() {
+    let mut doc = Frontend::new();
+    let root = record(&mut doc, Path::root());
+    let birds = record(&mut doc, Path::root().key("birds"));
+    let wrens = record(&mut doc, Path::root().key("birds").key("wrens"));
+    let fish = record(&mut doc, Path::root().key("fish"));
+
+    set(
+        &mut doc,
+        Path::root().key("birds"),
+        Value::from_json(&serde_json::json!({"wrens": 3})),
+    );
+    assert_eq!(root.borrow().len(), 1);
+    assert_eq!(
+        *birds.borrow(),
+        vec![Some(Value::from_json(&serde_json::json!({"wrens": 3})))]
+    );
+    assert_eq!(
+        *wrens.borrow(),
+        vec![Some(Value::Primitive(Primitive::F64(3.0)))]
+    );
+    assert!(fish.borrow().is_empty());
+
+    doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.add_change(LocalChange::delete(Path::root().key("birds")))
+    })
+    .unwrap();
+    assert_eq!(wrens.borrow().last(), Some(&None));
+    assert!(fish.borrow().is_empty());
+}
+
+#[test]
+fn remote_patches_notify_only_changed_paths() {
+    let mut backend = Backend::new();
+    let mut doc = Frontend::new();
+    let mut remote = Frontend::new();
+
+    let change = set(
+        &mut remote,
+        Path::root().key("birds"),
+        Value::from_json(&serde_json::json!({"wrens": 3, "sparrows": 15, "list": [1, 2]})),
+    );
+    doc.apply_patch(backend.apply_changes(vec![change.into()]).unwrap())
+        .unwrap();
+    remote.apply_patch(backend.get_patch().unwrap()).unwrap();
+
+    let birds = record(&mut doc, Path::root().key("birds"));
+    let wrens = record(&mut doc, Path::root().key("birds").key("wrens"));
+    let sparrows = record(&mut doc, Path::root().key("birds").key("sparrows"));
+    let second = record(&mut doc, Path::root().key("birds").key("list").index(1));
+
+    let change = set(
+        &mut remote,
+        Path::root().key("birds").key("wrens"),
+        Value::Primitive(Primitive::Int(4)),
+    );
+    doc.apply_patch(backend.apply_changes(vec![change.into()]).unwrap())
+        .unwrap();
+    assert_eq!(birds.borrow().len(), 1);
+    assert_eq!(
+        *wrens.borrow(),
+        vec![Some(Value::Primitive(Primitive::Int(4)))]
+    );
+    assert!(sparrows.borrow().is_empty());
+    assert!(second.borrow().is_empty());
+
+    // inserting at the start of the list moves every element along
+    let change = remote
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::insert(
+                Path::root().key("birds").key("list").index(0),
+                Value::Primitive(Primitive::Int(0)),
+            ))
+        })
+        .unwrap()
+        .1
+        .unwrap();
+    doc.apply_patch(backend.apply_changes(vec![change.into()]).unwrap())
+        .unwrap();
+    assert_eq!(
+        *second.borrow(),
+        vec![Some(Value::Primitive(Primitive::F64(1.0)))]
+    );
+    assert!(sparrows.borrow().is_empty());
+}
+
+#[test]
+fn remote_patches_are_reported_once_in_flight_requests_are_reconciled() {
+    let mut backend = Backend::new();
+    let mut doc = Frontend::new();
+    let mut remote = Frontend::new();
+    let fish = record(&mut doc, Path::root().key("fish"));
+
+    let local_change = set(
+        &mut doc,
+        Path::root().key("birds"),
+        Value::Primitive(Primitive::Int(1)),
+    );
+    let remote_change = set(
+        &mut remote,
+        Path::root().key("fish"),
+        Value::Primitive(Primitive::Int(2)),
+    );
+    doc.apply_patch(backend.apply_changes(vec![remote_change.into()]).unwrap())
+        .unwrap();
+    // the remote change is not visible yet
+    assert!(fish.borrow().is_empty());
+
+    let (patch, _) = backend.apply_local_change(local_change).unwrap();
+    doc.apply_patch(patch).unwrap();
+    assert_eq!(
+        *fish.borrow(),
+        vec![Some(Value::Primitive(Primitive::Int(2)))]
+    );
+}
+
+#[test]
+fn unsubscribed_callbacks_are_not_called() {
+    let mut doc = Frontend::new();
+    let calls = Rc::new(RefCell::new(0));
+    let counter = calls.clone();
+    let id = doc.subscribe(Path::root().key("birds"), move |_| {
+        *counter.borrow_mut() += 1
+    });
+
+    set(&mut doc, Path::root().key("birds"), Value::from("magpie"));
+    assert!(doc.unsubscribe(id));
+    set(&mut doc, Path::root().key("birds"), Value::from("wren"));
+    assert_eq!(*calls.borrow(), 1);
+    assert!(!doc.unsubscribe(id));
+}
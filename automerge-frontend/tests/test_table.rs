@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use automerge_backend::Backend;
+use automerge_frontend::{Frontend, InvalidChangeRequest, LocalChange, Path, Primitive, Value};
+use maplit::hashmap;
+use smol_str::SmolStr;
+
+fn book(title: &str, year: i64) -> Value {
+    Value::from(hashmap! {
+        "title" => Value::from(title),
+        "year" => Value::Primitive(Primitive::Int(year)),
+    })
+}
+
+/// A document with a table of books, and the row ID of each book
+fn library() -> (Frontend, Vec<SmolStr>) {
+    let mut doc = Frontend::new();
+    let books = Path::root().key("books");
+    let (ids, _) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(
+                books.clone(),
+                Value::Table(HashMap::new()),
+            ))?;
+            Ok(vec![
+                d.table_insert(&books, book("Middlemarch", 1871))?,
+                d.table_insert(&books, book("Emma", 1815))?,
+                d.table_insert(&books, book("Persuasion", 1817))?,
+            ])
+        })
+        .unwrap();
+    (doc, ids)
+}
+
+fn titles(rows: Vec<(&SmolStr, automerge_frontend::value_ref::ValueRef)>) -> Vec<Value> {
+    rows.into_iter()
+        .map(|(_, row)| row.map().unwrap().get("title").unwrap().value())
+        .collect()
+}
+
+#[test]
+fn inserted_rows_get_unique_ids() {
+    let (doc, ids) = library();
+    assert_eq!(ids.len(), 3);
+    assert_ne!(ids[0], ids[1]);
+    assert!(uuid::Uuid::parse_str(&ids[0]).is_ok());
+    assert_eq!(
+        doc.get_value(&Path::root().key("books").key(ids[1].clone())),
+        Some(book("Emma", 1815))
+    );
+
+    // the rows are saved by the backend
+    let mut backend = Backend::new();
+    let mut doc = Frontend::new();
+    let (_, change) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(
+                Path::root().key("books"),
+                Value::Table(HashMap::new()),
+            ))?;
+            d.table_insert(&Path::root().key("books"), book("Emma", 1815))
+        })
+        .unwrap();
+    backend.apply_local_change(change.unwrap()).unwrap();
+    let mut reloaded = Frontend::new();
+    reloaded.apply_patch(backend.get_patch().unwrap()).unwrap();
+    assert_eq!(reloaded.state(), doc.state());
+}
+
+#[test]
+fn rows_can_be_queried() {
+    let (doc, ids) = library();
+    let root = doc.value_ref();
+    let books = root.get("books").unwrap();
+    let books = books.table().unwrap();
+
+    let emma = books.lookup("title", &Value::from("Emma"));
+    assert_eq!(emma.len(), 1);
+    assert_eq!(emma[0].0, &ids[1]);
+    assert!(books.lookup("title", &Value::from("Ulysses")).is_empty());
+
+    let early = books.filter(|_, row| {
+        matches!(
+            row.map()
+                .and_then(|r| r.get("year"))
+                .and_then(|y| y.primitive().and_then(Primitive::int)),
+            Some(y) if y < 1850
+        )
+    });
+    assert_eq!(early.len(), 2);
+
+    assert_eq!(
+        titles(books.sort_by_columns(&["year"])),
+        vec![
+            Value::from("Emma"),
+            Value::from("Persuasion"),
+            Value::from("Middlemarch")
+        ]
+    );
+    assert_eq!(
+        titles(books.sort_by_columns(&["title"])),
+        vec![
+            Value::from("Emma"),
+            Value::from("Middlemarch"),
+            Value::from("Persuasion")
+        ]
+    );
+}
+
+#[test]
+fn rows_must_be_maps_in_tables() {
+    let (mut doc, _) = library();
+    let result = doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.table_insert(&Path::root().key("books"), Value::from("Emma"))
+    });
+    assert_eq!(
+        result,
+        Err(InvalidChangeRequest::InvalidTableRow {
+            path: Path::root().key("books"),
+            row: Value::from("Emma"),
+        })
+    );
+
+    let result = doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.add_change(LocalChange::set(
+            Path::root().key("list"),
+            Value::List(Vec::new()),
+        ))?;
+        d.table_insert(&Path::root().key("list"), book("Emma", 1815))
+    });
+    assert_eq!(
+        result,
+        Err(InvalidChangeRequest::UnexpectedObjectType {
+            path: Path::root().key("list"),
+            expected: "a table",
+        })
+    );
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use automerge_backend::Backend;
+use automerge_frontend::{Frontend, InvalidChangeRequest, LocalChange, Path, Primitive, Value};
+use maplit::hashmap;
+use smol_str::SmolStr;
+
+fn book(author: &str, year: i64) -> Value {
+    Value::from(hashmap! {
+        "author" => Value::from(author),
+        "year" => Value::Primitive(Primitive::Int(year)),
+    })
+}
+
+fn books() -> Path {
+    Path::root().key("books")
+}
+
+fn author(name: &str) -> Primitive {
+    Primitive::Str(name.into())
+}
+
+fn sorted(ids: &[&SmolStr]) -> Vec<SmolStr> {
+    let mut ids = ids.iter().map(|id| (*id).clone()).collect::<Vec<_>>();
+    ids.sort();
+    ids
+}
+
+/// The IDs of the rows with `author` in the index of the author column
+fn by_author(doc: &Frontend, name: &str) -> Vec<SmolStr> {
+    doc.table_index(&books(), "author")
+        .unwrap()
+        .get(&author(name))
+        .cloned()
+        .collect()
+}
+
+#[test]
+fn indexes_follow_local_changes() {
+    let mut doc = Frontend::new_with_actor_id(&[1]);
+    assert!(doc.add_table_index(books(), "author"));
+    assert!(!doc.add_table_index(books(), "author"));
+    assert!(doc.table_index(&books(), "author").unwrap().is_empty());
+
+    let (ids, _) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(books(), Value::Table(HashMap::new())))?;
+            Ok(vec![
+                d.table_insert(&books(), book("Eliot", 1871))?,
+                d.table_insert(&books(), book("Austen", 1815))?,
+                d.table_insert(&books(), book("Austen", 1817))?,
+            ])
+        })
+        .unwrap();
+    assert_eq!(by_author(&doc, "Austen"), sorted(&[&ids[1], &ids[2]]));
+    assert_eq!(by_author(&doc, "Eliot"), vec![ids[0].clone()]);
+
+    // changing a column moves the row
+    doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.add_change(LocalChange::set(
+            books().key(ids[2].clone()).key("author"),
+            "Eliot",
+        ))
+    })
+    .unwrap();
+    assert_eq!(by_author(&doc, "Austen"), vec![ids[1].clone()]);
+    assert_eq!(by_author(&doc, "Eliot"), sorted(&[&ids[0], &ids[2]]));
+
+    // deleting a row removes it
+    doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.add_change(LocalChange::delete(books().key(ids[1].clone())))
+    })
+    .unwrap();
+    assert!(by_author(&doc, "Austen").is_empty());
+    let index = doc.table_index(&books(), "author").unwrap();
+    assert_eq!(index.len(), 2);
+    assert_eq!(index.value_of(&ids[2]), Some(&author("Eliot")));
+
+    assert!(doc.remove_table_index(&books(), "author"));
+    assert!(doc.table_index(&books(), "author").is_none());
+}
+
+#[test]
+fn indexes_follow_patches() {
+    let mut backend = Backend::new();
+    let mut writer = Frontend::new_with_actor_id(&[1]);
+    let mut reader = Frontend::new_with_actor_id(&[2]);
+    reader.add_table_index(books(), "author");
+
+    let mut sync = |reader: &mut Frontend, change: Option<automerge_protocol::Change>| {
+        let patch = backend.apply_changes(vec![change.unwrap().into()]).unwrap();
+        reader.apply_patch(patch).unwrap();
+    };
+
+    let (ids, change) = writer
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(books(), Value::Table(HashMap::new())))?;
+            Ok(vec![
+                d.table_insert(&books(), book("Eliot", 1871))?,
+                d.table_insert(&books(), book("Austen", 1815))?,
+            ])
+        })
+        .unwrap();
+    sync(&mut reader, change);
+    assert_eq!(by_author(&reader, "Austen"), vec![ids[1].clone()]);
+
+    let (_, change) = writer
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(
+                books().key(ids[1].clone()).key("author"),
+                "Brontë",
+            ))
+        })
+        .unwrap();
+    sync(&mut reader, change);
+    assert!(by_author(&reader, "Austen").is_empty());
+    assert_eq!(by_author(&reader, "Brontë"), vec![ids[1].clone()]);
+
+    // replacing the table reindexes it
+    let (_, change) = writer
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(books(), Value::Table(HashMap::new())))
+        })
+        .unwrap();
+    sync(&mut reader, change);
+    assert!(reader.table_index(&books(), "author").unwrap().is_empty());
+}
+
+#[test]
+fn indexes_support_range_queries() {
+    let mut doc = Frontend::new_with_actor_id(&[1]);
+    let (ids, _) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(books(), Value::Table(HashMap::new())))?;
+            Ok(vec![
+                d.table_insert(&books(), book("Eliot", 1871))?,
+                d.table_insert(&books(), book("Austen", 1815))?,
+                d.table_insert(&books(), book("Austen", 1817))?,
+                d.table_insert(&books(), Value::from(hashmap! {"author" => "Anon"}))?,
+            ])
+        })
+        .unwrap();
+    // indexes added to existing tables index the rows already there
+    doc.add_table_index(books(), "year");
+    let index = doc.table_index(&books(), "year").unwrap();
+    assert_eq!(index.len(), 3);
+
+    let year = |y: i64| Primitive::Int(y);
+    let early = index.range(..year(1850)).cloned().collect::<Vec<_>>();
+    assert_eq!(early, vec![ids[1].clone(), ids[2].clone()]);
+    let all = index.range(..).cloned().collect::<Vec<_>>();
+    assert_eq!(all, vec![ids[1].clone(), ids[2].clone(), ids[0].clone()]);
+    // numbers are compared by value
+    let exact = index.get(&Primitive::F64(1817.0)).collect::<Vec<_>>();
+    assert_eq!(exact, vec![&ids[2]]);
+    assert_eq!(index.range(year(1900)..year(1800)).count(), 0);
+}
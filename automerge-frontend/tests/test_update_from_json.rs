@@ -0,0 +1,182 @@
+use automerge_backend::Backend;
+use automerge_frontend::{Frontend, InvalidChangeRequest, LocalChange, Path, Primitive, Value};
+use automerge_protocol as amp;
+use serde_json::json;
+
+/// A document which has been saved to `backend`
+fn doc_with(json: serde_json::Value) -> (Frontend, Backend) {
+    let mut doc = Frontend::new();
+    let mut backend = Backend::new();
+    let change = doc.update_from_json(&Path::root(), &json).unwrap().unwrap();
+    let (patch, _) = backend.apply_local_change(change).unwrap();
+    doc.apply_patch(patch).unwrap();
+    (doc, backend)
+}
+
+fn update(doc: &mut Frontend, json: serde_json::Value) -> amp::Change {
+    let change = doc.update_from_json(&Path::root(), &json).unwrap().unwrap();
+    assert_eq!(doc.state().to_json(), json);
+    change
+}
+
+#[test]
+fn unchanged_values_produce_no_change() {
+    let json = json!({"birds": {"wrens": 3.0}, "list": [1.0, "two"], "text": "hello"});
+    let (mut doc, _) = doc_with(json.clone());
+    assert_eq!(doc.update_from_json(&Path::root(), &json), Ok(None));
+}
+
+#[test]
+fn map_updates_only_touch_changed_keys() {
+    let (mut doc, _) = doc_with(json!({"birds": {"wrens": 3.0, "sparrows": 15.0}, "fish": 1.0}));
+    let change = update(&mut doc, json!({"birds": {"wrens": 4.0, "sparrows": 15.0}}));
+    assert_eq!(change.operations.len(), 2);
+    assert!(change
+        .operations
+        .iter()
+        .any(|op| op.action == amp::OpType::Set(amp::ScalarValue::F64(4.0))));
+    assert!(change
+        .operations
+        .iter()
+        .any(|op| matches!(op.action, amp::OpType::Del(_))));
+}
+
+#[test]
+fn list_updates_insert_and_delete_only_changed_elements() {
+    let (mut doc, _) = doc_with(json!({"list": [1.0, 2.0, 3.0, 4.0, 5.0]}));
+    let change = update(&mut doc, json!({"list": [0.0, 2.0, 3.0, 5.0, 6.0]}));
+    // 1 is replaced by 0 in place, 4 is deleted and 6 is inserted
+    assert_eq!(change.operations.len(), 3);
+}
+
+#[test]
+fn text_updates_splice_the_text() {
+    let mut doc = Frontend::new();
+    doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.add_change(LocalChange::set(
+            Path::root().key("text"),
+            Value::Text(
+                "hello world"
+                    .chars()
+                    .map(|c| c.to_string().into())
+                    .collect(),
+            ),
+        ))
+    })
+    .unwrap();
+    let change = update(&mut doc, json!({"text": "hello there world"}));
+    assert_eq!(change.operations.len(), "there ".len());
+    assert!(doc.get_value(&Path::root().key("text")).unwrap().is_text());
+}
+
+#[test]
+fn counters_are_incremented() {
+    let mut doc = Frontend::new();
+    doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.add_change(LocalChange::set(
+            Path::root().key("count"),
+            Value::Primitive(Primitive::Counter(3)),
+        ))
+    })
+    .unwrap();
+    let change = update(&mut doc, json!({"count": 5}));
+    assert_eq!(change.operations[0].action, amp::OpType::Inc(2));
+}
+
+#[test]
+fn concurrent_changes_elsewhere_are_preserved() {
+    let (mut doc, mut backend) = doc_with(json!({
+        "birds": [{"name": "magpie", "count": 1.0}, {"name": "wren", "count": 2.0}],
+    }));
+    let mut remote = Frontend::new();
+    remote.apply_patch(backend.get_patch().unwrap()).unwrap();
+
+    let remote_change = remote
+        .update_from_json(
+            &Path::root(),
+            &json!({
+                "birds": [{"name": "magpie", "count": 5.0}, {"name": "wren", "count": 2.0}],
+            }),
+        )
+        .unwrap()
+        .unwrap();
+    let local_change = update(
+        &mut doc,
+        json!({
+            "birds": [
+                {"name": "robin", "count": 1.0},
+                {"name": "magpie", "count": 1.0},
+                {"name": "wren", "count": 2.0},
+            ],
+        }),
+    );
+
+    backend.apply_local_change(local_change).unwrap();
+    backend.apply_changes(vec![remote_change.into()]).unwrap();
+    let mut merged = Frontend::new();
+    merged.apply_patch(backend.get_patch().unwrap()).unwrap();
+    assert_eq!(
+        merged.state().to_json(),
+        json!({
+            "birds": [
+                {"name": "robin", "count": 1.0},
+                {"name": "magpie", "count": 5.0},
+                {"name": "wren", "count": 2.0},
+            ],
+        })
+    );
+}
+
+#[test]
+fn random_list_updates() {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let random_list = |rng: &mut StdRng| {
+        let len = rng.gen_range(0..20);
+        (0..len)
+            .map(|_| json!(rng.gen_range(0..5) as f64))
+            .collect::<Vec<_>>()
+    };
+    for _ in 0..100 {
+        let (mut doc, _) = doc_with(json!({ "list": random_list(&mut rng) }));
+        let new = json!({ "list": random_list(&mut rng) });
+        doc.update_from_json(&Path::root(), &new).unwrap();
+        assert_eq!(doc.state().to_json(), new);
+    }
+}
+
+#[test]
+fn concurrent_text_edits_are_preserved() {
+    let mut doc = Frontend::new();
+    let mut backend = Backend::new();
+    let (_, change) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(
+                Path::root().key("text"),
+                Value::Text("the fox".chars().map(|c| c.to_string().into()).collect()),
+            ))
+        })
+        .unwrap();
+    let (patch, _) = backend.apply_local_change(change.unwrap()).unwrap();
+    doc.apply_patch(patch).unwrap();
+    let mut remote = Frontend::new();
+    remote.apply_patch(backend.get_patch().unwrap()).unwrap();
+
+    let text = Path::root().key("text");
+    let (_, remote_change) = remote
+        .change::<_, _, InvalidChangeRequest>(None, |d| d.update_text(&text, "the quick fox"))
+        .unwrap();
+    let local_change = update(&mut doc, json!({"text": "the fox jumped"}));
+
+    backend.apply_local_change(local_change).unwrap();
+    backend
+        .apply_changes(vec![remote_change.unwrap().into()])
+        .unwrap();
+    let mut merged = Frontend::new();
+    merged.apply_patch(backend.get_patch().unwrap()).unwrap();
+    assert_eq!(
+        merged.state().to_json(),
+        json!({"text": "the quick fox jumped"})
+    );
+}
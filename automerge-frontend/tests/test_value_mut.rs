@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use automerge_backend::Backend;
+use automerge_frontend::{
+    Cursor, Frontend, InvalidChangeRequest, LocalChange, MutableDocument, Path, Primitive, Value,
+};
+
+/// A frontend with a root like `{"a": {"b": [1], "t": "hello"}, "count": Counter(0)}`, saved to
+/// the returned backend
+fn saved_doc() -> (Frontend, Backend) {
+    let mut doc = Frontend::new_with_actor_id(&[1, 2, 3, 4]);
+    let mut backend = Backend::new();
+    let (_, change) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            // the keys are set one at a time so that both documents made by this function assign
+            // the same op IDs to them
+            d.add_change(LocalChange::set(
+                Path::root().key("a"),
+                Value::Map(HashMap::new()),
+            ))?;
+            d.add_change(LocalChange::set(
+                Path::root().key("a").key("b"),
+                Value::List(vec![Value::Primitive(Primitive::Int(1))]),
+            ))?;
+            d.add_change(LocalChange::set(
+                Path::root().key("a").key("t"),
+                Value::Text("hello".chars().map(|c| c.to_string().into()).collect()),
+            ))?;
+            d.add_change(LocalChange::set(
+                Path::root().key("count"),
+                Value::Primitive(Primitive::Counter(0)),
+            ))
+        })
+        .unwrap();
+    let (patch, _) = backend.apply_local_change(change.unwrap()).unwrap();
+    doc.apply_patch(patch).unwrap();
+    (doc, backend)
+}
+
+#[test]
+fn nested_handles_edit_the_document() {
+    let (mut doc, mut backend) = saved_doc();
+    let (_, change) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.root_mut()
+                .map_mut("a")?
+                .list_mut("b")?
+                .push(Value::Primitive(Primitive::Int(2)))?;
+            let mut text = d.root_mut().map_mut("a")?.text_mut("t")?;
+            text.splice(1, 4, "i")?;
+            text.push_str(" there")?;
+            assert_eq!(text.len(), 8);
+            d.root_mut().increment("count", 3)
+        })
+        .unwrap();
+
+    let expected_b = Value::List(vec![
+        Value::Primitive(Primitive::Int(1)),
+        Value::Primitive(Primitive::Int(2)),
+    ]);
+    let expected_t = Value::Text("hi there".chars().map(|c| c.to_string().into()).collect());
+    assert_eq!(
+        doc.get_value(&Path::root().key("a").key("b")),
+        Some(expected_b.clone())
+    );
+    assert_eq!(
+        doc.get_value(&Path::root().key("a").key("t")),
+        Some(expected_t.clone())
+    );
+
+    // the backend agrees with the optimistic state
+    backend.apply_local_change(change.unwrap()).unwrap();
+    let mut reloaded = Frontend::new();
+    reloaded.apply_patch(backend.get_patch().unwrap()).unwrap();
+    assert_eq!(reloaded.state(), doc.state());
+    assert_eq!(
+        reloaded.get_value(&Path::root().key("count")),
+        Some(Value::Primitive(Primitive::Counter(3)))
+    );
+}
+
+#[test]
+fn handles_generate_the_same_operations_as_local_changes() {
+    let (mut with_handles, _) = saved_doc();
+    let (mut with_changes, _) = saved_doc();
+
+    let (_, from_handles) = with_handles
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            let mut a = d.root_mut().map_mut("a")?;
+            a.set("x", "y")?;
+            a.delete("x")?;
+            let mut b = a.list_mut("b")?;
+            b.insert(0, "zero")?;
+            b.set(1, "one")?;
+            b.remove(0)
+        })
+        .unwrap();
+    let (_, from_changes) = with_changes
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            let a = Path::root().key("a");
+            d.add_change(LocalChange::set(a.clone().key("x"), "y"))?;
+            d.add_change(LocalChange::delete(a.clone().key("x")))?;
+            d.add_change(LocalChange::insert(
+                a.clone().key("b").index(0),
+                "zero".into(),
+            ))?;
+            d.add_change(LocalChange::set(a.clone().key("b").index(1), "one"))?;
+            d.add_change(LocalChange::delete(a.key("b").index(0)))
+        })
+        .unwrap();
+    assert_eq!(
+        from_handles.unwrap().operations,
+        from_changes.unwrap().operations
+    );
+}
+
+/// A document implemented outside of this crate, which only provides the required methods
+struct ForwardingDocument<'a>(&'a mut dyn MutableDocument);
+
+impl<'a> MutableDocument for ForwardingDocument<'a> {
+    fn value_at_path(&self, path: &Path) -> Option<Value> {
+        self.0.value_at_path(path)
+    }
+
+    fn cursor_to_path(&self, path: &Path) -> Option<Cursor> {
+        self.0.cursor_to_path(path)
+    }
+
+    fn add_change(&mut self, change: LocalChange) -> Result<(), InvalidChangeRequest> {
+        self.0.add_change(change)
+    }
+}
+
+#[test]
+fn handles_of_other_documents_make_local_changes() {
+    let (mut with_handles, _) = saved_doc();
+    let (mut with_forwarding, _) = saved_doc();
+
+    let edit = |d: &mut dyn MutableDocument| {
+        let mut a = d.root_mut().map_mut("a")?;
+        a.set("x", "y")?;
+        let mut b = a.list_mut("b")?;
+        b.push("two")?;
+        assert_eq!(b.len(), 2);
+        let mut t = d.root_mut().map_mut("a")?.text_mut("t")?;
+        t.splice(0, 1, "j")?;
+        assert_eq!(
+            t.value(),
+            Value::Text("jello".chars().map(|c| c.to_string().into()).collect())
+        );
+        d.root_mut().increment("count", 2)
+    };
+    let (_, from_handles) = with_handles
+        .change::<_, _, InvalidChangeRequest>(None, edit)
+        .unwrap();
+    let (_, from_forwarding) = with_forwarding
+        .change::<_, _, InvalidChangeRequest>(None, |d| edit(&mut ForwardingDocument(d)))
+        .unwrap();
+    assert_eq!(
+        from_handles.unwrap().operations,
+        from_forwarding.unwrap().operations
+    );
+
+    let result = with_forwarding.change::<_, _, InvalidChangeRequest>(None, |d| {
+        ForwardingDocument(d)
+            .root_mut()
+            .list_mut("count")
+            .map(|_| ())
+    });
+    assert_eq!(
+        result,
+        Err(InvalidChangeRequest::UnexpectedObjectType {
+            path: Path::root().key("count"),
+            expected: "a list",
+        })
+    );
+}
+
+#[test]
+fn other_documents_can_insert_table_rows() {
+    let (mut doc, _) = saved_doc();
+    let (row_id, _) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            let mut d = ForwardingDocument(d);
+            d.root_mut().set("books", Value::Table(HashMap::new()))?;
+            let row = Value::Map(maplit::hashmap! {"title".into() => Value::from("Dune")});
+            assert_eq!(
+                d.table_insert(&Path::root().key("a"), row.clone()),
+                Err(InvalidChangeRequest::UnexpectedObjectType {
+                    path: Path::root().key("a"),
+                    expected: "a table",
+                })
+            );
+            d.table_insert(&Path::root().key("books"), row)
+        })
+        .unwrap();
+    assert_eq!(
+        doc.get_value(&Path::root().key("books").key(row_id).key("title")),
+        Some(Value::from("Dune"))
+    );
+}
+
+#[test]
+fn other_documents_cannot_create_savepoints() {
+    let (mut doc, _) = saved_doc();
+    let result = doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        let mut d = ForwardingDocument(d);
+        d.root_mut().set("x", "y")?;
+        d.savepoint()
+    });
+    assert_eq!(result, Err(InvalidChangeRequest::SavepointsNotSupported));
+}
+
+#[test]
+fn maps_pushed_onto_lists_keep_their_keys() {
+    let (mut doc, mut backend) = saved_doc();
+    let bird = Value::Map(maplit::hashmap! {
+        "name".into() => Value::from("goldfinch"),
+        "colour".into() => Value::from("yellow"),
+    });
+    let (_, change) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.root_mut().map_mut("a")?.list_mut("b")?.push(bird.clone())
+        })
+        .unwrap();
+    backend.apply_local_change(change.unwrap()).unwrap();
+    assert_eq!(
+        doc.get_value(&Path::root().key("a").key("b").index(1)),
+        Some(bird)
+    );
+
+    let mut reloaded = Frontend::new();
+    reloaded.apply_patch(backend.get_patch().unwrap()).unwrap();
+    assert_eq!(reloaded.state(), doc.state());
+}
+
+#[test]
+fn handles_check_the_type_of_objects() {
+    let (mut doc, _) = saved_doc();
+    let before = doc.state().clone();
+    let result = doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.root_mut().map_mut("a")?.list_mut("b")?.push("pushed")?;
+        d.root_mut().map_mut("a")?.list_mut("t")?;
+        Ok(())
+    });
+    assert_eq!(
+        result,
+        Err(InvalidChangeRequest::UnexpectedObjectType {
+            path: Path::root().key("a").key("t"),
+            expected: "a list",
+        })
+    );
+    // the push made before the error is rolled back
+    assert_eq!(doc.state(), &before);
+
+    let result = doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.root_mut().map_mut("missing").map(|_| ())
+    });
+    assert_eq!(
+        result,
+        Err(InvalidChangeRequest::NoSuchPathError {
+            path: Path::root().key("missing"),
+        })
+    );
+
+    let result = doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.root_mut()
+            .set("count", Value::Primitive(Primitive::Int(1)))
+    });
+    assert_eq!(
+        result,
+        Err(InvalidChangeRequest::CannotOverwriteCounter {
+            path: Path::root().key("count"),
+        })
+    );
+}
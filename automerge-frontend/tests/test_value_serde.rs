@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use automerge_frontend::{
+    value_serde::{self, Counter, Table, Text, Timestamp},
+    Frontend, InvalidChangeRequest, LocalChange, Path, Primitive, SerdeError, Value,
+};
+use maplit::hashmap;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Todo {
+    title: Text,
+    done: bool,
+    views: Counter,
+    #[serde(with = "value_serde::timestamp")]
+    created: i64,
+    tags: Vec<String>,
+    assignee: Option<String>,
+    priority: Priority,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Priority {
+    Low,
+    High { reason: String },
+    Custom(u32),
+}
+
+fn todo() -> Todo {
+    Todo {
+        title: Text("water the plants".to_string()),
+        done: false,
+        views: Counter(3),
+        created: 1_600_000_000,
+        tags: vec!["home".to_string()],
+        assignee: None,
+        priority: Priority::High {
+            reason: "they are wilting".to_string(),
+        },
+    }
+}
+
+#[test]
+fn structs_serialize_to_maps_with_requested_representations() {
+    let value = value_serde::to_value(&todo()).unwrap();
+    let expected: Value = hashmap! {
+        "title" => Value::Text("water the plants".chars().map(|c| c.to_string().into()).collect()),
+        "done" => Value::Primitive(Primitive::Boolean(false)),
+        "views" => Value::Primitive(Primitive::Counter(3)),
+        "created" => Value::Primitive(Primitive::Timestamp(1_600_000_000)),
+        "tags" => Value::List(vec!["home".into()]),
+        "assignee" => Value::Primitive(Primitive::Null),
+        "priority" => Value::from(hashmap! {
+            "High" => Value::from(hashmap! { "reason" => Value::from("they are wilting") }),
+        }),
+    }
+    .into();
+    assert_eq!(value, expected);
+    assert_eq!(value_serde::from_value::<Todo>(&value).unwrap(), todo());
+}
+
+#[test]
+fn enum_variants_round_trip() {
+    for priority in [Priority::Low, Priority::Custom(7)].iter() {
+        let value = value_serde::to_value(priority).unwrap();
+        assert_eq!(
+            value_serde::from_value::<Priority>(&value).unwrap(),
+            *priority
+        );
+    }
+    assert_eq!(
+        value_serde::to_value(&Priority::Low).unwrap(),
+        Value::from("Low")
+    );
+}
+
+#[test]
+fn tables_serialize_from_maps() {
+    let rows: HashMap<String, u64> = hashmap! {"a".to_string() => 1, "b".to_string() => 2};
+    let value = value_serde::to_value(&Table(rows.clone())).unwrap();
+    assert_eq!(
+        value,
+        Value::Table(hashmap! {
+            "a".into() => Value::Primitive(Primitive::Uint(1)),
+            "b".into() => Value::Primitive(Primitive::Uint(2)),
+        })
+    );
+    assert_eq!(
+        value_serde::from_value::<Table<HashMap<String, u64>>>(&value).unwrap(),
+        Table(rows)
+    );
+}
+
+#[test]
+fn documents_can_be_written_and_read_with_serde() {
+    let mut doc = Frontend::new();
+    doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.add_change(LocalChange::set_serialize(
+            Path::root().key("todo"),
+            &todo(),
+        )?)?;
+        d.add_change(LocalChange::insert_serialize(
+            Path::root().key("todo").key("tags").index(1),
+            "garden",
+        )?)
+    })
+    .unwrap();
+    let mut expected = todo();
+    expected.tags.push("garden".to_string());
+
+    let result = doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.add_change(LocalChange::set_serialize(
+            Path::root().key("todo"),
+            &Table(vec![1u8]),
+        )?)
+    });
+    assert!(matches!(
+        result,
+        Err(InvalidChangeRequest::SerdeError {
+            source: SerdeError::InvalidRepresentation { .. }
+        })
+    ));
+
+    let todo_ref = doc.value_ref().get("todo").unwrap();
+    assert_eq!(
+        value_serde::from_value_ref::<Todo>(todo_ref).unwrap(),
+        expected
+    );
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Root {
+        todo: Todo,
+    }
+    assert_eq!(
+        Root::deserialize(doc.value_ref()).unwrap(),
+        Root { todo: expected }
+    );
+}
+
+#[test]
+fn representations_are_transparent_to_other_formats() {
+    let json = serde_json::to_value(todo()).unwrap();
+    assert_eq!(json["title"], "water the plants");
+    assert_eq!(json["views"], 3);
+    assert_eq!(serde_json::from_value::<Todo>(json).unwrap(), todo());
+}
+
+#[test]
+fn field_attributes_request_representations() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Stats {
+        #[serde(with = "value_serde::counter")]
+        count: i64,
+        #[serde(with = "value_serde::text")]
+        name: String,
+        last_seen: Timestamp,
+    }
+    let stats = Stats {
+        count: 1,
+        name: "hi".to_string(),
+        last_seen: Timestamp(5),
+    };
+    let value = value_serde::to_value(&stats).unwrap();
+    let expected: Value = hashmap! {
+        "count" => Value::Primitive(Primitive::Counter(1)),
+        "name" => Value::Text(vec!["h".into(), "i".into()]),
+        "last_seen" => Value::Primitive(Primitive::Timestamp(5)),
+    }
+    .into();
+    assert_eq!(value, expected);
+    assert_eq!(value_serde::from_value::<Stats>(&value).unwrap(), stats);
+}
+
+#[test]
+fn invalid_representations_are_errors() {
+    assert_eq!(
+        value_serde::to_value(&Table(vec![1u8])),
+        Err(SerdeError::InvalidRepresentation {
+            representation: "a table",
+            value: Value::List(vec![Value::Primitive(Primitive::Uint(1))]),
+        })
+    );
+    assert_eq!(
+        value_serde::to_value(&Counter(-1)).unwrap(),
+        Value::Primitive(Primitive::Counter(-1))
+    );
+
+    let keyed_by_list: HashMap<Vec<u8>, bool> = hashmap! {vec![1] => true};
+    assert_eq!(
+        value_serde::to_value(&keyed_by_list),
+        Err(SerdeError::KeyMustBeAString)
+    );
+    assert!(value_serde::from_value::<Todo>(&Value::from("not a todo")).is_err());
+}
@@ -503,6 +503,10 @@ pub enum OpType {
     Inc(i64),
     Set(ScalarValue),
     MultiSet(ScalarValues),
+    /// Move the value created by the operation with this ID (or by an earlier move of it) to the
+    /// `obj` and `key` of this operation. Versions of automerge which predate moves can't read
+    /// changes containing them.
+    Move(OpId),
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Serialize)]
@@ -56,6 +56,7 @@ impl Serialize for Op {
             OpType::Set(value) => op.serialize_field("value", &value)?,
             OpType::MultiSet(values) => op.serialize_field("values", &values.vec)?,
             OpType::Del(multi_op) => op.serialize_field("multiOp", &multi_op)?,
+            OpType::Move(source) => op.serialize_field("ref", &source)?,
             OpType::Make(..) => {}
         }
         op.serialize_field("pred", &self.pred)?;
@@ -77,6 +78,7 @@ pub enum RawOpType {
     Del,
     Inc,
     Set,
+    Move,
 }
 
 impl Serialize for RawOpType {
@@ -92,6 +94,7 @@ impl Serialize for RawOpType {
             RawOpType::Del => "del",
             RawOpType::Inc => "inc",
             RawOpType::Set => "set",
+            RawOpType::Move => "move",
         };
         serializer.serialize_str(s)
     }
@@ -110,6 +113,7 @@ impl<'de> Deserialize<'de> for RawOpType {
             "del",
             "inc",
             "set",
+            "move",
         ];
         // TODO: Probably more efficient to deserialize to a `&str`
         let raw_type = String::deserialize(deserializer)?;
@@ -121,6 +125,7 @@ impl<'de> Deserialize<'de> for RawOpType {
             "del" => Ok(RawOpType::Del),
             "inc" => Ok(RawOpType::Inc),
             "set" => Ok(RawOpType::Set),
+            "move" => Ok(RawOpType::Move),
             other => Err(Error::unknown_variant(other, VARIANTS)),
         }
     }
@@ -229,6 +234,10 @@ impl<'de> Deserialize<'de> for Op {
                             OpType::Set(value)
                         }
                     }
+                    RawOpType::Move => match ref_id {
+                        Some(source) => OpType::Move(source),
+                        None => return Err(Error::missing_field("ref")),
+                    },
                     RawOpType::Inc => match value.flatten() {
                         Some(ScalarValue::Int(n)) => Ok(OpType::Inc(n)),
                         Some(ScalarValue::Uint(n)) => Ok(OpType::Inc(n as i64)),
@@ -735,6 +744,15 @@ mod tests {
                 insert: true,
                 pred: SortedVec::new(),
             },
+            Op {
+                action: OpType::Move(OpId::from_str("2@7ef48769b04d47e9a88e98a134d62716").unwrap()),
+                obj: ObjectId::from_str("1@7ef48769b04d47e9a88e98a134d62716").unwrap(),
+                key: OpId::from_str("1@7ef48769b04d47e9a88e98a134d62716")
+                    .unwrap()
+                    .into(),
+                insert: true,
+                pred: SortedVec::new(),
+            },
         ];
         for (testcase_num, testcase) in testcases.iter().enumerate() {
             #[allow(clippy::expect_fun_call)]
@@ -19,6 +19,7 @@ impl Serialize for OpType {
             OpType::Inc(_) => RawOpType::Inc,
             OpType::Set(_) => RawOpType::Set,
             OpType::MultiSet(..) => RawOpType::Set,
+            OpType::Move(..) => RawOpType::Move,
         };
         raw_type.serialize(serializer)
     }
@@ -0,0 +1,50 @@
+use std::{
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use automerge_backend::SyncMessage;
+
+use crate::{
+    frame::{read_frame, validate_document_id, write_frame},
+    Error,
+};
+
+/// A connection to a [`crate::Server`] for a single document.
+#[derive(Debug)]
+pub struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    pub fn connect<A: ToSocketAddrs>(addr: A, document_id: &str) -> Result<Self, Error> {
+        validate_document_id(document_id)?;
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        write_frame(&mut stream, document_id.as_bytes())?;
+        Ok(Self { stream })
+    }
+
+    /// A second handle to the same connection, so that one thread can receive while another
+    /// sends.
+    pub fn try_clone(&self) -> Result<Self, Error> {
+        Ok(Self {
+            stream: self.stream.try_clone()?,
+        })
+    }
+
+    /// Set how long [`Client::receive`] waits for a message, `None` waits forever.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), Error> {
+        self.stream.set_read_timeout(timeout)?;
+        Ok(())
+    }
+
+    pub fn send(&mut self, message: SyncMessage) -> Result<(), Error> {
+        write_frame(&mut self.stream, &message.encode()?)
+    }
+
+    pub fn receive(&mut self) -> Result<SyncMessage, Error> {
+        let bytes = read_frame(&mut self.stream)?;
+        Ok(SyncMessage::decode(&bytes)?)
+    }
+}
@@ -0,0 +1,20 @@
+use std::io;
+
+use automerge_backend::{AutomergeError, DecodingError, EncodingError};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Invalid document id {0:?}, ids may only contain ascii letters, digits, '-' and '_'")]
+    InvalidDocumentId(String),
+    #[error("Frame of {0} bytes exceeds the maximum frame size")]
+    FrameTooLarge(usize),
+    #[error("Decoding error {0}")]
+    Decoding(#[from] DecodingError),
+    #[error("Encoding error {0}")]
+    Encoding(#[from] EncodingError),
+    #[error(transparent)]
+    Automerge(#[from] AutomergeError),
+}
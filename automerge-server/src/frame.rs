@@ -0,0 +1,46 @@
+use std::{
+    convert::TryFrom,
+    io,
+    io::{Read, Write},
+};
+
+use crate::Error;
+
+// Generous enough for the snapshot of a large document while still rejecting garbage lengths
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+pub(crate) fn write_frame<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<(), Error> {
+    let len = u32::try_from(bytes.len()).map_err(|_| Error::FrameTooLarge(bytes.len()))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(bytes)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub(crate) fn read_frame<R: Read>(reader: &mut R) -> Result<Vec<u8>, Error> {
+    let mut len_bytes = [0; 4];
+    reader.read_exact(&mut len_bytes)?;
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(Error::FrameTooLarge(len));
+    }
+    // grow the buffer as the data arrives rather than trusting the peer's length up front
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(bytes)
+}
+
+pub(crate) fn validate_document_id(id: &str) -> Result<(), Error> {
+    if !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(())
+    } else {
+        Err(Error::InvalidDocumentId(id.to_string()))
+    }
+}
@@ -0,0 +1,17 @@
+//! A small relay which hosts automerge documents and synchronises them with any number of
+//! clients over TCP.
+//!
+//! Every connection starts with a frame containing the id of the document the client wants to
+//! sync, after which both sides exchange frames containing encoded [`SyncMessage`]s. A frame is a
+//! big endian `u32` length followed by that many bytes.
+//!
+//! [`SyncMessage`]: automerge_backend::SyncMessage
+
+mod client;
+mod error;
+mod frame;
+mod server;
+
+pub use client::Client;
+pub use error::Error;
+pub use server::Server;
@@ -0,0 +1,29 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use automerge_server::Server;
+use clap::Clap;
+
+#[derive(Debug, Clap)]
+#[clap(about = "Relay automerge documents between clients over TCP")]
+struct Opts {
+    /// Address to listen on
+    #[clap(long, short, default_value = "127.0.0.1:3456")]
+    address: String,
+
+    /// Directory to persist documents in, documents are only held in memory if this is not given
+    #[clap(parse(from_os_str), long, short)]
+    data_dir: Option<PathBuf>,
+}
+
+fn main() -> Result<()> {
+    let opts = Opts::parse();
+    let mut server = Server::bind(&opts.address)?;
+    if let Some(data_dir) = opts.data_dir {
+        std::fs::create_dir_all(&data_dir)?;
+        server = server.with_data_dir(data_dir);
+    }
+    eprintln!("Listening on {}", server.local_addr()?);
+    server.run()?;
+    Ok(())
+}
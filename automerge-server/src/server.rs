@@ -0,0 +1,232 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::ErrorKind,
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+};
+
+use automerge_backend::{Backend, SyncMessage, SyncState};
+
+use crate::{
+    frame::{read_frame, validate_document_id, write_frame},
+    Error,
+};
+
+/// Hosts documents in memory, optionally persisting them to a directory with [`Backend::save`],
+/// and relays changes between every client connected to the same document.
+#[derive(Debug)]
+pub struct Server {
+    listener: TcpListener,
+    documents: Arc<Documents>,
+}
+
+#[derive(Debug, Default)]
+struct Documents {
+    data_dir: Option<PathBuf>,
+    next_peer_id: AtomicU64,
+    documents: Mutex<HashMap<String, Document>>,
+}
+
+#[derive(Debug, Default)]
+struct Document {
+    backend: Backend,
+    peers: HashMap<u64, Peer>,
+}
+
+/// A connected client. Frames are handed to a writer thread through `outbox` so that a slow
+/// client never holds up the lock on `Documents::documents`.
+#[derive(Debug)]
+struct Peer {
+    outbox: mpsc::Sender<Vec<u8>>,
+    sync_state: SyncState,
+}
+
+impl Server {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self, Error> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            documents: Arc::new(Documents::default()),
+        })
+    }
+
+    /// Load documents from and save them to `data_dir`, one file per document id.
+    pub fn with_data_dir(self, data_dir: PathBuf) -> Self {
+        Self {
+            listener: self.listener,
+            documents: Arc::new(Documents {
+                data_dir: Some(data_dir),
+                ..Documents::default()
+            }),
+        }
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accept connections forever, serving each one on its own thread.
+    pub fn run(self) -> Result<(), Error> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let documents = self.documents.clone();
+            thread::spawn(move || {
+                if let Err(e) = documents.serve(stream) {
+                    tracing::debug!(error = %e, "connection closed with an error");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Run the server on a background thread.
+    pub fn spawn(self) -> thread::JoinHandle<Result<(), Error>> {
+        thread::spawn(move || self.run())
+    }
+}
+
+impl Documents {
+    fn serve(&self, mut stream: TcpStream) -> Result<(), Error> {
+        stream.set_nodelay(true)?;
+        let document_id = String::from_utf8(read_frame(&mut stream)?)
+            .map_err(|e| Error::InvalidDocumentId(String::from_utf8_lossy(e.as_bytes()).into()))?;
+        validate_document_id(&document_id)?;
+
+        let peer_id = self.next_peer_id.fetch_add(1, Ordering::Relaxed);
+        let (outbox, inbox) = mpsc::channel();
+        spawn_writer(stream.try_clone()?, inbox);
+        self.connect(&document_id, peer_id, outbox)?;
+        let result = self.receive_messages(&document_id, peer_id, &mut stream);
+        // dropping the peer's outbox stops its writer thread once the queued frames are sent
+        self.disconnect(&document_id, peer_id);
+        result
+    }
+
+    fn receive_messages(
+        &self,
+        document_id: &str,
+        peer_id: u64,
+        stream: &mut TcpStream,
+    ) -> Result<(), Error> {
+        loop {
+            let bytes = match read_frame(stream) {
+                Ok(bytes) => bytes,
+                Err(Error::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            let message = SyncMessage::decode(&bytes)?;
+            self.receive(document_id, peer_id, message)?;
+        }
+    }
+
+    fn connect(
+        &self,
+        document_id: &str,
+        peer_id: u64,
+        outbox: mpsc::Sender<Vec<u8>>,
+    ) -> Result<(), Error> {
+        let mut documents = self.documents.lock().unwrap();
+        if !documents.contains_key(document_id) {
+            let document = Document {
+                backend: self.load(document_id)?,
+                peers: HashMap::new(),
+            };
+            documents.insert(document_id.to_string(), document);
+        }
+        let document = documents.get_mut(document_id).unwrap();
+        document.peers.insert(
+            peer_id,
+            Peer {
+                outbox,
+                sync_state: SyncState::default(),
+            },
+        );
+        document.sync_peers();
+        Ok(())
+    }
+
+    fn disconnect(&self, document_id: &str, peer_id: u64) {
+        let mut documents = self.documents.lock().unwrap();
+        if let Some(document) = documents.get_mut(document_id) {
+            document.peers.remove(&peer_id);
+        }
+    }
+
+    fn receive(&self, document_id: &str, peer_id: u64, message: SyncMessage) -> Result<(), Error> {
+        let mut documents = self.documents.lock().unwrap();
+        let document = documents
+            .get_mut(document_id)
+            .expect("document removed while a peer was connected");
+        let Document { backend, peers } = document;
+
+        let heads_before = backend.get_heads();
+        if let Some(peer) = peers.get_mut(&peer_id) {
+            backend.receive_sync_message(&mut peer.sync_state, message)?;
+        }
+        if backend.get_heads() != heads_before {
+            self.save(document_id, backend)?;
+        }
+
+        document.sync_peers();
+        Ok(())
+    }
+
+    fn load(&self, document_id: &str) -> Result<Backend, Error> {
+        if let Some(data_dir) = &self.data_dir {
+            match fs::read(data_dir.join(document_id)) {
+                Ok(bytes) => return Ok(Backend::load(bytes)?),
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(Backend::new())
+    }
+
+    fn save(&self, document_id: &str, backend: &Backend) -> Result<(), Error> {
+        if let Some(data_dir) = &self.data_dir {
+            // write to a temporary file first so that a crash never leaves a truncated document
+            let path = data_dir.join(document_id);
+            let tmp_path = data_dir.join(format!("{}.tmp", document_id));
+            fs::write(&tmp_path, backend.save()?)?;
+            fs::rename(tmp_path, path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Document {
+    /// Queue whatever every connected peer is missing. A peer whose connection has failed is
+    /// left for its own thread to remove.
+    fn sync_peers(&mut self) {
+        for peer in self.peers.values_mut() {
+            if let Some(message) = self.backend.generate_sync_message(&mut peer.sync_state) {
+                match message.encode() {
+                    Ok(bytes) => {
+                        // the writer thread has already gone if its connection failed
+                        let _ = peer.outbox.send(bytes);
+                    }
+                    Err(e) => tracing::debug!(error = %e, "failed to encode sync message"),
+                }
+            }
+        }
+    }
+}
+
+/// Write every frame sent to `inbox` to `stream` until the sender is dropped. If a write fails
+/// the connection is shut down so that the reading thread notices and disconnects the peer.
+fn spawn_writer(mut stream: TcpStream, inbox: mpsc::Receiver<Vec<u8>>) {
+    thread::spawn(move || {
+        for bytes in inbox {
+            if let Err(e) = write_frame(&mut stream, &bytes) {
+                tracing::debug!(error = %e, "failed to send sync message");
+                let _ = stream.shutdown(Shutdown::Both);
+                break;
+            }
+        }
+    });
+}
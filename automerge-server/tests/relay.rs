@@ -0,0 +1,120 @@
+use std::{
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use automerge_backend::{Backend, SyncMessage, SyncState};
+use automerge_frontend::{Frontend, InvalidChangeRequest, LocalChange, Path, Value};
+use automerge_server::{Client, Error, Server};
+
+const DEADLINE: Duration = Duration::from_secs(10);
+
+/// A client backend along with the messages the server has sent it, which are read on a
+/// separate thread so that the test never has to guess how long the server will take.
+struct Peer {
+    client: Client,
+    incoming: mpsc::Receiver<SyncMessage>,
+    backend: Backend,
+    sync_state: SyncState,
+}
+
+impl Peer {
+    fn connect(addr: std::net::SocketAddr, document_id: &str, backend: Backend) -> Self {
+        let client = Client::connect(addr, document_id).unwrap();
+        let mut reader = client.try_clone().unwrap();
+        let (sender, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(message) = reader.receive() {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            client,
+            incoming,
+            backend,
+            sync_state: SyncState::default(),
+        }
+    }
+
+    /// Exchange messages with the server until `done` holds for our backend.
+    fn sync_until<F: Fn(&Backend) -> bool>(&mut self, done: F) {
+        let deadline = Instant::now() + DEADLINE;
+        loop {
+            if let Some(message) = self.backend.generate_sync_message(&mut self.sync_state) {
+                self.client.send(message).unwrap();
+            }
+            if done(&self.backend) {
+                return;
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let message = self
+                .incoming
+                .recv_timeout(remaining)
+                .expect("condition was not reached before the deadline");
+            self.backend
+                .receive_sync_message(&mut self.sync_state, message)
+                .unwrap();
+        }
+    }
+}
+
+fn backend_with_value(key: &str, value: &str) -> Backend {
+    let mut backend = Backend::new();
+    let mut frontend = Frontend::new();
+    let change = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(Path::root().key(key), Value::from(value)))
+        })
+        .unwrap()
+        .1
+        .unwrap();
+    backend.apply_local_change(change).unwrap();
+    backend
+}
+
+#[test]
+fn relays_changes_between_clients() {
+    let server = Server::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    server.spawn();
+
+    let backend1 = backend_with_value("bird", "magpie");
+    let backend2 = backend_with_value("other", "wren");
+    let mut expected_heads = backend1.get_heads();
+    expected_heads.extend(backend2.get_heads());
+    expected_heads.sort();
+
+    let mut peer1 = Peer::connect(addr, "birds", backend1);
+    let mut peer2 = Peer::connect(addr, "birds", backend2);
+    // each peer only reaches the merged heads once the server has relayed the other's change, so
+    // both have to sync at the same time
+    let peer1_heads = expected_heads.clone();
+    let peer1 = thread::spawn(move || {
+        peer1.sync_until(|backend| backend.get_heads() == peer1_heads);
+        peer1
+    });
+    peer2.sync_until(|backend| backend.get_heads() == expected_heads);
+    let peer1 = peer1.join().unwrap();
+
+    assert_eq!(
+        peer1.backend.get_patch().unwrap(),
+        peer2.backend.get_patch().unwrap()
+    );
+
+    // the server announces its heads as soon as a peer connects, so the first message tells us
+    // what it holds for a new document
+    let peer3 = Peer::connect(addr, "other-birds", Backend::new());
+    let message = peer3.incoming.recv_timeout(DEADLINE).unwrap();
+    assert!(message.heads.is_empty());
+}
+
+#[test]
+fn rejects_invalid_document_ids() {
+    assert!(matches!(
+        Client::connect("127.0.0.1:0", "../etc/passwd"),
+        Err(Error::InvalidDocumentId(_))
+    ));
+}
@@ -0,0 +1,342 @@
+//! A deterministic simulation of many peers syncing over an unreliable network.
+//!
+//! Each peer is a [`Frontend`] and [`Backend`] pair which keeps a [`SyncState`] for every other
+//! peer. Messages between peers are queued in an in-memory network which, driven by a seeded
+//! RNG, may drop, duplicate and reorder them. A dropped message is treated as the connection
+//! failing: both ends fall back to their persisted sync state (as returned by
+//! [`SyncState::encode`]) and start again, which is what applications are expected to do when
+//! they reconnect.
+//!
+//! Running the same scenario with the same seed always produces the same sequence of events, so
+//! a failing seed can be replayed.
+//!
+//! This module is only available with the `simulation` feature.
+//!
+//! ```
+//! use automerge::{simulation::{NetworkConfig, Simulation}, LocalChange, Path, Value};
+//!
+//! let mut sim = Simulation::new(3, 42, NetworkConfig::default());
+//! for (peer, key) in [(0, "a"), (2, "b")].iter() {
+//!     sim.change(*peer, |doc| {
+//!         doc.add_change(LocalChange::set(Path::root().key(*key), Value::from(1_i64)))
+//!     })
+//!     .unwrap();
+//! }
+//! sim.run(100).unwrap();
+//! sim.settle().unwrap();
+//! sim.assert_converged();
+//! ```
+
+use std::{collections::HashMap, error::Error};
+
+use automerge_backend::{AutomergeError, Backend, SyncMessage, SyncState};
+use automerge_frontend::{Frontend, MutableDocument, Value};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// How unreliable the simulated network is. Probabilities are in the range `0.0..=1.0`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkConfig {
+    /// The chance that a message is lost, resetting the connection it was sent on
+    pub drop_probability: f64,
+    /// The chance that a message is delivered twice
+    pub duplicate_probability: f64,
+    /// Whether messages may be delivered in a different order to the one they were sent in
+    pub reorder: bool,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            drop_probability: 0.05,
+            duplicate_probability: 0.05,
+            reorder: true,
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// A network which delivers every message exactly once and in order.
+    pub fn reliable() -> Self {
+        Self {
+            drop_probability: 0.0,
+            duplicate_probability: 0.0,
+            reorder: false,
+        }
+    }
+}
+
+pub struct Peer {
+    pub frontend: Frontend,
+    pub backend: Backend,
+}
+
+/// The state of the connection from one peer to another. The epoch is bumped whenever the
+/// connection is reset so that messages sent on the old connection are discarded.
+#[derive(Debug, Default)]
+struct Link {
+    sync_state: SyncState,
+    epoch: u64,
+}
+
+#[derive(Debug)]
+struct InFlight {
+    from: usize,
+    to: usize,
+    epoch: u64,
+    message: SyncMessage,
+}
+
+pub struct Simulation {
+    rng: StdRng,
+    config: NetworkConfig,
+    peers: Vec<Peer>,
+    links: HashMap<(usize, usize), Link>,
+    in_flight: Vec<InFlight>,
+}
+
+impl Simulation {
+    pub fn new(num_peers: usize, seed: u64, config: NetworkConfig) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let peers = (0..num_peers)
+            .map(|_| {
+                let actor_id: [u8; 16] = rng.gen();
+                Peer {
+                    frontend: Frontend::new_with_timestamper_and_actor_id(
+                        Box::new(|| None),
+                        &actor_id,
+                    ),
+                    backend: Backend::new(),
+                }
+            })
+            .collect();
+        let mut links = HashMap::new();
+        for from in 0..num_peers {
+            for to in 0..num_peers {
+                if from != to {
+                    links.insert((from, to), Link::default());
+                }
+            }
+        }
+        Self {
+            rng,
+            config,
+            peers,
+            links,
+            in_flight: Vec::new(),
+        }
+    }
+
+    pub fn peers(&self) -> &[Peer] {
+        &self.peers
+    }
+
+    pub fn peer(&self, index: usize) -> &Peer {
+        &self.peers[index]
+    }
+
+    /// The simulation's RNG, for making application level choices (such as which peer edits
+    /// next) that are reproducible from the seed.
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Make a local change on `peer` and announce it to every other peer.
+    ///
+    /// # Panics
+    ///
+    /// If the backend rejects the change the frontend produced, or the frontend rejects the
+    /// resulting patch. Either indicates a bug in automerge rather than in the application.
+    pub fn change<F, O, E>(&mut self, peer: usize, change_closure: F) -> Result<O, E>
+    where
+        E: Error,
+        F: FnOnce(&mut dyn MutableDocument) -> Result<O, E>,
+    {
+        let Peer { frontend, backend } = &mut self.peers[peer];
+        let (result, change) = frontend.change(None, change_closure)?;
+        if let Some(change) = change {
+            let (patch, _) = backend
+                .apply_local_change(change)
+                .expect("backend rejected a local change");
+            frontend
+                .apply_patch(patch)
+                .expect("frontend rejected a local patch");
+            self.broadcast(peer);
+        }
+        Ok(result)
+    }
+
+    /// Deliver one message, returning false if there were none to deliver.
+    ///
+    /// # Errors
+    ///
+    /// If the receiving backend rejects the message, which the sync protocol should never cause
+    /// however the network misbehaves.
+    pub fn step(&mut self) -> Result<bool, AutomergeError> {
+        if self.in_flight.is_empty() {
+            return Ok(false);
+        }
+        let index = if self.config.reorder {
+            self.rng.gen_range(0..self.in_flight.len())
+        } else {
+            0
+        };
+        let InFlight {
+            from,
+            to,
+            epoch,
+            message,
+        } = self.in_flight.remove(index);
+        if self.links[&(from, to)].epoch != epoch {
+            // sent on a connection which has since been reset
+            return Ok(true);
+        }
+
+        let link = self.links.get_mut(&(to, from)).unwrap();
+        let Peer { frontend, backend } = &mut self.peers[to];
+        if let Some(patch) = backend.receive_sync_message(&mut link.sync_state, message)? {
+            frontend
+                .apply_patch(patch)
+                .expect("frontend rejected a patch from its backend");
+        }
+        self.broadcast(to);
+        Ok(true)
+    }
+
+    /// Deliver up to `steps` messages.
+    pub fn run(&mut self, steps: usize) -> Result<(), AutomergeError> {
+        for _ in 0..steps {
+            if !self.step()? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Make the network reliable, reconnect every peer and deliver messages until none are left.
+    ///
+    /// # Panics
+    ///
+    /// If the peers are still exchanging messages after a generous number of steps.
+    pub fn settle(&mut self) -> Result<(), AutomergeError> {
+        self.config = NetworkConfig::reliable();
+        self.in_flight.clear();
+        let mut links = self.links.keys().copied().collect::<Vec<_>>();
+        links.sort_unstable();
+        for (from, to) in links {
+            if from < to {
+                self.reconnect(from, to);
+            }
+        }
+
+        let max_steps = 100 * self.links.len().max(1);
+        for _ in 0..max_steps {
+            if !self.step()? {
+                return Ok(());
+            }
+        }
+        panic!(
+            "peers did not settle within {} steps, {} messages still in flight",
+            max_steps,
+            self.in_flight.len()
+        );
+    }
+
+    /// # Panics
+    ///
+    /// If any two peers have different heads, if any two frontends have different states, or if
+    /// a frontend's state differs from the state of its own backend.
+    pub fn assert_converged(&mut self) {
+        let heads = self.peers[0].backend.get_heads();
+        let mut expected_state: Option<Value> = None;
+        for (index, peer) in self.peers.iter_mut().enumerate() {
+            assert_eq!(
+                peer.backend.get_heads(),
+                heads,
+                "peer {} has different heads to peer 0",
+                index
+            );
+
+            let mut from_backend = Frontend::new_with_timestamper(Box::new(|| None));
+            from_backend
+                .apply_patch(peer.backend.get_patch().unwrap())
+                .unwrap();
+            let state = peer.frontend.state().clone();
+            assert_eq!(
+                &state,
+                from_backend.state(),
+                "peer {} has a frontend which differs from its backend",
+                index
+            );
+
+            if let Some(expected_state) = &expected_state {
+                assert_eq!(
+                    &state, expected_state,
+                    "peer {} has a different state to peer 0",
+                    index
+                );
+            } else {
+                expected_state = Some(state);
+            }
+        }
+    }
+
+    /// Generate a message from `from` to every other peer which it has something to say to.
+    fn broadcast(&mut self, from: usize) {
+        for to in 0..self.peers.len() {
+            if to != from {
+                self.send(from, to);
+            }
+        }
+    }
+
+    fn send(&mut self, from: usize, to: usize) {
+        let link = self.links.get_mut(&(from, to)).unwrap();
+        let message = match self.peers[from]
+            .backend
+            .generate_sync_message(&mut link.sync_state)
+        {
+            Some(message) => message,
+            None => return,
+        };
+        let epoch = link.epoch;
+
+        if self.rng.gen_bool(self.config.drop_probability) {
+            self.reset(from, to);
+            return;
+        }
+        if self.rng.gen_bool(self.config.duplicate_probability) {
+            self.in_flight.push(InFlight {
+                from,
+                to,
+                epoch,
+                message: message.clone(),
+            });
+        }
+        self.in_flight.push(InFlight {
+            from,
+            to,
+            epoch,
+            message,
+        });
+    }
+
+    /// Drop the connection between two peers, keeping only the state which survives
+    /// [`SyncState::encode`]. The peers talk again the next time either of them has news.
+    fn reset(&mut self, a: usize, b: usize) {
+        for key in &[(a, b), (b, a)] {
+            let link = self.links.get_mut(key).unwrap();
+            link.sync_state = SyncState::decode(&link.sync_state.encode().unwrap()).unwrap();
+            link.epoch += 1;
+        }
+    }
+
+    fn reconnect(&mut self, a: usize, b: usize) {
+        self.reset(a, b);
+        self.send(a, b);
+        self.send(b, a);
+    }
+}
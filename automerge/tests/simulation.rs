@@ -0,0 +1,78 @@
+use automerge::{
+    simulation::{NetworkConfig, Simulation},
+    InvalidChangeRequest, LocalChange, Path, Primitive, Value,
+};
+use rand::Rng;
+
+fn list_len(sim: &Simulation, peer: usize) -> u32 {
+    match sim.peer(peer).frontend.get_value(&Path::root().key("list")) {
+        Some(Value::List(items)) => items.len() as u32,
+        _ => 0,
+    }
+}
+
+fn random_edits(seed: u64, num_peers: usize, config: NetworkConfig) {
+    let mut sim = Simulation::new(num_peers, seed, config);
+    sim.change::<_, _, InvalidChangeRequest>(0, |doc| {
+        doc.add_change(LocalChange::set(
+            Path::root().key("list"),
+            Value::List(Vec::new()),
+        ))
+    })
+    .unwrap();
+    sim.settle().unwrap();
+
+    for _ in 0..30 {
+        let peer = sim.rng().gen_range(0..num_peers);
+        let len = list_len(&sim, peer);
+        let choice = sim.rng().gen_range(0..3);
+        let value = sim.rng().gen_range(0..100_i64);
+        let index = sim.rng().gen_range(0..=len);
+        sim.change::<_, _, InvalidChangeRequest>(peer, |doc| match choice {
+            0 => doc.add_change(LocalChange::set(
+                Path::root().key(format!("key{}", value % 5)),
+                Value::Primitive(Primitive::Int(value)),
+            )),
+            1 => doc.add_change(LocalChange::insert(
+                Path::root().key("list").index(index),
+                Value::Primitive(Primitive::Int(value)),
+            )),
+            _ if len > 0 => doc.add_change(LocalChange::delete(
+                Path::root().key("list").index(index.min(len - 1)),
+            )),
+            _ => Ok(()),
+        })
+        .unwrap();
+        let steps = sim.rng().gen_range(0..20);
+        sim.run(steps).unwrap();
+    }
+
+    sim.settle().unwrap();
+    sim.assert_converged();
+}
+
+#[test]
+fn reliable_network_converges() {
+    for seed in 0..3 {
+        random_edits(seed, 3, NetworkConfig::reliable());
+    }
+}
+
+#[test]
+fn unreliable_network_converges() {
+    for seed in 0..4 {
+        random_edits(seed, 4, NetworkConfig::default());
+    }
+}
+
+#[test]
+fn very_lossy_network_converges() {
+    let config = NetworkConfig {
+        drop_probability: 0.5,
+        duplicate_probability: 0.3,
+        reorder: true,
+    };
+    for seed in 0..3 {
+        random_edits(seed, 3, config.clone());
+    }
+}
@@ -0,0 +1,420 @@
+use automerge::{Backend, Frontend, InvalidChangeRequest, LocalChange, Path, Value};
+use automerge_backend::{
+    AutomergeError, DocumentSet, DocumentSetMessage, DocumentSetSyncState, EphemeralManager,
+    EphemeralMessage, SyncMessage, SyncState,
+};
+use automerge_protocol as amp;
+use automerge_protocol::ActorId;
+
+fn sync(
+    a: &mut Backend,
+    b: &mut Backend,
+    a_sync_state: &mut SyncState,
+    b_sync_state: &mut SyncState,
+) -> Vec<SyncMessage> {
+    const MAX_ITER: u32 = 10;
+    let mut messages = Vec::new();
+    let mut i = 0;
+    loop {
+        let a_to_b_msg = a.generate_sync_message(a_sync_state);
+        if let Some(message) = a_to_b_msg.clone() {
+            let message = SyncMessage::decode(&message.encode().unwrap()).unwrap();
+            messages.push(message.clone());
+            b.receive_sync_message(b_sync_state, message).unwrap();
+        }
+
+        let b_to_a_msg = b.generate_sync_message(b_sync_state);
+        if let Some(message) = b_to_a_msg.clone() {
+            let message = SyncMessage::decode(&message.encode().unwrap()).unwrap();
+            messages.push(message.clone());
+            a.receive_sync_message(a_sync_state, message).unwrap();
+        }
+
+        i += 1;
+        if i > MAX_ITER {
+            panic!(
+                "Did not synchronize within {} iterations. Do you have a bug causing an infinite loop?",
+                MAX_ITER
+            )
+        }
+        if a_to_b_msg.is_none() && b_to_a_msg.is_none() {
+            break;
+        }
+    }
+    messages
+}
+
+fn backend_with_changes(count: u32) -> Backend {
+    let mut backend = Backend::new();
+    let mut frontend = Frontend::new_with_timestamper(Box::new(|| None));
+    for i in 0..count {
+        let change = frontend
+            .change::<_, _, InvalidChangeRequest>(None, |d| {
+                d.add_change(LocalChange::set(
+                    Path::root().key("counter"),
+                    Value::from(i64::from(i)),
+                ))
+            })
+            .unwrap()
+            .1
+            .unwrap();
+        let (patch, _) = backend.apply_local_change(change).unwrap();
+        frontend.apply_patch(patch).unwrap();
+    }
+    backend
+}
+
+#[test]
+fn new_peer_is_bootstrapped_with_a_snapshot() {
+    let mut n1 = backend_with_changes(10);
+    let mut n2 = Backend::new();
+    let mut s1 = SyncState {
+        bootstrap_with_snapshot: true,
+        ..SyncState::default()
+    };
+    let mut s2 = SyncState::default();
+
+    let messages = sync(&mut n1, &mut n2, &mut s1, &mut s2);
+
+    assert_eq!(n1.get_heads(), n2.get_heads());
+    assert_eq!(n1.get_patch().unwrap(), n2.get_patch().unwrap());
+    let snapshots = messages
+        .iter()
+        .filter(|message| message.document.is_some())
+        .collect::<Vec<_>>();
+    assert_eq!(snapshots.len(), 1);
+    assert!(snapshots[0].changes.is_empty());
+    assert!(messages.iter().all(|message| message.changes.is_empty()));
+}
+
+#[test]
+fn snapshot_is_not_sent_unless_enabled() {
+    let mut n1 = backend_with_changes(10);
+    let mut n2 = Backend::new();
+    let mut s1 = SyncState::default();
+    let mut s2 = SyncState::default();
+
+    let messages = sync(&mut n1, &mut n2, &mut s1, &mut s2);
+
+    assert_eq!(n1.get_heads(), n2.get_heads());
+    assert!(messages.iter().all(|message| message.document.is_none()));
+    assert_eq!(
+        messages
+            .iter()
+            .map(|message| message.changes.len())
+            .sum::<usize>(),
+        10
+    );
+}
+
+#[test]
+fn snapshot_is_not_sent_to_a_peer_with_history() {
+    let mut n1 = backend_with_changes(10);
+    let mut n2 = backend_with_changes(1);
+    let mut s1 = SyncState {
+        bootstrap_with_snapshot: true,
+        ..SyncState::default()
+    };
+    let mut s2 = SyncState::default();
+
+    let messages = sync(&mut n1, &mut n2, &mut s1, &mut s2);
+
+    assert_eq!(n1.get_heads(), n2.get_heads());
+    assert!(messages.iter().all(|message| message.document.is_none()));
+}
+
+#[test]
+fn document_sets_replicate_every_document() {
+    let mut set1 = DocumentSet::new();
+    set1.insert("a".to_string(), backend_with_changes(3));
+    set1.insert("b".to_string(), backend_with_changes(5));
+    let mut set2 = DocumentSet::new();
+    set2.insert("c".to_string(), backend_with_changes(2));
+    let mut s1 = DocumentSetSyncState::default();
+    let mut s2 = DocumentSetSyncState::default();
+
+    let mut i = 0;
+    loop {
+        let one_to_two = set1.generate_sync_message(&mut s1);
+        if let Some(message) = one_to_two.clone() {
+            let message = DocumentSetMessage::decode(&message.encode().unwrap()).unwrap();
+            set2.receive_sync_message(&mut s2, message).unwrap();
+        }
+        let two_to_one = set2.generate_sync_message(&mut s2);
+        if let Some(message) = two_to_one.clone() {
+            let message = DocumentSetMessage::decode(&message.encode().unwrap()).unwrap();
+            set1.receive_sync_message(&mut s1, message).unwrap();
+        }
+        i += 1;
+        assert!(i <= 10, "Did not synchronize within 10 iterations");
+        if one_to_two.is_none() && two_to_one.is_none() {
+            break;
+        }
+    }
+
+    assert_eq!(set1.document_ids(), vec!["a", "b", "c"]);
+    assert_eq!(set2.document_ids(), vec!["a", "b", "c"]);
+    for id in set1.document_ids() {
+        assert_eq!(
+            set1.get(&id).unwrap().get_heads(),
+            set2.get(&id).unwrap().get_heads()
+        );
+    }
+    assert_eq!(
+        s1.their_documents,
+        Some(
+            vec!["a".to_string(), "b".to_string(), "c".to_string()]
+                .into_iter()
+                .collect()
+        )
+    );
+    // set2 announced every document set1 ended up with, so set1 never needed to announce again
+    assert_eq!(
+        s2.their_documents,
+        Some(vec!["a".to_string(), "b".to_string()].into_iter().collect())
+    );
+}
+
+#[test]
+fn document_set_messages_with_huge_counts_fail_to_decode() {
+    // type byte, no document list, then a message count of u32::MAX as a LEB128 varint
+    let bytes = [0x44, 0x00, 0xff, 0xff, 0xff, 0xff, 0x0f];
+    assert!(DocumentSetMessage::decode(&bytes).is_err());
+}
+
+#[test]
+fn ephemeral_messages_replace_and_expire() {
+    let alice = ActorId::random();
+    let bob = ActorId::random();
+    let mut alice_presence = EphemeralManager::new(alice.clone());
+    let mut bob_presence = EphemeralManager::new(bob);
+
+    let first = alice_presence.broadcast(b"cursor:1".to_vec(), 1000, 0);
+    let second = alice_presence.broadcast(b"cursor:2".to_vec(), 1000, 10);
+
+    let second = EphemeralMessage::decode(&second.encode().unwrap()).unwrap();
+    assert!(bob_presence.receive(second.clone(), 20));
+    assert!(!bob_presence.receive(second, 20));
+    assert!(!bob_presence.receive(first, 20));
+    assert_eq!(bob_presence.get(&alice, 20), Some(&b"cursor:2"[..]));
+
+    assert_eq!(bob_presence.get(&alice, 1010), None);
+    assert_eq!(bob_presence.prune(1010), vec![alice]);
+    assert!(bob_presence.live_messages(1010).is_empty());
+}
+
+#[test]
+fn ephemeral_messages_travel_with_document_sets() {
+    let alice = ActorId::random();
+    let mut alice_presence = EphemeralManager::new(alice.clone());
+    let mut bob_presence = EphemeralManager::new(ActorId::random());
+    let mut alice_set = DocumentSet::new();
+    alice_set.insert("a".to_string(), backend_with_changes(1));
+    let mut bob_set = DocumentSet::new();
+    let mut alice_state = DocumentSetSyncState::default();
+    let mut bob_state = DocumentSetSyncState::default();
+
+    alice_state.queue_ephemeral(alice_presence.broadcast(b"online".to_vec(), 1000, 0));
+    let message = alice_set.generate_sync_message(&mut alice_state).unwrap();
+    let message = DocumentSetMessage::decode(&message.encode().unwrap()).unwrap();
+    bob_set
+        .receive_sync_message(&mut bob_state, message)
+        .unwrap();
+    for message in bob_state.take_ephemeral() {
+        assert!(bob_presence.receive(message, 10));
+    }
+    assert_eq!(bob_presence.get(&alice, 10), Some(&b"online"[..]));
+
+    // with the documents in sync a queued ephemeral message is still sent on its own
+    let mut i = 0;
+    while let Some(message) = bob_set.generate_sync_message(&mut bob_state) {
+        alice_set
+            .receive_sync_message(&mut alice_state, message)
+            .unwrap();
+        if let Some(message) = alice_set.generate_sync_message(&mut alice_state) {
+            bob_set
+                .receive_sync_message(&mut bob_state, message)
+                .unwrap();
+        }
+        i += 1;
+        assert!(i <= 10, "Did not synchronize within 10 iterations");
+    }
+    alice_state.queue_ephemeral(alice_presence.broadcast(b"away".to_vec(), 1000, 20));
+    let message = alice_set.generate_sync_message(&mut alice_state).unwrap();
+    assert!(message.messages.is_empty());
+    bob_set
+        .receive_sync_message(&mut bob_state, message)
+        .unwrap();
+    assert_eq!(bob_state.take_ephemeral().len(), 1);
+}
+
+/// A change setting `key` which overwrites the change with the previous `seq`. This is much
+/// cheaper than going through a frontend, which matters for very long sessions.
+fn overwrite_change(actor: &ActorId, key: &str, seq: u64) -> amp::Change {
+    amp::Change {
+        actor_id: actor.clone(),
+        seq,
+        start_op: seq,
+        time: 0,
+        message: None,
+        hash: None,
+        deps: Vec::new(),
+        operations: vec![amp::Op {
+            action: amp::OpType::Set(amp::ScalarValue::Uint(seq)),
+            obj: amp::ObjectId::Root,
+            key: key.into(),
+            insert: false,
+            pred: if seq == 1 {
+                Vec::new().into()
+            } else {
+                vec![actor.op_id_at(seq - 1)].into()
+            },
+        }],
+        extra_bytes: Vec::new(),
+    }
+}
+
+/// Two peers run a session of `num_changes` changes, each replying to the other one round late,
+/// and check that neither remembers more than the changes still in flight. When `concurrent` is
+/// false only the first peer makes changes.
+fn check_sent_hashes_stay_bounded(num_changes: u64, concurrent: bool) {
+    let (mut n1, mut n2) = (Backend::new(), Backend::new());
+    let (mut s1, mut s2) = (SyncState::default(), SyncState::default());
+    let (actor1, actor2) = (ActorId::random(), ActorId::random());
+    let (mut to_n1, mut to_n2): (Option<SyncMessage>, Option<SyncMessage>) = (None, None);
+    let changes_per_round = if concurrent { 2 } else { 1 };
+
+    for round in 0..num_changes / changes_per_round + 3 {
+        // the last few rounds only deliver the messages still in flight
+        if round < num_changes / changes_per_round {
+            let seq = round + 1;
+            n1.apply_local_change(overwrite_change(&actor1, "n1", seq))
+                .unwrap();
+            if concurrent {
+                n2.apply_local_change(overwrite_change(&actor2, "n2", seq))
+                    .unwrap();
+            }
+        }
+
+        if let Some(message) = to_n1.take() {
+            n1.receive_sync_message(&mut s1, message).unwrap();
+        }
+        if let Some(message) = to_n2.take() {
+            n2.receive_sync_message(&mut s2, message).unwrap();
+        }
+        to_n2 = n1.generate_sync_message(&mut s1);
+        to_n1 = n2.generate_sync_message(&mut s2);
+
+        assert!(
+            s1.sent_hashes.len() <= 4 && s2.sent_hashes.len() <= 4,
+            "sent {} and {} unacknowledged changes after {} rounds",
+            s1.sent_hashes.len(),
+            s2.sent_hashes.len(),
+            round
+        );
+    }
+    assert_eq!(n1.get_heads(), n2.get_heads());
+}
+
+#[test]
+fn sent_hashes_are_pruned_once_acknowledged() {
+    check_sent_hashes_stay_bounded(1_000, true);
+}
+
+#[test]
+fn sent_hashes_stay_bounded_when_one_peer_writes() {
+    check_sent_hashes_stay_bounded(5_000, false);
+}
+
+#[test]
+#[ignore = "takes minutes without optimisations, run with --release -- --ignored"]
+fn sent_hashes_stay_bounded_for_a_million_changes() {
+    check_sent_hashes_stay_bounded(1_000_000, false);
+}
+
+#[test]
+fn authenticated_messages_are_verified() {
+    let mut n1 = backend_with_changes(3);
+    let mut n2 = Backend::new();
+    let (mut s1, mut s2) = (SyncState::default(), SyncState::default());
+    let key = b"a secret shared by n1 and n2";
+    let (a1, a2) = (ActorId::from(&[1][..]), ActorId::from(&[2][..]));
+
+    for _ in 0..5 {
+        if let Some(bytes) = n1
+            .generate_authenticated_sync_message(&mut s1, key, &a1)
+            .unwrap()
+        {
+            n2.receive_authenticated_sync_message(&mut s2, key, &a1, &bytes)
+                .unwrap();
+        }
+        if let Some(bytes) = n2
+            .generate_authenticated_sync_message(&mut s2, key, &a2)
+            .unwrap()
+        {
+            n1.receive_authenticated_sync_message(&mut s1, key, &a2, &bytes)
+                .unwrap();
+        }
+    }
+    assert_eq!(n1.get_heads(), n2.get_heads());
+
+    let mut n3 = Backend::new();
+    let bytes = n1
+        .generate_authenticated_sync_message(&mut SyncState::default(), key, &a1)
+        .unwrap()
+        .unwrap();
+    let mut tampered = bytes.clone();
+    tampered[1] ^= 1;
+    for (key, sender, bytes) in [
+        (&b"the wrong secret"[..], &a1, &bytes[..]),
+        (&key[..], &a2, &bytes[..]),
+        (&key[..], &a1, &tampered[..]),
+        (&key[..], &a1, &bytes[..10]),
+    ]
+    .iter()
+    {
+        assert!(matches!(
+            n3.receive_authenticated_sync_message(&mut SyncState::default(), key, sender, bytes),
+            Err(AutomergeError::SyncMessageAuthenticationFailed)
+        ));
+    }
+    assert!(n3.get_heads().is_empty());
+
+    // a message can only be received once per sync state
+    let mut s3 = SyncState::default();
+    n3.receive_authenticated_sync_message(&mut s3, key, &a1, &bytes)
+        .unwrap();
+    assert!(matches!(
+        n3.receive_authenticated_sync_message(&mut s3, key, &a1, &bytes),
+        Err(AutomergeError::SyncMessageReplayed { counter: 1 })
+    ));
+}
+
+#[test]
+fn authenticated_messages_sent_back_to_their_sender_are_rejected() {
+    let mut n1 = backend_with_changes(3);
+    let n2 = backend_with_changes(2);
+    let (mut s1, mut s2) = (SyncState::default(), SyncState::default());
+    let key = b"a secret shared by n1 and n2";
+    let (a1, a2) = (ActorId::from(&[1][..]), ActorId::from(&[2][..]));
+
+    let from_n2 = n2
+        .generate_authenticated_sync_message(&mut s2, key, &a2)
+        .unwrap()
+        .unwrap();
+    n1.receive_authenticated_sync_message(&mut s1, key, &a2, &from_n2)
+        .unwrap();
+    let heads = n1.get_heads();
+
+    // the counter of this message is above that of the last one n1 accepted from n2
+    s1.authenticated_sent = 5;
+    let reflected = n1
+        .generate_authenticated_sync_message(&mut s1, key, &a1)
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        n1.receive_authenticated_sync_message(&mut s1, key, &a2, &reflected),
+        Err(AutomergeError::SyncMessageAuthenticationFailed)
+    ));
+    assert_eq!(n1.get_heads(), heads);
+}